@@ -0,0 +1,100 @@
+use lofty::file::{TaggedFile, TaggedFileExt};
+use lofty::picture::{MimeType, PictureType};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Directory extracted covers are cached in, e.g. `~/.cache/mp3_player/covers`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("mp3_player").join("covers"))
+}
+
+/// Save the embedded cover of `track` (the front cover if tagged, else the first
+/// picture) from its already read `tagged_file` into `cache_dir` and return its
+/// path. Tracks without art yield `None`. The file name depends on the track's
+/// path and mtime, so a retagged track gets its new cover.
+pub fn extract_to(track: &Path, tagged_file: &TaggedFile, cache_dir: &Path) -> Option<PathBuf> {
+    let picture = tagged_file
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures())
+        .min_by_key(|picture| picture.pic_type() != PictureType::CoverFront)?;
+
+    let extension = match picture.mime_type() {
+        Some(MimeType::Png) => "png",
+        Some(MimeType::Jpeg) => "jpg",
+        Some(MimeType::Gif) => "gif",
+        Some(MimeType::Bmp) => "bmp",
+        _ => "img",
+    };
+    let mut hasher = DefaultHasher::new();
+    track.hash(&mut hasher);
+    std::fs::metadata(track)
+        .ok()?
+        .modified()
+        .ok()?
+        .hash(&mut hasher);
+    let cover_path = cache_dir.join(format!("{:016x}.{}", hasher.finish(), extension));
+
+    if !cover_path.exists() {
+        std::fs::create_dir_all(cache_dir).ok()?;
+        std::fs::write(&cover_path, picture.data()).ok()?;
+    }
+    Some(cover_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lofty::config::WriteOptions;
+    use lofty::picture::Picture;
+    use lofty::tag::{Tag, TagExt, TagType};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mp3_player_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_extract_embedded_cover() {
+        let dir = temp_dir("cover_art");
+        let track = dir.join("tagged.mp3");
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("test_220hz.mp3"),
+            &track,
+        )
+        .unwrap();
+
+        let png = b"\x89PNG\r\n\x1a\nfake image data".to_vec();
+        let mut tag = Tag::new(TagType::Id3v2);
+        tag.push_picture(Picture::new_unchecked(
+            PictureType::CoverFront,
+            Some(MimeType::Png),
+            None,
+            png.clone(),
+        ));
+        tag.save_to_path(&track, WriteOptions::default()).unwrap();
+
+        let tagged_file = lofty::read_from_path(&track).unwrap();
+        let cover = extract_to(&track, &tagged_file, &dir.join("covers")).unwrap();
+        assert_eq!(cover.extension().unwrap(), "png");
+        assert_eq!(std::fs::read(&cover).unwrap(), png);
+
+        // Retagging changes the mtime, so the old cover isn't reused
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        tag.save_to_path(&track, WriteOptions::default()).unwrap();
+        let tagged_file = lofty::read_from_path(&track).unwrap();
+        let retagged = extract_to(&track, &tagged_file, &dir.join("covers")).unwrap();
+        assert_ne!(retagged, cover);
+    }
+
+    #[test]
+    fn test_track_without_art() {
+        let dir = temp_dir("no_cover_art");
+        let track = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_880hz.mp3");
+        let tagged_file = lofty::read_from_path(&track).unwrap();
+        assert_eq!(extract_to(&track, &tagged_file, &dir), None);
+    }
+}
@@ -1,5 +1,7 @@
+mod cover_art;
 mod player;
 mod playlist;
+mod track_scan;
 mod ui;
 
 use anyhow::Result;
@@ -10,10 +12,29 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::{io, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::Mutex;
 
-use crate::{player::Player, playlist::Playlist, ui::UI};
+use crate::{
+    player::{Player, DEFAULT_VOLUME},
+    playlist::{Playlist, SortMode, DEFAULT_MAX_DEPTH},
+    ui::UI,
+};
+
+/// How far the seek keys jump
+#[derive(Clone, Copy, Debug)]
+struct SeekSteps {
+    small: Duration,
+    large: Duration,
+}
+
+/// Where the `w` key saves the playlist
+const PLAYLIST_FILE: &str = "playlist.m3u";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -29,6 +50,46 @@ struct Args {
     /// Shuffle playlist
     #[arg(short, long)]
     shuffle: bool,
+
+    /// Load tracks from an M3U playlist file
+    #[arg(short, long, value_name = "FILE")]
+    playlist: Option<PathBuf>,
+
+    /// Order of tracks loaded from a directory
+    #[arg(long, value_enum, default_value_t = SortMode::Natural)]
+    sort: SortMode,
+
+    /// Only load the files directly inside the directory
+    #[arg(long)]
+    no_recursive: bool,
+
+    /// How many directory levels to descend into
+    #[arg(long, value_name = "DEPTH", default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+
+    /// Follow symbolic links while loading a directory
+    #[arg(long)]
+    follow_links: bool,
+
+    /// Stop playback after this many minutes, 0 for no sleep timer
+    #[arg(long, value_name = "MINUTES", default_value_t = 0)]
+    sleep: u64,
+
+    /// Stop at the end of the sleep timer without fading out
+    #[arg(long)]
+    no_sleep_fade: bool,
+
+    /// Starting volume from 0.0 to 1.0, kept across track changes
+    #[arg(long, value_name = "LEVEL", default_value_t = DEFAULT_VOLUME)]
+    volume: f32,
+
+    /// Seconds the `,`/`.` keys seek by
+    #[arg(long, value_name = "SECONDS", default_value_t = 5)]
+    seek_step: u64,
+
+    /// Seconds the `<`/`>` (Shift+`,`/`.`) keys seek by
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    large_seek_step: u64,
 }
 
 #[tokio::main]
@@ -44,6 +105,13 @@ async fn main() -> Result<()> {
 
     // Create playlist
     let mut playlist = Playlist::new();
+    playlist.set_sort_mode(args.sort);
+    playlist.set_recursive(!args.no_recursive, args.max_depth);
+    playlist.set_follow_links(args.follow_links);
+
+    if let Some(m3u) = args.playlist.clone() {
+        playlist.load_m3u(m3u)?;
+    }
 
     // Load files from path if provided
     if let Some(path) = args.path {
@@ -52,7 +120,7 @@ async fn main() -> Result<()> {
         } else if path.is_dir() {
             playlist.load_directory(path)?;
         }
-    } else {
+    } else if args.playlist.is_none() {
         // Load from current directory if no path specified
         playlist.load_directory(std::env::current_dir()?)?;
     }
@@ -62,7 +130,11 @@ async fn main() -> Result<()> {
     }
 
     // Create player
-    let player = Arc::new(Mutex::new(Player::new()));
+    let mut player = Player::new();
+    player.set_volume(args.volume).await?;
+    player.set_sleep_fade(!args.no_sleep_fade);
+    player.set_sleep_timer(Some(Duration::from_secs(args.sleep * 60)));
+    let player = Arc::new(Mutex::new(player));
     let ui = Arc::new(Mutex::new(UI::new()));
 
     // Start playing if autoplay is enabled
@@ -75,7 +147,11 @@ async fn main() -> Result<()> {
     }
 
     // Main event loop
-    let result = run_app(&mut terminal, player, ui, &mut playlist).await;
+    let seek_steps = SeekSteps {
+        small: Duration::from_secs(args.seek_step),
+        large: Duration::from_secs(args.large_seek_step),
+    };
+    let result = run_app(&mut terminal, player, ui, &mut playlist, seek_steps).await;
 
     // Restore terminal
     disable_raw_mode()?;
@@ -94,8 +170,18 @@ async fn run_app(
     player: Arc<Mutex<Player>>,
     ui: Arc<Mutex<UI>>,
     playlist: &mut Playlist,
+    seek_steps: SeekSteps,
 ) -> Result<()> {
     loop {
+        playlist.apply_scans();
+        {
+            let mut player_lock = player.lock().await;
+            player_lock.check_sleep_timer(Instant::now()).await?;
+            if let Some(message) = auto_advance(&mut player_lock, playlist).await? {
+                ui.lock().await.show_message(message);
+            }
+        }
+
         // Update UI state
         {
             let player_lock = player.lock().await;
@@ -105,22 +191,45 @@ async fn run_app(
                 player_lock.get_position(),
                 player_lock.get_duration(),
                 player_lock.get_volume(),
+                player_lock.is_muted(),
             );
-            ui_lock.update_playlist(playlist.get_tracks(), playlist.current_index());
-            if let Some(track) = playlist.current() {
-                ui_lock.update_current_track(Some(track.clone()));
-            }
+            ui_lock.update_playlist(
+                playlist.get_tracks(),
+                playlist.current_index(),
+                playlist.selected_index(),
+            );
+            ui_lock.update_repeat_mode(playlist.repeat_mode());
+            ui_lock.update_sleep_timer(player_lock.sleep_remaining());
+            ui_lock.update_current_track(playlist.current().cloned());
         }
 
         // Draw UI
         {
-            let ui_lock = ui.lock().await;
+            let mut ui_lock = ui.lock().await;
             terminal.draw(|f| ui_lock.draw(f))?;
         }
 
         // Handle input
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
+                let mut ui_lock = ui.lock().await;
+                ui_lock.clear_message();
+                if ui_lock.is_searching() {
+                    // The search box takes all keys until confirmed or cancelled
+                    match key.code {
+                        KeyCode::Enter => ui_lock.confirm_search(),
+                        KeyCode::Esc => ui_lock.cancel_search(),
+                        KeyCode::Backspace => ui_lock.pop_search_char(),
+                        KeyCode::Char(c) => ui_lock.push_search_char(c),
+                        _ => {}
+                    }
+                    if let Some(index) = ui_lock.visible_selection() {
+                        playlist.select(index);
+                    }
+                    continue;
+                }
+                drop(ui_lock);
+
                 match key.code {
                     KeyCode::Char('q') | KeyCode::Char('Q') => break,
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
@@ -135,6 +244,7 @@ async fn run_app(
                     }
                     KeyCode::Enter => {
                         // Play selected track
+                        playlist.play_selected();
                         if let Some(track) = playlist.current() {
                             let mut player_lock = player.lock().await;
                             player_lock.load_track(track.path.clone()).await?;
@@ -161,13 +271,49 @@ async fn run_app(
                             }
                         }
                     }
+                    KeyCode::Char(c @ (',' | '.' | '<' | '>')) => {
+                        // Seek, Shift (`<`/`>`) for a large step
+                        let large =
+                            matches!(c, '<' | '>') || key.modifiers.contains(KeyModifiers::SHIFT);
+                        let step = if large {
+                            seek_steps.large
+                        } else {
+                            seek_steps.small
+                        };
+                        let forward = matches!(c, '.' | '>');
+                        let mut player_lock = player.lock().await;
+                        // A source that can't seek keeps playing where it was
+                        let _ = player_lock.seek_relative(step, forward).await;
+                    }
+                    KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                        // Move the selected track up
+                        playlist.move_up(playlist.selected_index());
+                    }
+                    KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                        // Move the selected track down
+                        playlist.move_down(playlist.selected_index());
+                    }
                     KeyCode::Up => {
                         // Move selection up in playlist
-                        playlist.move_selection_up();
+                        let ui_lock = ui.lock().await;
+                        if !ui_lock.is_flat() {
+                            if let Some(index) = ui_lock.select_relative(false) {
+                                playlist.select(index);
+                            }
+                        } else {
+                            playlist.move_selection_up();
+                        }
                     }
                     KeyCode::Down => {
                         // Move selection down in playlist
-                        playlist.move_selection_down();
+                        let ui_lock = ui.lock().await;
+                        if !ui_lock.is_flat() {
+                            if let Some(index) = ui_lock.select_relative(true) {
+                                playlist.select(index);
+                            }
+                        } else {
+                            playlist.move_selection_down();
+                        }
                     }
                     KeyCode::Char('+') | KeyCode::Char('=') => {
                         // Volume up
@@ -181,15 +327,57 @@ async fn run_app(
                         let current = player_lock.get_volume();
                         player_lock.set_volume((current - 0.1).max(0.0)).await?;
                     }
+                    KeyCode::Char('m') | KeyCode::Char('M') => {
+                        // Mute/unmute, keeping the volume level
+                        let mut player_lock = player.lock().await;
+                        player_lock.toggle_mute().await?;
+                    }
                     KeyCode::Char('s') | KeyCode::Char('S') => {
                         // Stop
                         let mut player_lock = player.lock().await;
                         player_lock.stop().await?;
                     }
+                    KeyCode::Char('d') | KeyCode::Delete => {
+                        // Remove the selected track, stopping it if it is playing
+                        let index = playlist.selected_index();
+                        let was_current = playlist.current_index() == Some(index);
+                        if playlist.remove(index).is_some() && was_current {
+                            let mut player_lock = player.lock().await;
+                            player_lock.stop().await?;
+                        }
+                    }
+                    KeyCode::Char('w') | KeyCode::Char('W') => {
+                        // Save the playlist
+                        if let Err(e) = playlist.save_m3u(Path::new(PLAYLIST_FILE)) {
+                            let mut ui_lock = ui.lock().await;
+                            ui_lock.show_message(format!("Failed to save playlist: {:#}", e));
+                        }
+                    }
                     KeyCode::Char('r') | KeyCode::Char('R') => {
                         // Toggle repeat
                         playlist.toggle_repeat();
                     }
+                    KeyCode::Char('t') | KeyCode::Char('T') => {
+                        // Cycle the sleep timer through the presets and off
+                        let mut player_lock = player.lock().await;
+                        let next = player::next_sleep_preset(player_lock.sleep_remaining());
+                        player_lock.set_sleep_timer(next);
+                    }
+                    KeyCode::Char('/') => {
+                        // Filter the playlist by name
+                        let mut ui_lock = ui.lock().await;
+                        ui_lock.start_search();
+                    }
+                    KeyCode::Char('g') | KeyCode::Char('G') => {
+                        // Cycle playlist grouping
+                        let mut ui_lock = ui.lock().await;
+                        ui_lock.toggle_group_mode();
+                    }
+                    KeyCode::Char('c') => {
+                        // Collapse/expand the selected group
+                        let mut ui_lock = ui.lock().await;
+                        ui_lock.toggle_collapse();
+                    }
                     KeyCode::Char('h') | KeyCode::Char('H') | KeyCode::Char('?') => {
                         // Toggle help
                         let mut ui_lock = ui.lock().await;
@@ -203,3 +391,112 @@ async fn run_app(
 
     Ok(())
 }
+
+/// Move on once the current track played to its end, like the server's
+/// `play_next`. Tracks that fail to load are skipped, the returned message
+/// names the last one. At the end of the playlist (with repeat off) playback
+/// stops.
+async fn auto_advance(player: &mut Player, playlist: &mut Playlist) -> Result<Option<String>> {
+    if !player.has_ended() {
+        return Ok(None);
+    }
+
+    let mut skipped = None;
+    // At most one pass over the playlist, so a playlist of bad files can't spin
+    for _ in 0..playlist.len() {
+        if !playlist.advance() {
+            break;
+        }
+        let Some(track) = playlist.current() else {
+            break;
+        };
+        match player.load_track(track.path.clone()).await {
+            Ok(()) => {
+                player.play().await?;
+                return Ok(skipped);
+            }
+            Err(e) => skipped = Some(format!("Skipped {}: {:#}", track.name, e)),
+        }
+    }
+    player.stop().await?;
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_auto_advance_stops_at_end_of_playlist() {
+        let mut playlist = Playlist::new();
+        let dir = std::env::temp_dir().join("mp3_player_auto_advance");
+        std::fs::create_dir_all(&dir).unwrap();
+        let last = dir.join("last.mp3");
+        std::fs::write(&last, b"").unwrap();
+        playlist.add_file(last.clone()).unwrap();
+
+        let mut player = Player::with_finished_track(last);
+        assert_eq!(
+            auto_advance(&mut player, &mut playlist).await.unwrap(),
+            None
+        );
+
+        assert!(!player.has_ended());
+        assert_eq!(player.get_current_track(), None);
+        assert_eq!(playlist.current_index(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_auto_advance_waits_for_track_to_end() {
+        let mut playlist = Playlist::new();
+        let mut player = Player::new();
+        auto_advance(&mut player, &mut playlist).await.unwrap();
+        assert_eq!(playlist.current_index(), None);
+    }
+
+    /// A second of 8kHz mono silence as a 16-bit WAV file
+    fn write_wav(path: &Path) {
+        let rate = 8000u32;
+        let data_len = rate * 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&rate.to_le_bytes());
+        wav.extend_from_slice(&(rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        wav.resize(wav.len() + data_len as usize, 0);
+        std::fs::write(path, wav).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auto_advance_skips_to_next_playable_track() {
+        let dir = std::env::temp_dir().join("mp3_player_auto_advance_skip");
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.wav");
+        let bad = dir.join("bad.mp3");
+        let third = dir.join("third.wav");
+        write_wav(&first);
+        std::fs::write(&bad, b"not audio").unwrap();
+        write_wav(&third);
+        let mut playlist = Playlist::new();
+        for path in [&first, &bad, &third] {
+            playlist.add_file(path.clone()).unwrap();
+        }
+
+        let mut player = Player::with_finished_track(first);
+        let message = auto_advance(&mut player, &mut playlist).await.unwrap();
+
+        assert!(message.unwrap().starts_with("Skipped bad"));
+        assert_eq!(playlist.current_index(), Some(2));
+        assert_eq!(player.get_current_track(), Some(third.as_path()));
+        assert!(player.is_playing());
+        assert!(!player.has_ended());
+    }
+}
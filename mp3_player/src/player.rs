@@ -5,16 +5,103 @@ use std::{
     io::BufReader,
     path::{Path, PathBuf},
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+/// Volume a new player starts with, see `--volume`
+pub const DEFAULT_VOLUME: f32 = 0.5;
+
+/// How long before the sleep timer ends the volume starts fading out
+const SLEEP_FADE: Duration = Duration::from_secs(30);
+
+/// Sleep timer lengths in minutes that [`next_sleep_preset`] steps through
+const SLEEP_TIMER_PRESETS: [u64; 4] = [15, 30, 60, 90];
+
+/// The shortest preset longer than `remaining`, or `None` (off) after the longest
+pub fn next_sleep_preset(remaining: Option<Duration>) -> Option<Duration> {
+    let remaining = remaining.unwrap_or_default();
+    SLEEP_TIMER_PRESETS
+        .iter()
+        .map(|minutes| Duration::from_secs(minutes * 60))
+        .find(|preset| *preset > remaining)
+}
+
+/// Volume factor while fading out, from 1.0 at [`SLEEP_FADE`] before the deadline down to 0.0
+fn sleep_fade_factor(remaining: Duration) -> f32 {
+    (remaining.as_secs_f32() / SLEEP_FADE.as_secs_f32()).min(1.0)
+}
+
+/// Move `position` by `step` in either direction, staying within `0..=duration`
+fn seek_target(
+    position: Duration,
+    step: Duration,
+    forward: bool,
+    duration: Option<Duration>,
+) -> Duration {
+    if !forward {
+        return position.saturating_sub(step);
+    }
+    let target = position.saturating_add(step);
+    match duration {
+        Some(duration) => target.min(duration),
+        None => target,
+    }
+}
+
+/// Tracks the playback position from a monotonic clock: the time accumulated
+/// before the last pause or seek, plus the time since playback resumed.
+#[derive(Debug, Default)]
+struct PlaybackClock {
+    accumulated: Duration,
+    resumed_at: Option<Instant>,
+}
+
+impl PlaybackClock {
+    fn position(&self, now: Instant) -> Duration {
+        match self.resumed_at {
+            Some(resumed_at) => self.accumulated + now.saturating_duration_since(resumed_at),
+            None => self.accumulated,
+        }
+    }
+
+    fn resume(&mut self, now: Instant) {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(now);
+        }
+    }
+
+    fn pause(&mut self, now: Instant) {
+        self.accumulated = self.position(now);
+        self.resumed_at = None;
+    }
+
+    fn seek(&mut self, position: Duration, now: Instant) {
+        self.accumulated = position;
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(now);
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 pub struct Player {
     sink: Option<Arc<RwLock<Sink>>>,
     stream: Option<OutputStream>,
     current_track: Option<PathBuf>,
     volume: f32,
-    position: Arc<RwLock<Duration>>,
+    muted: bool,
+    clock: PlaybackClock,
     duration: Option<Duration>,
+    sleep_deadline: Option<Instant>,
+    sleep_fade: bool,
+    /// Applied on top of the volume while the sleep timer fades out
+    fade: f32,
+    /// Load tracks into sinks without an output device
+    #[cfg(test)]
+    detached: bool,
 }
 
 impl Player {
@@ -24,21 +111,37 @@ impl Player {
             stream: None,
             // stream_handle: None,
             current_track: None,
-            volume: 0.5,
-            position: Arc::new(RwLock::new(Duration::from_secs(0))),
+            volume: DEFAULT_VOLUME,
+            muted: false,
+            clock: PlaybackClock::default(),
             duration: None,
+            sleep_deadline: None,
+            sleep_fade: true,
+            fade: 1.0,
+            #[cfg(test)]
+            detached: false,
         }
     }
 
-    pub async fn load_track(&mut self, path: PathBuf) -> Result<()> {
-        // Stop current playback if any
-        self.stop().await?;
+    /// A new sink on the default output, opening the stream on first use
+    fn connect_sink(&mut self) -> Result<Sink> {
+        #[cfg(test)]
+        if self.detached {
+            let (sink, _output) = Sink::new();
+            return Ok(sink);
+        }
 
-        // Create output stream if not exists
         if self.stream.is_none() {
             let stream = OutputStreamBuilder::open_default_stream()?;
             self.stream = Some(stream);
         }
+        let stream_handle = self.stream.as_ref().unwrap();
+        Ok(Sink::connect_new(stream_handle.mixer()))
+    }
+
+    pub async fn load_track(&mut self, path: PathBuf) -> Result<()> {
+        // Stop current playback if any
+        self.stop().await?;
 
         // Load and decode the audio file
         let file = File::open(&path)
@@ -53,17 +156,14 @@ impl Player {
         self.duration = source.total_duration();
 
         // Create new sink and append the source
-        if let Some(ref stream_handle) = self.stream {
-            let sink = Sink::connect_new(stream_handle.mixer());
+        let sink = self.connect_sink()?;
+        sink.set_volume(self.output_volume());
+        sink.append(source);
+        sink.pause(); // Start paused
 
-            sink.set_volume(self.volume);
-            sink.append(source);
-            sink.pause(); // Start paused
-
-            self.sink = Some(Arc::new(RwLock::new(sink)));
-            self.current_track = Some(path);
-            *self.position.write().unwrap() = Duration::from_secs(0);
-        }
+        self.sink = Some(Arc::new(RwLock::new(sink)));
+        self.current_track = Some(path);
+        self.clock.reset();
 
         Ok(())
     }
@@ -72,6 +172,7 @@ impl Player {
         if let Some(ref sink) = self.sink.as_ref() {
             let sink = sink.write().unwrap();
             sink.play();
+            self.clock.resume(Instant::now());
         }
         Ok(())
     }
@@ -80,6 +181,7 @@ impl Player {
         if let Some(ref sink) = self.sink.as_ref() {
             let sink = sink.write().unwrap();
             sink.pause();
+            self.clock.pause(Instant::now());
         }
         Ok(())
     }
@@ -90,7 +192,7 @@ impl Player {
             sink.stop();
         }
         self.current_track = None;
-        *self.position.write().unwrap() = Duration::from_secs(0);
+        self.clock.reset();
         self.duration = None;
         Ok(())
     }
@@ -105,9 +207,11 @@ impl Player {
     }
 
     pub fn get_position(&self) -> Duration {
-        // Note: elapsed() method doesn't exist in rodio 0.19
-        // We would need to track position manually or use a different approach
-        Duration::from_secs(0)
+        let position = self.clock.position(Instant::now());
+        match self.duration {
+            Some(duration) => position.min(duration),
+            None => position,
+        }
     }
 
     pub fn get_duration(&self) -> Option<Duration> {
@@ -121,7 +225,7 @@ impl Player {
             // We'll handle it differently
             match sink.try_seek(position) {
                 Ok(()) => {
-                    *self.position.write().unwrap() = position;
+                    self.clock.seek(position, Instant::now());
                 }
                 Err(_) => {
                     return Err(anyhow::anyhow!("Failed to seek to position"));
@@ -131,12 +235,20 @@ impl Player {
         Ok(())
     }
 
+    /// Seek `step` forwards or backwards from the current position, clamped to the track
+    pub async fn seek_relative(&mut self, step: Duration, forward: bool) -> Result<()> {
+        if self.sink.is_none() {
+            return Ok(());
+        }
+        let target = seek_target(self.get_position(), step, forward, self.duration);
+        self.seek(target).await
+    }
+
+    /// Set the stored volume level. While muted the sink stays silent and the
+    /// new level applies on unmute.
     pub async fn set_volume(&mut self, volume: f32) -> Result<()> {
         self.volume = volume.clamp(0.0, 1.0);
-        if let Some(ref sink) = self.sink.as_ref() {
-            let sink = sink.write().unwrap();
-            sink.set_volume(self.volume);
-        }
+        self.apply_volume();
         Ok(())
     }
 
@@ -144,11 +256,84 @@ impl Player {
         self.volume
     }
 
+    pub async fn toggle_mute(&mut self) -> Result<()> {
+        self.muted = !self.muted;
+        self.apply_volume();
+        Ok(())
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Stop playback once `after` has passed, `None` or a zero duration cancels the timer
+    pub fn set_sleep_timer(&mut self, after: Option<Duration>) {
+        self.sleep_deadline = after
+            .filter(|after| !after.is_zero())
+            .map(|after| Instant::now() + after);
+        self.fade = 1.0;
+        self.apply_volume();
+    }
+
+    /// Whether the sleep timer fades the volume out over its last 30 seconds
+    pub fn set_sleep_fade(&mut self, fade: bool) {
+        self.sleep_fade = fade;
+    }
+
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        self.sleep_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Fade out or stop as the sleep timer requires, returning whether it stopped playback
+    pub async fn check_sleep_timer(&mut self, now: Instant) -> Result<bool> {
+        let deadline = match self.sleep_deadline {
+            Some(deadline) => deadline,
+            None => return Ok(false),
+        };
+
+        let remaining = deadline.saturating_duration_since(now);
+        if remaining.is_zero() {
+            self.sleep_deadline = None;
+            self.fade = 1.0;
+            self.stop().await?;
+            self.apply_volume();
+            return Ok(true);
+        }
+
+        if self.sleep_fade {
+            self.fade = sleep_fade_factor(remaining);
+            self.apply_volume();
+        }
+        Ok(false)
+    }
+
+    /// The volume actually sent to the sink
+    fn output_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume * self.fade
+        }
+    }
+
+    fn apply_volume(&self) {
+        if let Some(ref sink) = self.sink.as_ref() {
+            let sink = sink.write().unwrap();
+            sink.set_volume(self.output_volume());
+        }
+    }
+
     pub fn get_current_track(&self) -> Option<&Path> {
         self.current_track.as_deref()
     }
 
+    /// Whether the loaded track played to its end. A stopped player has no
+    /// current track and never counts as ended.
     pub fn has_ended(&self) -> bool {
+        if self.current_track.is_none() {
+            return false;
+        }
         if let Some(ref sink) = self.sink.as_ref() {
             let sink = sink.read().unwrap();
             sink.empty()
@@ -156,4 +341,141 @@ impl Player {
             false
         }
     }
+
+    /// A player whose track `path` already finished, without opening an output
+    /// device. Tracks loaded into it later never finish.
+    #[cfg(test)]
+    pub fn with_finished_track(path: PathBuf) -> Self {
+        let (sink, _output) = Sink::new();
+        Self {
+            sink: Some(Arc::new(RwLock::new(sink))),
+            current_track: Some(path),
+            detached: true,
+            ..Self::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_excludes_paused_time() {
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+        let mut clock = PlaybackClock::default();
+
+        clock.resume(start);
+        assert_eq!(clock.position(secs(3)), Duration::from_secs(3));
+
+        clock.pause(secs(3));
+        assert_eq!(clock.position(secs(10)), Duration::from_secs(3));
+
+        clock.resume(secs(10));
+        assert_eq!(clock.position(secs(12)), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_mute_keeps_stored_volume() {
+        let mut player = Player::new();
+        player.set_volume(0.7).await.unwrap();
+
+        player.toggle_mute().await.unwrap();
+        assert!(player.is_muted());
+        assert_eq!(player.output_volume(), 0.0);
+        assert_eq!(player.get_volume(), 0.7);
+
+        // Adjusting while muted changes the level restored on unmute
+        player.set_volume(0.4).await.unwrap();
+        assert_eq!(player.output_volume(), 0.0);
+
+        player.toggle_mute().await.unwrap();
+        assert!(!player.is_muted());
+        assert_eq!(player.output_volume(), 0.4);
+    }
+
+    #[tokio::test]
+    async fn test_has_ended() {
+        assert!(!Player::new().has_ended());
+
+        let mut player = Player::with_finished_track(PathBuf::from("done.mp3"));
+        assert!(player.has_ended());
+
+        // Stopping is not the same as reaching the end
+        player.stop().await.unwrap();
+        assert!(!player.has_ended());
+    }
+
+    #[tokio::test]
+    async fn test_sleep_timer_stops_at_deadline() {
+        let mut player = Player::with_finished_track(PathBuf::from("bedtime.mp3"));
+        player.set_volume(0.8).await.unwrap();
+        player.set_sleep_timer(Some(Duration::from_secs(60)));
+        let start = Instant::now();
+
+        assert!(!player.check_sleep_timer(start).await.unwrap());
+        assert_eq!(player.output_volume(), 0.8);
+
+        // Halfway through the fade
+        let fading = start + Duration::from_secs(45);
+        assert!(!player.check_sleep_timer(fading).await.unwrap());
+        assert!((player.output_volume() - 0.4).abs() < 0.05);
+
+        let deadline = start + Duration::from_secs(61);
+        assert!(player.check_sleep_timer(deadline).await.unwrap());
+        assert_eq!(player.get_current_track(), None);
+        assert_eq!(player.sleep_remaining(), None);
+        assert_eq!(player.output_volume(), 0.8);
+    }
+
+    #[test]
+    fn test_sleep_timer_cancel_and_presets() {
+        let mut player = Player::new();
+        player.set_sleep_timer(Some(Duration::from_secs(600)));
+        assert!(player.sleep_remaining().is_some());
+        player.set_sleep_timer(Some(Duration::ZERO));
+        assert_eq!(player.sleep_remaining(), None);
+
+        let minutes = |m: u64| Some(Duration::from_secs(m * 60));
+        assert_eq!(next_sleep_preset(None), minutes(15));
+        assert_eq!(next_sleep_preset(minutes(15)), minutes(30));
+        assert_eq!(next_sleep_preset(minutes(90)), None);
+    }
+
+    #[test]
+    fn test_seek_target_clamps() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            seek_target(secs(10), secs(5), true, Some(secs(60))),
+            secs(15)
+        );
+        assert_eq!(
+            seek_target(secs(10), secs(30), false, Some(secs(60))),
+            secs(0)
+        );
+        assert_eq!(
+            seek_target(secs(50), secs(30), true, Some(secs(60))),
+            secs(60)
+        );
+        assert_eq!(seek_target(secs(50), secs(30), true, None), secs(80));
+    }
+
+    #[test]
+    fn test_clock_seek() {
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+        let mut clock = PlaybackClock::default();
+
+        clock.resume(start);
+        clock.seek(Duration::from_secs(60), secs(5));
+        assert_eq!(clock.position(secs(7)), Duration::from_secs(62));
+
+        clock.pause(secs(7));
+        clock.seek(Duration::from_secs(10), secs(8));
+        assert_eq!(clock.position(secs(20)), Duration::from_secs(10));
+
+        clock.reset();
+        assert_eq!(clock.position(secs(30)), Duration::ZERO);
+    }
 }
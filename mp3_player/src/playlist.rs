@@ -1,7 +1,14 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
 use walkdir::WalkDir;
 
+use crate::cover_art;
+use crate::track_scan::{self, ScanCache, TrackInfo};
+
 #[derive(Clone, Debug)]
 pub struct Track {
     pub path: PathBuf,
@@ -9,6 +16,8 @@ pub struct Track {
     pub artist: Option<String>,
     pub album: Option<String>,
     pub duration: Option<std::time::Duration>,
+    /// Embedded cover art extracted into the cache directory
+    pub cover_art: Option<PathBuf>,
 }
 
 impl Track {
@@ -25,15 +34,86 @@ impl Track {
             artist: None,
             album: None,
             duration: None,
+            cover_art: None,
+        }
+    }
+}
+
+/// What happens when a track ends or playback runs past either end of the playlist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// Stop after the last track
+    #[default]
+    Off,
+    /// Replay the current track when it ends
+    One,
+    /// Wrap around to the first track after the last
+    All,
+}
+
+impl RepeatMode {
+    fn next(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "Off",
+            RepeatMode::One => "One",
+            RepeatMode::All => "All",
         }
     }
 }
 
+/// Order of the tracks added by [`Playlist::load_directory`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortMode {
+    /// By path, comparing runs of digits by value so `Track 2` precedes `Track 10`
+    #[default]
+    Natural,
+    /// By path, character by character
+    Alphabetical,
+    /// Oldest modification time first
+    Modified,
+}
+
+impl SortMode {
+    fn sort(self, files: &mut [PathBuf]) {
+        match self {
+            SortMode::Natural => {
+                files.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()))
+            }
+            SortMode::Alphabetical => files.sort(),
+            SortMode::Modified => files.sort_by_cached_key(|path| {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                (modified, path.clone())
+            }),
+        }
+    }
+}
+
+/// How deep `load_directory` descends by default, guarding against huge trees
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
 pub struct Playlist {
     tracks: Vec<Track>,
     current_index: Option<usize>,
     selected_index: usize,
-    repeat: bool,
+    repeat: RepeatMode,
+    /// Durations and tags arriving from background scans of loaded tracks
+    scans: Vec<Receiver<(PathBuf, TrackInfo)>>,
+    scan_cache: Option<PathBuf>,
+    cover_dir: Option<PathBuf>,
+    sort_mode: SortMode,
+    /// Descend into subdirectories when loading a directory
+    recursive: bool,
+    max_depth: usize,
+    /// Off by default so symlink cycles can't make loading loop
+    follow_links: bool,
 }
 
 impl Playlist {
@@ -42,7 +122,23 @@ impl Playlist {
             tracks: Vec::new(),
             current_index: None,
             selected_index: 0,
-            repeat: false,
+            repeat: RepeatMode::Off,
+            scans: Vec::new(),
+            // Tests never touch the caches in the user's home
+            scan_cache: if cfg!(test) {
+                None
+            } else {
+                ScanCache::default_path()
+            },
+            cover_dir: if cfg!(test) {
+                None
+            } else {
+                cover_art::default_cache_dir()
+            },
+            sort_mode: SortMode::Natural,
+            recursive: true,
+            max_depth: DEFAULT_MAX_DEPTH,
+            follow_links: false,
         }
     }
 
@@ -55,10 +151,11 @@ impl Playlist {
         if let Some(ext) = path.extension() {
             let ext = ext.to_str().unwrap_or("").to_lowercase();
             if matches!(ext.as_str(), "mp3" | "flac" | "ogg" | "wav" | "m4a" | "aac") {
-                self.tracks.push(Track::from_path(path));
+                self.tracks.push(Track::from_path(path.clone()));
                 if self.current_index.is_none() && !self.tracks.is_empty() {
                     self.current_index = Some(0);
                 }
+                self.scan(vec![path]);
             }
         }
 
@@ -72,8 +169,10 @@ impl Playlist {
 
         let mut files = Vec::new();
 
+        let max_depth = if self.recursive { self.max_depth } else { 1 };
         for entry in WalkDir::new(dir)
-            .follow_links(true)
+            .follow_links(self.follow_links)
+            .max_depth(max_depth)
             .into_iter()
             .filter_map(|e| e.ok())
         {
@@ -88,20 +187,132 @@ impl Playlist {
             }
         }
 
-        // Sort files alphabetically
-        files.sort();
+        self.sort_mode.sort(&mut files);
 
-        for file in files {
-            self.tracks.push(Track::from_path(file));
+        for file in &files {
+            self.tracks.push(Track::from_path(file.clone()));
         }
 
         if self.current_index.is_none() && !self.tracks.is_empty() {
             self.current_index = Some(0);
         }
 
+        self.scan(files);
+
         Ok(())
     }
 
+    /// Read durations, tags and covers of `paths` without holding up the UI
+    fn scan(&mut self, paths: Vec<PathBuf>) {
+        self.scans.push(track_scan::spawn(
+            paths,
+            self.scan_cache.clone(),
+            self.cover_dir.clone(),
+        ));
+    }
+
+    /// Copy what background scans found so far into their tracks
+    pub fn apply_scans(&mut self) {
+        let tracks = &mut self.tracks;
+        self.scans.retain(|receiver| loop {
+            match receiver.try_recv() {
+                Ok((path, info)) => {
+                    for track in tracks.iter_mut().filter(|t| t.path == path) {
+                        if info.duration.is_some() {
+                            track.duration = info.duration;
+                        }
+                        track.artist = info.artist.clone();
+                        track.album = info.album.clone();
+                        track.cover_art = info.cover_art.clone();
+                    }
+                }
+                Err(TryRecvError::Empty) => break true,
+                Err(TryRecvError::Disconnected) => break false,
+            }
+        });
+    }
+
+    /// Write the playlist as an extended M3U file. Tracks under the playlist's
+    /// directory are stored relative to it, others with their full path.
+    pub fn save_m3u(&self, path: &Path) -> Result<()> {
+        let base = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        let mut content = String::from("#EXTM3U\n");
+        for track in &self.tracks {
+            let seconds = track.duration.map_or(-1, |d| d.as_secs() as i64);
+            let entry = match base {
+                Some(base) => track.path.strip_prefix(base).unwrap_or(&track.path),
+                None => &track.path,
+            };
+            writeln!(content, "#EXTINF:{},{}", seconds, track.name)?;
+            writeln!(content, "{}", entry.display())?;
+        }
+
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write playlist: {}", path.display()))
+    }
+
+    /// Append the tracks of an M3U file, resolving relative entries against the
+    /// playlist's directory. Entries whose file is missing are skipped.
+    pub fn load_m3u(&mut self, path: PathBuf) -> Result<()> {
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read playlist: {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let mut paths = Vec::new();
+        let mut info: Option<(Option<Duration>, String)> = None;
+        for line in content.lines().map(str::trim) {
+            if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+                info = parse_extinf(extinf);
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let entry = Path::new(line);
+            let track_path = if entry.is_absolute() {
+                entry.to_path_buf()
+            } else {
+                base.join(entry)
+            };
+            if !track_path.exists() {
+                info = None;
+                continue;
+            }
+
+            let mut track = Track::from_path(track_path.clone());
+            paths.push(track_path);
+            if let Some((duration, name)) = info.take() {
+                track.duration = duration;
+                if !name.is_empty() {
+                    track.name = name;
+                }
+            }
+            self.tracks.push(track);
+        }
+
+        if self.current_index.is_none() && !self.tracks.is_empty() {
+            self.current_index = Some(0);
+        }
+        self.scan(paths);
+
+        Ok(())
+    }
+
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        self.sort_mode = sort_mode;
+    }
+
+    /// Load only the directory's own files, or descend up to `max_depth` levels
+    pub fn set_recursive(&mut self, recursive: bool, max_depth: usize) {
+        self.recursive = recursive;
+        self.max_depth = max_depth;
+    }
+
+    pub fn set_follow_links(&mut self, follow_links: bool) {
+        self.follow_links = follow_links;
+    }
+
     pub fn clear(&mut self) {
         self.tracks.clear();
         self.current_index = None;
@@ -131,7 +342,7 @@ impl Playlist {
                 self.current_index = Some(index + 1);
                 self.selected_index = index + 1;
                 true
-            } else if self.repeat {
+            } else if self.repeat != RepeatMode::Off {
                 self.current_index = Some(0);
                 self.selected_index = 0;
                 true
@@ -157,7 +368,7 @@ impl Playlist {
                 self.current_index = Some(index - 1);
                 self.selected_index = index - 1;
                 true
-            } else if self.repeat {
+            } else if self.repeat != RepeatMode::Off {
                 let last = self.tracks.len() - 1;
                 self.current_index = Some(last);
                 self.selected_index = last;
@@ -174,15 +385,79 @@ impl Playlist {
         }
     }
 
+    /// Move the selection up, wrapping from the first track to the last
     pub fn move_selection_up(&mut self) {
-        if !self.tracks.is_empty() && self.selected_index > 0 {
-            self.selected_index -= 1;
+        if self.tracks.is_empty() {
+            return;
         }
+        self.selected_index = if self.selected_index > 0 {
+            self.selected_index - 1
+        } else {
+            self.tracks.len() - 1
+        };
     }
 
+    /// Move the selection down, wrapping from the last track to the first
     pub fn move_selection_down(&mut self) {
-        if !self.tracks.is_empty() && self.selected_index < self.tracks.len() - 1 {
-            self.selected_index += 1;
+        if self.tracks.is_empty() {
+            return;
+        }
+        self.selected_index = (self.selected_index + 1) % self.tracks.len();
+    }
+
+    /// Swap the track at `index` with the one above it. The selection follows the
+    /// moved track and `current_index` keeps pointing at the playing one.
+    pub fn move_up(&mut self, index: usize) -> bool {
+        if index == 0 || index >= self.tracks.len() {
+            return false;
+        }
+        self.swap(index - 1, index);
+        self.selected_index = index - 1;
+        true
+    }
+
+    /// Swap the track at `index` with the one below it, see [`Playlist::move_up`]
+    pub fn move_down(&mut self, index: usize) -> bool {
+        if index + 1 >= self.tracks.len() {
+            return false;
+        }
+        self.swap(index, index + 1);
+        self.selected_index = index + 1;
+        true
+    }
+
+    /// Remove the track at `index`. Removing the current track clears
+    /// `current_index`, the caller is expected to stop playback.
+    pub fn remove(&mut self, index: usize) -> Option<Track> {
+        if index >= self.tracks.len() {
+            return None;
+        }
+        let track = self.tracks.remove(index);
+
+        self.current_index = match self.current_index {
+            Some(i) if i == index => None,
+            Some(i) if i > index => Some(i - 1),
+            other => other,
+        };
+        if index < self.selected_index || self.selected_index >= self.tracks.len() {
+            self.selected_index = self.selected_index.saturating_sub(1);
+        }
+
+        Some(track)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.tracks.swap(a, b);
+        self.current_index = match self.current_index {
+            Some(i) if i == a => Some(b),
+            Some(i) if i == b => Some(a),
+            other => other,
+        };
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.tracks.len() {
+            self.selected_index = index;
         }
     }
 
@@ -216,11 +491,357 @@ impl Playlist {
         self.tracks.len()
     }
 
+    /// Cycle the repeat mode Off -> All -> One -> Off
     pub fn toggle_repeat(&mut self) {
-        self.repeat = !self.repeat;
+        self.repeat = self.repeat.next();
     }
 
-    pub fn is_repeat(&self) -> bool {
+    pub fn repeat_mode(&self) -> RepeatMode {
         self.repeat
     }
+
+    /// Move on after the current track finished playing. Unlike [`Playlist::next`],
+    /// `RepeatMode::One` stays on the current track.
+    pub fn advance(&mut self) -> bool {
+        if self.repeat == RepeatMode::One && self.current().is_some() {
+            return true;
+        }
+        self.next()
+    }
+}
+
+/// Compare strings case-insensitively, treating each run of digits as one number
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        let (a_char, b_char) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) => (*a_char, *b_char),
+        };
+
+        if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let a_number = take_digits(&mut a_chars);
+            let b_number = take_digits(&mut b_chars);
+            let a_value = a_number.trim_start_matches('0');
+            let b_value = b_number.trim_start_matches('0');
+            let ordering = a_value
+                .len()
+                .cmp(&b_value.len())
+                .then_with(|| a_value.cmp(b_value));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            continue;
+        }
+
+        let ordering = a_char.to_lowercase().cmp(b_char.to_lowercase());
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a_chars.next();
+        b_chars.next();
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Parse the `<seconds>,<title>` part of an `#EXTINF` line, negative meaning unknown
+fn parse_extinf(extinf: &str) -> Option<(Option<Duration>, String)> {
+    let (seconds, name) = extinf.split_once(',')?;
+    let duration = seconds
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|s| *s >= 0.0)
+        .map(Duration::from_secs_f64);
+    Some((duration, name.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mp3_player_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_m3u_round_trip() {
+        let dir = temp_dir("m3u_round_trip");
+        std::fs::create_dir_all(dir.join("album")).unwrap();
+        let outside = temp_dir("m3u_round_trip_outside");
+        let files = [dir.join("album/one.mp3"), outside.join("two.mp3")];
+        let mut playlist = Playlist::new();
+        for file in &files {
+            std::fs::write(file, b"").unwrap();
+            playlist.add_file(file.clone()).unwrap();
+        }
+        playlist.tracks[0].duration = Some(Duration::from_secs(215));
+        playlist.tracks[1].name = "Second Song".to_string();
+
+        let m3u = dir.join("playlist.m3u");
+        playlist.save_m3u(&m3u).unwrap();
+        let content = std::fs::read_to_string(&m3u).unwrap();
+        assert!(content.contains("#EXTINF:215,one\nalbum/one.mp3\n"));
+        assert!(content.contains(&format!("{}\n", files[1].display())));
+
+        let mut loaded = Playlist::new();
+        loaded.load_m3u(m3u).unwrap();
+        let tracks = loaded.get_tracks();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].path, files[0]);
+        assert_eq!(tracks[0].duration, Some(Duration::from_secs(215)));
+        assert_eq!(tracks[1].path, files[1]);
+        assert_eq!(tracks[1].name, "Second Song");
+        assert_eq!(tracks[1].duration, None);
+        assert_eq!(loaded.current_index(), Some(0));
+    }
+
+    fn playlist_of(count: usize) -> Playlist {
+        let mut playlist = Playlist::new();
+        for i in 0..count {
+            playlist
+                .tracks
+                .push(Track::from_path(PathBuf::from(format!("{}.mp3", i))));
+        }
+        playlist.current_index = Some(0);
+        playlist
+    }
+
+    #[test]
+    fn test_repeat_off_stops_at_end() {
+        let mut playlist = playlist_of(2);
+        assert!(playlist.advance());
+        assert_eq!(playlist.current_index(), Some(1));
+        assert!(!playlist.advance());
+        assert_eq!(playlist.current_index(), Some(1));
+    }
+
+    #[test]
+    fn test_repeat_one_replays_current() {
+        let mut playlist = playlist_of(2);
+        playlist.toggle_repeat();
+        playlist.toggle_repeat();
+        assert_eq!(playlist.repeat_mode(), RepeatMode::One);
+
+        assert!(playlist.advance());
+        assert_eq!(playlist.current_index(), Some(0));
+        // Skipping manually still moves on
+        assert!(playlist.next());
+        assert_eq!(playlist.current_index(), Some(1));
+    }
+
+    #[test]
+    fn test_repeat_all_wraps() {
+        let mut playlist = playlist_of(2);
+        playlist.toggle_repeat();
+        assert_eq!(playlist.repeat_mode(), RepeatMode::All);
+
+        assert!(playlist.advance());
+        assert!(playlist.advance());
+        assert_eq!(playlist.current_index(), Some(0));
+        assert!(playlist.previous());
+        assert_eq!(playlist.current_index(), Some(1));
+
+        playlist.toggle_repeat();
+        playlist.toggle_repeat();
+        assert_eq!(playlist.repeat_mode(), RepeatMode::Off);
+    }
+
+    #[test]
+    fn test_move_current_track() {
+        let mut playlist = playlist_of(3);
+        assert!(playlist.move_down(0));
+        assert_eq!(playlist.current_index(), Some(1));
+        assert_eq!(playlist.selected_index(), 1);
+        assert_eq!(playlist.current().unwrap().name, "0");
+
+        assert!(playlist.move_up(1));
+        assert_eq!(playlist.current_index(), Some(0));
+        assert_eq!(playlist.current().unwrap().name, "0");
+    }
+
+    #[test]
+    fn test_move_neighbour_of_current_track() {
+        let mut playlist = playlist_of(3);
+        playlist.current_index = Some(1);
+        assert!(playlist.move_up(2));
+        assert_eq!(playlist.current_index(), Some(2));
+        assert_eq!(playlist.current().unwrap().name, "1");
+    }
+
+    #[test]
+    fn test_move_respects_bounds() {
+        let mut playlist = playlist_of(3);
+        assert!(!playlist.move_up(0));
+        assert!(!playlist.move_down(2));
+        assert!(!playlist.move_down(7));
+        let names: Vec<_> = playlist
+            .get_tracks()
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(names, ["0", "1", "2"]);
+    }
+
+    #[test]
+    fn test_remove_current_track() {
+        let mut playlist = playlist_of(3);
+        playlist.current_index = Some(1);
+        playlist.selected_index = 1;
+
+        assert_eq!(playlist.remove(1).unwrap().name, "1");
+        assert_eq!(playlist.current_index(), None);
+        // The following track takes the selection
+        assert_eq!(playlist.selected_index(), 1);
+        assert_eq!(playlist.get_tracks()[1].name, "2");
+    }
+
+    #[test]
+    fn test_remove_track_before_current() {
+        let mut playlist = playlist_of(3);
+        playlist.current_index = Some(2);
+        playlist.selected_index = 2;
+
+        playlist.remove(0);
+        assert_eq!(playlist.current_index(), Some(1));
+        assert_eq!(playlist.current().unwrap().name, "2");
+        assert_eq!(playlist.selected_index(), 1);
+    }
+
+    #[test]
+    fn test_remove_last_track() {
+        let mut playlist = playlist_of(2);
+        playlist.selected_index = 1;
+
+        playlist.remove(1);
+        assert_eq!(playlist.selected_index(), 0);
+        assert_eq!(playlist.current_index(), Some(0));
+
+        playlist.remove(0);
+        assert!(playlist.is_empty());
+        assert_eq!(playlist.current_index(), None);
+        assert_eq!(playlist.selected_index(), 0);
+        assert!(playlist.remove(0).is_none());
+    }
+
+    #[test]
+    fn test_load_m3u_without_extinf() {
+        let dir = temp_dir("m3u_plain");
+        std::fs::write(dir.join("a.mp3"), b"").unwrap();
+        let m3u = dir.join("plain.m3u");
+        std::fs::write(&m3u, "a.mp3\nmissing.mp3\n").unwrap();
+
+        let mut playlist = Playlist::new();
+        playlist.load_m3u(m3u).unwrap();
+        assert_eq!(playlist.len(), 1);
+        assert_eq!(playlist.get_tracks()[0].name, "a");
+    }
+
+    #[test]
+    fn test_load_directory_scans_durations() {
+        let dir = temp_dir("duration_scan");
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("test_220hz.mp3"),
+            dir.join("tone.mp3"),
+        )
+        .unwrap();
+
+        let mut playlist = Playlist::new();
+        playlist.load_directory(dir).unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while playlist.get_tracks()[0].duration.is_none() {
+            assert!(std::time::Instant::now() < deadline, "scan timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            playlist.apply_scans();
+        }
+        let duration = playlist.get_tracks()[0].duration.unwrap();
+        assert!(duration > Duration::from_millis(500), "{:?}", duration);
+        assert!(duration < Duration::from_secs(60), "{:?}", duration);
+    }
+
+    fn load_names(name: &str, files: &[&str], sort_mode: SortMode) -> Vec<String> {
+        let dir = temp_dir(name);
+        for file in files {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        let mut playlist = Playlist::new();
+        playlist.set_sort_mode(sort_mode);
+        playlist.load_directory(dir).unwrap();
+        playlist
+            .get_tracks()
+            .iter()
+            .map(|t| t.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_natural_sort() {
+        let files = ["10.mp3", "2.mp3", "11.mp3", "1.mp3"];
+        assert_eq!(
+            load_names("sort_natural", &files, SortMode::Natural),
+            ["1", "2", "10", "11"]
+        );
+        assert_eq!(
+            load_names("sort_alphabetical", &files, SortMode::Alphabetical),
+            ["1", "10", "11", "2"]
+        );
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("Track 2", "Track 10"), Ordering::Less);
+        assert_eq!(natural_cmp("track 02", "Track 2"), Ordering::Greater);
+        assert_eq!(natural_cmp("disc1/b", "Disc1/A"), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+    }
+
+    fn nested_dir(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        std::fs::create_dir_all(dir.join("album/disc1")).unwrap();
+        for file in ["top.mp3", "album/inner.mp3", "album/disc1/deep.mp3"] {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn names_in(dir: PathBuf, recursive: bool, max_depth: usize) -> Vec<String> {
+        let mut playlist = Playlist::new();
+        playlist.set_recursive(recursive, max_depth);
+        playlist.load_directory(dir).unwrap();
+        playlist
+            .get_tracks()
+            .iter()
+            .map(|t| t.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_load_directory_non_recursive() {
+        let dir = nested_dir("non_recursive");
+        assert_eq!(names_in(dir, false, DEFAULT_MAX_DEPTH), ["top"]);
+    }
+
+    #[test]
+    fn test_load_directory_recursive() {
+        let dir = nested_dir("recursive");
+        assert_eq!(
+            names_in(dir.clone(), true, DEFAULT_MAX_DEPTH),
+            ["deep", "inner", "top"]
+        );
+        assert_eq!(names_in(dir, true, 2), ["inner", "top"]);
+    }
 }
@@ -0,0 +1,236 @@
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::tag::Accessor;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::cover_art;
+
+/// What a scan read from one file's headers and tags
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackInfo {
+    pub duration: Option<Duration>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Embedded cover art extracted into the cover directory
+    pub cover_art: Option<PathBuf>,
+}
+
+/// Scanned track info keyed by path, only reused while the file's mtime is unchanged
+#[derive(Default)]
+pub struct ScanCache {
+    entries: HashMap<PathBuf, (u128, TrackInfo)>,
+}
+
+impl ScanCache {
+    /// Where the cache is kept between runs, e.g. `~/.cache/mp3_player/tracks.tsv`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("mp3_player").join("tracks.tsv"))
+    }
+
+    /// Read a cache written by [`ScanCache::save`], skipping malformed lines.
+    /// A missing or unreadable file gives an empty cache.
+    pub fn load(path: &Path) -> Self {
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        let entries = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(6, '\t');
+                let mtime = fields.next()?.parse().ok()?;
+                let duration = optional(fields.next()?)
+                    .map(|millis| millis.parse().map(Duration::from_millis))
+                    .transpose()
+                    .ok()?;
+                let artist = optional(fields.next()?).map(str::to_string);
+                let album = optional(fields.next()?).map(str::to_string);
+                let cover_art = optional(fields.next()?).map(PathBuf::from);
+                let path = PathBuf::from(fields.next()?);
+                let info = TrackInfo {
+                    duration,
+                    artist,
+                    album,
+                    cover_art,
+                };
+                Some((path, (mtime, info)))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut content = String::new();
+        for (track, (mtime, info)) in &self.entries {
+            let _ = writeln!(
+                content,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                mtime,
+                info.duration
+                    .map(|d| d.as_millis().to_string())
+                    .unwrap_or_default(),
+                field(info.artist.as_deref()),
+                field(info.album.as_deref()),
+                info.cover_art
+                    .as_ref()
+                    .map(|cover| cover.display().to_string())
+                    .unwrap_or_default(),
+                track.display()
+            );
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content)
+    }
+
+    /// The cached info of `path`, reading the file on a miss, when it changed or
+    /// when its cached cover was removed
+    pub fn info(&mut self, path: &Path, cover_dir: Option<&Path>) -> Option<TrackInfo> {
+        let mtime = modified_nanos(path)?;
+        if let Some((cached_mtime, info)) = self.entries.get(path) {
+            let cover_kept = info.cover_art.as_ref().is_none_or(|cover| cover.exists());
+            if *cached_mtime == mtime && cover_kept {
+                return Some(info.clone());
+            }
+        }
+
+        let info = read_info(path, cover_dir)?;
+        self.entries
+            .insert(path.to_path_buf(), (mtime, info.clone()));
+        Some(info)
+    }
+}
+
+/// Read the duration and the artist and album tags without decoding any audio,
+/// extracting the embedded cover into `cover_dir` when one is given
+pub fn read_info(path: &Path, cover_dir: Option<&Path>) -> Option<TrackInfo> {
+    let tagged_file = lofty::read_from_path(path).ok()?;
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag());
+    Some(TrackInfo {
+        duration: Some(tagged_file.properties().duration()).filter(|d| !d.is_zero()),
+        artist: tag.and_then(|tag| tag.artist()).map(|s| s.into_owned()),
+        album: tag.and_then(|tag| tag.album()).map(|s| s.into_owned()),
+        cover_art: cover_dir.and_then(|dir| cover_art::extract_to(path, &tagged_file, dir)),
+    })
+}
+
+/// Read `paths` on a background thread, sending each file's info as it is found.
+/// The cache at `cache_path` is used and updated once the scan finishes, covers
+/// are extracted into `cover_dir`.
+pub fn spawn(
+    paths: Vec<PathBuf>,
+    cache_path: Option<PathBuf>,
+    cover_dir: Option<PathBuf>,
+) -> Receiver<(PathBuf, TrackInfo)> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut cache = cache_path
+            .as_deref()
+            .map(ScanCache::load)
+            .unwrap_or_default();
+
+        for path in paths {
+            if let Some(info) = cache.info(&path, cover_dir.as_deref()) {
+                if sender.send((path, info)).is_err() {
+                    // The playlist was dropped, keep what was scanned so far
+                    break;
+                }
+            }
+        }
+
+        if let Some(cache_path) = cache_path {
+            let _ = cache.save(&cache_path);
+        }
+    });
+    receiver
+}
+
+fn modified_nanos(path: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// A cache field, with tabs and line breaks that would split the line replaced
+fn field(value: Option<&str>) -> String {
+    value.unwrap_or_default().replace(['\t', '\n', '\r'], " ")
+}
+
+fn optional(field: &str) -> Option<&str> {
+    Some(field).filter(|field| !field.is_empty())
+}
+
+/// A copy of the 220Hz fixture in `dir`, tagged with `artist` and `album`
+#[cfg(test)]
+pub fn tagged_fixture(dir: &Path, name: &str, artist: &str, album: &str) -> PathBuf {
+    use lofty::config::WriteOptions;
+    use lofty::tag::{Tag, TagExt, TagType};
+
+    let path = dir.join(name);
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("test_220hz.mp3"),
+        &path,
+    )
+    .unwrap();
+    let mut tag = Tag::new(TagType::Id3v2);
+    tag.set_artist(artist.to_string());
+    tag.set_album(album.to_string());
+    tag.save_to_path(&path, WriteOptions::default()).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+    }
+
+    #[test]
+    fn test_read_fixture_info() {
+        let info = read_info(&fixture("test_220hz.mp3"), None).unwrap();
+        let duration = info.duration.unwrap();
+        assert!(duration > Duration::from_millis(500), "{:?}", duration);
+        assert!(duration < Duration::from_secs(60), "{:?}", duration);
+        assert_eq!(read_info(&fixture("missing.mp3"), None), None);
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join("mp3_player_scan_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join("tracks.tsv");
+        let track = tagged_fixture(&dir, "tagged.mp3", "Tab\tArtist", "Album");
+
+        let mut cache = ScanCache::default();
+        let info = cache.info(&track, None).unwrap();
+        cache.save(&cache_path).unwrap();
+
+        let mut loaded = ScanCache::load(&cache_path);
+        assert_eq!(loaded.entries.len(), 1);
+        let loaded_info = loaded.info(&track, None).unwrap();
+        // Durations are stored with millisecond precision
+        assert_eq!(
+            loaded_info.duration.map(|d| d.as_millis()),
+            info.duration.map(|d| d.as_millis())
+        );
+        assert_eq!(loaded_info.artist.as_deref(), Some("Tab Artist"));
+        assert_eq!(loaded_info.album.as_deref(), Some("Album"));
+    }
+
+    #[test]
+    fn test_spawn_sends_info() {
+        let receiver = spawn(
+            vec![fixture("test_220hz.mp3"), fixture("missing.mp3")],
+            None,
+            None,
+        );
+        let results: Vec<_> = receiver.iter().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, fixture("test_220hz.mp3"));
+    }
+}
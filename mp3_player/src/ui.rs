@@ -2,12 +2,127 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Gauge, List, ListItem, Paragraph},
+    widgets::{Block, BorderType, Borders, Gauge, List, ListItem, ListState, Paragraph},
     Frame,
 };
+use std::collections::{BTreeMap, HashSet};
 use std::time::Duration;
 
-use crate::playlist::Track;
+use crate::playlist::{RepeatMode, Track};
+
+/// How the playlist view groups tracks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupMode {
+    None,
+    Album,
+    Artist,
+}
+
+impl GroupMode {
+    fn next(self) -> Self {
+        match self {
+            GroupMode::None => GroupMode::Album,
+            GroupMode::Album => GroupMode::Artist,
+            GroupMode::Artist => GroupMode::None,
+        }
+    }
+
+    fn group_name(self, track: &Track) -> Option<String> {
+        match self {
+            GroupMode::None => None,
+            GroupMode::Album => Some(
+                track
+                    .album
+                    .clone()
+                    .unwrap_or_else(|| "Unknown Album".to_string()),
+            ),
+            GroupMode::Artist => Some(
+                track
+                    .artist
+                    .clone()
+                    .unwrap_or_else(|| "Unknown Artist".to_string()),
+            ),
+        }
+    }
+}
+
+/// One line of the playlist view
+#[derive(Debug, PartialEq)]
+enum PlaylistRow {
+    Header {
+        name: String,
+        count: usize,
+        collapsed: bool,
+    },
+    Track(usize),
+}
+
+/// Case-insensitive substring match of a track name against the search filter
+fn matches_filter(name: &str, filter: &str) -> bool {
+    filter.is_empty() || name.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Lay out the playlist rows matching `filter`, groups sorted by name and tracks
+/// kept in playlist order
+fn build_rows(
+    tracks: &[Track],
+    mode: GroupMode,
+    collapsed: &HashSet<String>,
+    filter: &str,
+) -> Vec<PlaylistRow> {
+    let visible = tracks
+        .iter()
+        .enumerate()
+        .filter(|(_, track)| matches_filter(&track.name, filter));
+    if mode == GroupMode::None {
+        return visible.map(|(i, _)| PlaylistRow::Track(i)).collect();
+    }
+
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, track) in visible {
+        if let Some(name) = mode.group_name(track) {
+            groups.entry(name).or_default().push(i);
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (name, indices) in groups {
+        let is_collapsed = collapsed.contains(&name);
+        rows.push(PlaylistRow::Header {
+            name,
+            count: indices.len(),
+            collapsed: is_collapsed,
+        });
+        if !is_collapsed {
+            rows.extend(indices.into_iter().map(PlaylistRow::Track));
+        }
+    }
+    rows
+}
+
+/// Row that represents `track`: the track itself, or its header when the group is collapsed
+fn row_of(rows: &[PlaylistRow], tracks: &[Track], mode: GroupMode, track: usize) -> Option<usize> {
+    if let Some(row) = rows.iter().position(|r| *r == PlaylistRow::Track(track)) {
+        return Some(row);
+    }
+    let name = mode.group_name(tracks.get(track)?)?;
+    rows.iter()
+        .position(|r| matches!(r, PlaylistRow::Header { name: n, .. } if *n == name))
+}
+
+/// First visible row so that `selected` stays on screen, scrolling as little as possible
+fn scroll_offset(offset: usize, selected: usize, height: usize) -> usize {
+    if height == 0 {
+        return selected;
+    }
+    if selected < offset {
+        selected
+    } else if selected >= offset + height {
+        selected + 1 - height
+    } else {
+        offset
+    }
+}
 
 pub struct UI {
     current_track: Option<Track>,
@@ -15,10 +130,22 @@ pub struct UI {
     position: Duration,
     duration: Option<Duration>,
     volume: f32,
+    muted: bool,
     playlist: Vec<Track>,
     current_index: Option<usize>,
     selected_index: usize,
+    repeat_mode: RepeatMode,
+    sleep_remaining: Option<Duration>,
     show_help: bool,
+    group_mode: GroupMode,
+    collapsed_groups: HashSet<String>,
+    list_state: ListState,
+    /// Search text being typed, `Some` while the search box has focus
+    search_input: Option<String>,
+    /// Confirmed search filter
+    filter: String,
+    /// Error or notice shown on the controls bar until the next key press
+    message: Option<String>,
 }
 
 impl UI {
@@ -29,10 +156,19 @@ impl UI {
             position: Duration::from_secs(0),
             duration: None,
             volume: 0.5,
+            muted: false,
             playlist: Vec::new(),
             current_index: None,
             selected_index: 0,
+            repeat_mode: RepeatMode::Off,
+            sleep_remaining: None,
             show_help: false,
+            group_mode: GroupMode::None,
+            collapsed_groups: HashSet::new(),
+            list_state: ListState::default(),
+            search_input: None,
+            filter: String::new(),
+            message: None,
         }
     }
 
@@ -42,27 +178,161 @@ impl UI {
         position: Duration,
         duration: Option<Duration>,
         volume: f32,
+        muted: bool,
     ) {
         self.is_playing = is_playing;
         self.position = position;
         self.duration = duration;
         self.volume = volume;
+        self.muted = muted;
     }
 
-    pub fn update_playlist(&mut self, playlist: &[Track], current_index: Option<usize>) {
+    pub fn update_playlist(
+        &mut self,
+        playlist: &[Track],
+        current_index: Option<usize>,
+        selected_index: usize,
+    ) {
         self.playlist = playlist.to_vec();
         self.current_index = current_index;
+        self.selected_index = selected_index;
+    }
+
+    pub fn update_repeat_mode(&mut self, repeat_mode: RepeatMode) {
+        self.repeat_mode = repeat_mode;
+    }
+
+    pub fn update_sleep_timer(&mut self, remaining: Option<Duration>) {
+        self.sleep_remaining = remaining;
     }
 
     pub fn update_current_track(&mut self, track: Option<Track>) {
         self.current_track = track;
     }
 
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
     pub fn toggle_help(&mut self) {
         self.show_help = !self.show_help;
     }
 
-    pub fn draw(&self, frame: &mut Frame) {
+    /// Cycle between a flat list, grouping by album and grouping by artist
+    pub fn toggle_group_mode(&mut self) {
+        self.group_mode = self.group_mode.next();
+        self.collapsed_groups.clear();
+    }
+
+    pub fn is_grouped(&self) -> bool {
+        self.group_mode != GroupMode::None
+    }
+
+    /// Whether the view differs from plain playlist order, so navigation must
+    /// go through [`UI::select_relative`]
+    pub fn is_flat(&self) -> bool {
+        !self.is_grouped() && self.active_filter().is_empty()
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search_input.is_some()
+    }
+
+    pub fn start_search(&mut self) {
+        self.search_input = Some(self.filter.clone());
+    }
+
+    pub fn push_search_char(&mut self, c: char) {
+        if let Some(ref mut input) = self.search_input {
+            input.push(c);
+        }
+    }
+
+    pub fn pop_search_char(&mut self) {
+        if let Some(ref mut input) = self.search_input {
+            input.pop();
+        }
+    }
+
+    /// Keep the typed text as the playlist filter
+    pub fn confirm_search(&mut self) {
+        if let Some(input) = self.search_input.take() {
+            self.filter = input;
+        }
+    }
+
+    /// Leave the search box and drop the filter
+    pub fn cancel_search(&mut self) {
+        self.search_input = None;
+        self.filter.clear();
+    }
+
+    /// The filter applied to the view, updated live while typing
+    fn active_filter(&self) -> &str {
+        self.search_input.as_deref().unwrap_or(&self.filter)
+    }
+
+    /// The selected track if it is still visible, otherwise the first visible one
+    pub fn visible_selection(&self) -> Option<usize> {
+        let rows = self.rows();
+        if rows.contains(&PlaylistRow::Track(self.selected_index)) {
+            return Some(self.selected_index);
+        }
+        rows.iter().find_map(|row| match row {
+            PlaylistRow::Track(i) => Some(*i),
+            PlaylistRow::Header { .. } => None,
+        })
+    }
+
+    /// Collapse or expand the group containing the selected track
+    pub fn toggle_collapse(&mut self) {
+        let Some(name) = self
+            .playlist
+            .get(self.selected_index)
+            .and_then(|track| self.group_mode.group_name(track))
+        else {
+            return;
+        };
+        if !self.collapsed_groups.remove(&name) {
+            self.collapsed_groups.insert(name);
+        }
+    }
+
+    /// The track above or below the selection in display order, skipping headers
+    /// and collapsed groups and wrapping around at the ends
+    pub fn select_relative(&self, forward: bool) -> Option<usize> {
+        let rows = self.rows();
+        let start = row_of(&rows, &self.playlist, self.group_mode, self.selected_index)?;
+        let track_at = |row: &PlaylistRow| match row {
+            PlaylistRow::Track(i) => Some(*i),
+            PlaylistRow::Header { .. } => None,
+        };
+        let (before, after) = (&rows[..start], &rows[start + 1..]);
+        if forward {
+            after.iter().chain(before).find_map(track_at)
+        } else {
+            before
+                .iter()
+                .rev()
+                .chain(after.iter().rev())
+                .find_map(track_at)
+        }
+    }
+
+    fn rows(&self) -> Vec<PlaylistRow> {
+        build_rows(
+            &self.playlist,
+            self.group_mode,
+            &self.collapsed_groups,
+            self.active_filter(),
+        )
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(1)
@@ -78,7 +348,16 @@ impl UI {
         self.draw_title(frame, chunks[0]);
         self.draw_now_playing(frame, chunks[1]);
         self.draw_progress(frame, chunks[2]);
-        self.draw_playlist(frame, chunks[3]);
+        if self.is_searching() || !self.filter.is_empty() {
+            let playlist_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(2)])
+                .split(chunks[3]);
+            self.draw_search(frame, playlist_chunks[0]);
+            self.draw_playlist(frame, playlist_chunks[1]);
+        } else {
+            self.draw_playlist(frame, chunks[3]);
+        }
         self.draw_controls(frame, chunks[4]);
 
         if self.show_help {
@@ -107,7 +386,7 @@ impl UI {
         let mut lines = vec![];
 
         if let Some(ref track) = self.current_track {
-            lines.push(Line::from(vec![
+            let mut title = vec![
                 Span::styled("Now Playing: ", Style::default().fg(Color::Yellow)),
                 Span::styled(
                     &track.name,
@@ -115,7 +394,11 @@ impl UI {
                         .fg(Color::White)
                         .add_modifier(Modifier::BOLD),
                 ),
-            ]));
+            ];
+            if track.cover_art.is_some() {
+                title.push(Span::styled("  [art]", Style::default().fg(Color::Magenta)));
+            }
+            lines.push(Line::from(title));
 
             if let Some(ref artist) = track.artist {
                 lines.push(Line::from(vec![
@@ -141,10 +424,24 @@ impl UI {
             } else {
                 Color::Yellow
             };
-            lines.push(Line::from(Span::styled(
-                status,
-                Style::default().fg(status_color),
-            )));
+            let mut status_line = vec![
+                Span::styled(status, Style::default().fg(status_color)),
+                Span::styled(
+                    format!("   Repeat: {}", self.repeat_mode.label()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ];
+            if let Some(remaining) = self.sleep_remaining {
+                status_line.push(Span::styled(
+                    format!(
+                        "   Sleep: {}:{:02}",
+                        remaining.as_secs() / 60,
+                        remaining.as_secs() % 60
+                    ),
+                    Style::default().fg(Color::Magenta),
+                ));
+            }
+            lines.push(Line::from(status_line));
         } else {
             lines.push(Line::from(Span::styled(
                 "No track loaded",
@@ -216,41 +513,102 @@ impl UI {
         frame.render_widget(duration_widget, chunks[2]);
 
         // Volume
-        let volume_text = format!("Vol: {}%", (self.volume * 100.0) as u32);
-        let volume_widget = Paragraph::new(volume_text)
-            .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
+        let (volume_label, volume_ratio, volume_color) = if self.muted {
+            ("Muted".to_string(), 0.0, Color::DarkGray)
+        } else {
+            (
+                format!("Vol {}%", (self.volume * 100.0).round() as u32),
+                self.volume.clamp(0.0, 1.0) as f64,
+                Color::Magenta,
+            )
+        };
+        let volume_widget = Gauge::default()
+            .block(Block::default().borders(Borders::ALL))
+            .gauge_style(Style::default().fg(volume_color))
+            .ratio(volume_ratio)
+            .label(volume_label);
         frame.render_widget(volume_widget, chunks[3]);
     }
 
-    fn draw_playlist(&self, frame: &mut Frame, area: Rect) {
-        let items: Vec<ListItem> = self
-            .playlist
-            .iter()
-            .enumerate()
-            .map(|(i, track)| {
-                let prefix = if Some(i) == self.current_index {
-                    "▶ "
-                } else {
-                    "  "
-                };
+    fn draw_search(&self, frame: &mut Frame, area: Rect) {
+        let (text, border_color) = match self.search_input {
+            Some(ref input) => (format!("/{}█", input), Color::Yellow),
+            None => (format!("/{}", self.filter), Color::DarkGray),
+        };
+        let search = Paragraph::new(text).block(
+            Block::default()
+                .title("Search (Enter: keep, Esc: clear)")
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(border_color)),
+        );
+        frame.render_widget(search, area);
+    }
 
-                let style = if Some(i) == self.current_index {
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD)
-                } else {
-                    Style::default()
+    fn draw_playlist(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.rows();
+        let selected_row = row_of(&rows, &self.playlist, self.group_mode, self.selected_index);
+        let indent = if self.is_grouped() { "  " } else { "" };
+        let items: Vec<ListItem> = rows
+            .iter()
+            .map(|entry| {
+                let (text, style) = match entry {
+                    PlaylistRow::Header {
+                        name,
+                        count,
+                        collapsed,
+                    } => {
+                        let marker = if *collapsed { "▸" } else { "▾" };
+                        (
+                            format!("{} {} ({})", marker, name, count),
+                            Style::default()
+                                .fg(Color::Yellow)
+                                .add_modifier(Modifier::BOLD),
+                        )
+                    }
+                    PlaylistRow::Track(i) => {
+                        let i = *i;
+                        let prefix = if Some(i) == self.current_index {
+                            "▶ "
+                        } else {
+                            "  "
+                        };
+
+                        let style = if Some(i) == self.current_index {
+                            Style::default()
+                                .fg(Color::Green)
+                                .add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default()
+                        };
+
+                        let track = &self.playlist[i];
+                        let length = track.duration.map_or(String::new(), |d| {
+                            format!("  {}:{:02}", d.as_secs() / 60, d.as_secs() % 60)
+                        });
+                        (
+                            format!("{}{}{}{}", indent, prefix, track.name, length),
+                            style,
+                        )
+                    }
                 };
 
-                ListItem::new(format!("{}{}", prefix, track.name)).style(style)
+                ListItem::new(text).style(style)
             })
             .collect();
 
         let playlist = List::new(items)
             .block(
                 Block::default()
-                    .title(format!("Playlist ({} tracks)", self.playlist.len()))
+                    .title(match self.group_mode {
+                        GroupMode::None => format!("Playlist ({} tracks)", self.playlist.len()),
+                        GroupMode::Album => {
+                            format!("Playlist ({} tracks, by album)", self.playlist.len())
+                        }
+                        GroupMode::Artist => {
+                            format!("Playlist ({} tracks, by artist)", self.playlist.len())
+                        }
+                    })
                     .borders(Borders::ALL)
                     .border_type(BorderType::Rounded),
             )
@@ -261,18 +619,35 @@ impl UI {
             )
             .highlight_symbol("> ");
 
-        frame.render_widget(playlist, area);
+        // Keep the selection on screen, the list block's borders take two rows
+        let height = area.height.saturating_sub(2) as usize;
+        let offset = match selected_row {
+            Some(row) => scroll_offset(self.list_state.offset(), row, height),
+            None => 0,
+        };
+        *self.list_state.offset_mut() = offset;
+        self.list_state.select(selected_row);
+
+        frame.render_stateful_widget(playlist, area, &mut self.list_state);
     }
 
     fn draw_controls(&self, frame: &mut Frame, area: Rect) {
         let controls = vec![
             ("Space", "Play/Pause"),
             ("←/→", "Prev/Next"),
+            (",/.", "Seek"),
             ("↑/↓", "Select"),
+            ("Shift+↑/↓", "Move"),
+            ("d", "Remove"),
             ("Enter", "Play Selected"),
             ("+/-", "Volume"),
+            ("m", "Mute"),
             ("s", "Stop"),
             ("r", "Repeat"),
+            ("t", "Sleep"),
+            ("w", "Save"),
+            ("g", "Group"),
+            ("/", "Search"),
             ("h", "Help"),
             ("q", "Quit"),
         ];
@@ -287,12 +662,17 @@ impl UI {
             })
             .collect();
 
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        if let Some(ref message) = self.message {
+            block = block.title(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
         let controls_widget = Paragraph::new(Line::from(control_text))
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded),
-            )
+            .block(block)
             .alignment(Alignment::Center);
 
         frame.render_widget(controls_widget, area);
@@ -320,21 +700,57 @@ impl UI {
                 Span::styled("← / →      ", Style::default().fg(Color::Cyan)),
                 Span::raw("Previous/Next track"),
             ]),
+            Line::from(vec![
+                Span::styled(", / .      ", Style::default().fg(Color::Cyan)),
+                Span::raw("Seek backward/forward (< / > for a large step)"),
+            ]),
             Line::from(vec![
                 Span::styled("↑ / ↓      ", Style::default().fg(Color::Cyan)),
                 Span::raw("Move selection up/down"),
             ]),
+            Line::from(vec![
+                Span::styled("Shift+↑/↓  ", Style::default().fg(Color::Cyan)),
+                Span::raw("Move selected track up/down"),
+            ]),
+            Line::from(vec![
+                Span::styled("d / Del    ", Style::default().fg(Color::Cyan)),
+                Span::raw("Remove selected track"),
+            ]),
             Line::from(vec![
                 Span::styled("+ / -      ", Style::default().fg(Color::Cyan)),
                 Span::raw("Increase/Decrease volume"),
             ]),
+            Line::from(vec![
+                Span::styled("m          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Mute/Unmute"),
+            ]),
             Line::from(vec![
                 Span::styled("s          ", Style::default().fg(Color::Cyan)),
                 Span::raw("Stop playback"),
             ]),
             Line::from(vec![
                 Span::styled("r          ", Style::default().fg(Color::Cyan)),
-                Span::raw("Toggle repeat mode"),
+                Span::raw("Cycle repeat mode (off/all/one)"),
+            ]),
+            Line::from(vec![
+                Span::styled("t          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Cycle sleep timer (15/30/60/90 min/off)"),
+            ]),
+            Line::from(vec![
+                Span::styled("w          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Save playlist to playlist.m3u"),
+            ]),
+            Line::from(vec![
+                Span::styled("g          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Group by album/artist/none"),
+            ]),
+            Line::from(vec![
+                Span::styled("c          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Collapse/Expand selected group"),
+            ]),
+            Line::from(vec![
+                Span::styled("/          ", Style::default().fg(Color::Cyan)),
+                Span::raw("Filter tracks by name"),
             ]),
             Line::from(vec![
                 Span::styled("h / ?      ", Style::default().fg(Color::Cyan)),
@@ -385,3 +801,159 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::Playlist;
+    use crate::track_scan::tagged_fixture;
+    use std::path::PathBuf;
+
+    fn track(name: &str, album: Option<&str>) -> Track {
+        Track {
+            album: album.map(str::to_string),
+            ..Track::from_path(PathBuf::from(format!("{}.mp3", name)))
+        }
+    }
+
+    fn ui_with(tracks: Vec<Track>) -> UI {
+        let mut ui = UI::new();
+        ui.update_playlist(&tracks, None, 0);
+        ui
+    }
+
+    #[test]
+    fn test_build_rows_groups_by_album() {
+        let tracks = vec![
+            track("a", Some("Zeta")),
+            track("b", Some("Alpha")),
+            track("c", None),
+            track("d", Some("Zeta")),
+        ];
+        let rows = build_rows(&tracks, GroupMode::Album, &HashSet::new(), "");
+        let header = |name: &str, count| PlaylistRow::Header {
+            name: name.to_string(),
+            count,
+            collapsed: false,
+        };
+        assert_eq!(
+            rows,
+            vec![
+                header("Alpha", 1),
+                PlaylistRow::Track(1),
+                header("Unknown Album", 1),
+                PlaylistRow::Track(2),
+                header("Zeta", 2),
+                PlaylistRow::Track(0),
+                PlaylistRow::Track(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanned_tags_group_tracks() {
+        let dir = std::env::temp_dir().join("mp3_player_tagged_groups");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        tagged_fixture(&dir, "1.mp3", "Band", "Second");
+        tagged_fixture(&dir, "2.mp3", "Band", "First");
+        tagged_fixture(&dir, "3.mp3", "Solo", "Second");
+
+        let mut playlist = Playlist::new();
+        playlist.load_directory(dir).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while playlist.get_tracks().iter().any(|t| t.album.is_none()) {
+            assert!(std::time::Instant::now() < deadline, "scan timed out");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            playlist.apply_scans();
+        }
+
+        let tracks = playlist.get_tracks();
+        let headers = |mode| -> Vec<(String, usize)> {
+            build_rows(tracks, mode, &HashSet::new(), "")
+                .into_iter()
+                .filter_map(|row| match row {
+                    PlaylistRow::Header { name, count, .. } => Some((name, count)),
+                    PlaylistRow::Track(_) => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            headers(GroupMode::Album),
+            [("First".to_string(), 1), ("Second".to_string(), 2)]
+        );
+        assert_eq!(
+            headers(GroupMode::Artist),
+            [("Band".to_string(), 2), ("Solo".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_navigation_skips_headers_and_collapsed_groups() {
+        let mut ui = ui_with(vec![
+            track("a", Some("Zeta")),
+            track("b", Some("Alpha")),
+            track("c", Some("Mid")),
+        ]);
+        ui.toggle_group_mode();
+        ui.selected_index = 1;
+
+        // Alpha(b) -> Mid(c) -> Zeta(a), crossing headers
+        assert_eq!(ui.select_relative(true), Some(2));
+        ui.selected_index = 2;
+        assert_eq!(ui.select_relative(true), Some(0));
+        assert_eq!(ui.select_relative(false), Some(1));
+
+        ui.toggle_collapse();
+        ui.selected_index = 1;
+        assert_eq!(ui.select_relative(true), Some(0));
+        ui.selected_index = 0;
+        assert_eq!(ui.select_relative(true), Some(1));
+        ui.selected_index = 1;
+        assert_eq!(ui.select_relative(false), Some(0));
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        assert!(matches_filter("Bohemian Rhapsody", ""));
+        assert!(matches_filter("Bohemian Rhapsody", "rhap"));
+        assert!(matches_filter("Bohemian Rhapsody", "BOHEMIAN R"));
+        assert!(!matches_filter("Bohemian Rhapsody", "queen"));
+    }
+
+    #[test]
+    fn test_search_narrows_navigation() {
+        let mut ui = ui_with(vec![
+            track("alpha", None),
+            track("beta", None),
+            track("alphabet", None),
+        ]);
+        ui.start_search();
+        for c in "alp".chars() {
+            ui.push_search_char(c);
+        }
+        assert!(!ui.is_flat());
+        assert_eq!(ui.select_relative(true), Some(2));
+
+        ui.confirm_search();
+        ui.selected_index = 1;
+        assert_eq!(ui.visible_selection(), Some(0));
+
+        ui.start_search();
+        ui.cancel_search();
+        assert!(ui.is_flat());
+    }
+
+    #[test]
+    fn test_scroll_offset_follows_selection() {
+        // Within the window nothing moves
+        assert_eq!(scroll_offset(0, 3, 10), 0);
+        // Past the bottom the selection becomes the last visible row
+        assert_eq!(scroll_offset(0, 12, 10), 3);
+        // Above the top the selection becomes the first visible row
+        assert_eq!(scroll_offset(5, 2, 10), 2);
+        // Wrapping from the end back to the start jumps to the top
+        assert_eq!(scroll_offset(40, 0, 10), 0);
+        assert_eq!(scroll_offset(0, 4, 0), 4);
+    }
+}
@@ -0,0 +1,9 @@
+def add(a, b):
+    return {"sum": a + b}
+
+
+def slow(seconds):
+    import time
+
+    time.sleep(seconds)
+    return seconds
@@ -1,21 +1,24 @@
 use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
 use axum::response::IntoResponse;
 use axum::response::Response;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 
 use crate::api::utils::{FailureResponse, GetEntryResponse, PaginationRequest, SuccessResponse};
 use crate::app_state::AppState;
 use crate::dao::file_dao;
+use crate::player::metadata;
 
 pub async fn get_file_list(
     state: State<Arc<AppState>>,
     Json(req): Json<PaginationRequest<file_dao::FileEntryFilter>>,
 ) -> Result<SuccessResponse<GetEntryResponse<file_dao::FileEntry>>, GetFileListError> {
-    let file_dao = file_dao::FileDao::new(&state.db_state).await;
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
     let get_result = file_dao.get_files(&req).await;
     if get_result.is_err() {
         return Err(GetFileListError::DatabaseError);
@@ -23,30 +26,149 @@ pub async fn get_file_list(
 
     let (files, count) = get_result.unwrap();
     Ok(SuccessResponse::new(
-        GetEntryResponse {
-            entries: files,
-            entries_per_page: req.page_size,
-            total_entries: count as i32,
-        },
+        GetEntryResponse::new(files, req.page, req.page_size, count as i32),
         "Success",
     ))
 }
 
+#[derive(Debug, Serialize)]
+pub struct CountResponse {
+    pub total_entries: i32,
+}
+
+pub async fn count_files(
+    state: State<Arc<AppState>>,
+    Json(filter): Json<file_dao::FileEntryFilter>,
+) -> Result<SuccessResponse<CountResponse>, GetFileListError> {
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
+    match file_dao.count_files(Some(&filter)).await {
+        Ok(count) => Ok(SuccessResponse::new(
+            CountResponse {
+                total_entries: count as i32,
+            },
+            "Success",
+        )),
+        Err(_) => Err(GetFileListError::DatabaseError),
+    }
+}
+
 pub async fn update_class(
     state: State<Arc<AppState>>,
     Json(req): Json<file_dao::UpdateClassRequest>,
 ) -> Result<SuccessResponse<()>, GetFileListError> {
-    let file_dao = file_dao::FileDao::new(&state.db_state).await;
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
     let update_result = file_dao.update_class(&req).await;
-    if update_result.is_err() {
+    match update_result {
+        Ok(true) => Ok(SuccessResponse::new((), "Success")),
+        Ok(false) => Err(GetFileListError::NotFound),
+        Err(_) => Err(GetFileListError::DatabaseError),
+    }
+}
+
+pub async fn update_training_flag(
+    state: State<Arc<AppState>>,
+    Json(req): Json<file_dao::UpdateTrainingFlagRequest>,
+) -> Result<SuccessResponse<()>, GetFileListError> {
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
+    let update_result = file_dao.update_training_flag(&req).await;
+    match update_result {
+        Ok(true) => Ok(SuccessResponse::new((), "Success")),
+        Ok(false) => Err(GetFileListError::NotFound),
+        Err(_) => Err(GetFileListError::DatabaseError),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResult {
+    pub id: i64,
+    pub success: bool,
+}
+
+/// Apply one action to many files at once, reporting which ids it applied to
+pub async fn bulk_update(
+    state: State<Arc<AppState>>,
+    Json(req): Json<file_dao::BulkRequest>,
+) -> Result<SuccessResponse<Vec<BulkResult>>, GetFileListError> {
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
+    let updated = match file_dao.bulk_update(&req).await {
+        Ok(updated) => updated,
+        Err(_) => return Err(GetFileListError::DatabaseError),
+    };
+
+    let results = req
+        .ids
+        .iter()
+        .zip(updated)
+        .map(|(&id, success)| BulkResult { id, success })
+        .collect();
+    Ok(SuccessResponse::new(results, "Success"))
+}
+
+/// Embedded cover art of a file, served with the picture's own mime type
+pub async fn get_cover(
+    state: State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Response, GetFileListError> {
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetFileListError::DatabaseError);
+    };
+    let file = match file_dao.get_file_by_id(id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return Err(GetFileListError::NotFound),
+        Err(_) => return Err(GetFileListError::DatabaseError),
+    };
+
+    let cover =
+        tokio::task::spawn_blocking(move || metadata::read_cover(std::path::Path::new(&file.path)))
+            .await
+            .ok()
+            .flatten();
+    match cover {
+        Some((data, mime_type)) => Ok(([(header::CONTENT_TYPE, mime_type)], data).into_response()),
+        None => Err(GetFileListError::NotFound),
+    }
+}
+
+/// Whether a file can be played, probed once and then served from the file row
+pub async fn probe_file(
+    state: State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<SuccessResponse<metadata::FileProbe>, GetFileListError> {
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
         return Err(GetFileListError::DatabaseError);
+    };
+    match file_dao.get_probe(id).await {
+        Ok(Some(probe)) => return Ok(SuccessResponse::new(probe, "Success")),
+        Ok(None) => {}
+        Err(_) => return Err(GetFileListError::DatabaseError),
     }
+    let file = match file_dao.get_file_by_id(id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return Err(GetFileListError::NotFound),
+        Err(_) => return Err(GetFileListError::DatabaseError),
+    };
 
-    Ok(SuccessResponse::new((), "Success"))
+    let probe =
+        tokio::task::spawn_blocking(move || metadata::probe(std::path::Path::new(&file.path)))
+            .await
+            .unwrap_or_default();
+    if file_dao.set_probe(id, &probe).await.is_err() {
+        return Err(GetFileListError::DatabaseError);
+    }
+    Ok(SuccessResponse::new(probe, "Success"))
 }
 
 pub enum GetFileListError {
     DatabaseError,
+    NotFound,
 }
 
 impl IntoResponse for GetFileListError {
@@ -56,6 +178,7 @@ impl IntoResponse for GetFileListError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Failed to query file list from database",
             ),
+            GetFileListError::NotFound => (StatusCode::NOT_FOUND, "File not found"),
         };
 
         let res = FailureResponse::new(error_msg);
@@ -63,3 +186,146 @@ impl IntoResponse for GetFileListError {
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::wav_track;
+
+    async fn insert(state: &AppState, name: &str, path: &str) -> i64 {
+        let file_dao = file_dao::FileDao::new(&state.db_state).await.unwrap();
+        file_dao
+            .insert_file(file_dao::FileEntry {
+                id: None,
+                name: name.to_string(),
+                size: 0.0,
+                path: path.to_string(),
+                class: 0,
+                is_training_data: Some(false),
+                created_at: 0.0,
+            })
+            .await
+            .unwrap();
+        file_dao.get_file_by_name(name).await.unwrap().id.unwrap()
+    }
+
+    async fn probe(state: &Arc<AppState>, id: i64) -> serde_json::Value {
+        let Ok(res) = probe_file(State(state.clone()), Path(id)).await else {
+            panic!("Probing file {} failed", id);
+        };
+        serde_json::to_value(res).unwrap()["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_probe_valid_and_invalid_files() {
+        let state = Arc::new(AppState::open_temp("filelist-probe").await);
+        let valid = wav_track("probe", 2);
+        let valid_id = insert(&state, "probe.wav", valid.path()).await;
+        let invalid_path =
+            std::env::temp_dir().join(format!("emes-probe-{}.mp3", std::process::id()));
+        std::fs::write(&invalid_path, b"not audio at all").unwrap();
+        let invalid_id = insert(&state, "probe.mp3", &invalid_path.to_string_lossy()).await;
+        let empty = wav_track("probe-empty", 0);
+        let empty_id = insert(&state, "probe-empty.wav", empty.path()).await;
+
+        let res = probe(&state, valid_id).await;
+        assert_eq!(res["playable"], true);
+        assert_eq!(res["format"], "pcm_s16le");
+        assert_eq!(res["duration_sec"], 2.0);
+        assert_eq!(probe(&state, invalid_id).await["playable"], false);
+        // A valid header with no samples behind it
+        assert_eq!(probe(&state, empty_id).await["playable"], false);
+
+        // Served from the file row afterwards
+        let file_dao = file_dao::FileDao::new(&state.db_state).await.unwrap();
+        let cached = file_dao.get_probe(valid_id).await.unwrap().unwrap();
+        assert!(cached.playable);
+        assert!(
+            !file_dao
+                .get_probe(invalid_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .playable
+        );
+
+        assert!(matches!(
+            probe_file(State(state.clone()), Path(999)).await,
+            Err(GetFileListError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_set_class() {
+        let state = Arc::new(AppState::open_temp("filelist-bulk").await);
+        let mut ids = Vec::new();
+        for name in ["a.mp3", "b.mp3", "c.mp3"] {
+            ids.push(insert(&state, name, &format!("/music/{}", name)).await);
+        }
+        let untouched = insert(&state, "d.mp3", "/music/d.mp3").await;
+
+        let req = file_dao::BulkRequest {
+            ids: ids.iter().copied().chain([999]).collect(),
+            action: serde_json::from_str(r#"{ "set_class": 3 }"#).unwrap(),
+        };
+        let Ok(res) = bulk_update(State(state.clone()), Json(req)).await else {
+            panic!("Bulk update failed");
+        };
+        let data = serde_json::to_value(res).unwrap()["data"].clone();
+        let success: Vec<bool> = data
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["success"].as_bool().unwrap())
+            .collect();
+        assert_eq!(success, [true, true, true, false]);
+
+        let file_dao = file_dao::FileDao::new(&state.db_state).await.unwrap();
+        for id in ids {
+            assert_eq!(file_dao.get_file_by_id(id).await.unwrap().unwrap().class, 3);
+        }
+        let untouched = file_dao.get_file_by_id(untouched).await.unwrap().unwrap();
+        assert_eq!(untouched.class, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_class() {
+        let state = Arc::new(AppState::open_temp("filelist-update-class").await);
+        let id = insert(&state, "a.mp3", "/music/a.mp3").await;
+
+        let req = file_dao::UpdateClassRequest { id, class: 2 };
+        assert!(update_class(State(state.clone()), Json(req)).await.is_ok());
+        let file_dao = file_dao::FileDao::new(&state.db_state).await.unwrap();
+        assert_eq!(file_dao.get_file_by_name("a.mp3").await.unwrap().class, 2);
+
+        let req = file_dao::UpdateClassRequest { id: 999, class: 2 };
+        let res = update_class(State(state.clone()), Json(req)).await;
+        assert!(matches!(res, Err(GetFileListError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_update_training_flag() {
+        let state = Arc::new(AppState::open_temp("filelist-update-training").await);
+        let id = insert(&state, "a.mp3", "/music/a.mp3").await;
+
+        let req = file_dao::UpdateTrainingFlagRequest {
+            id,
+            is_training_data: true,
+        };
+        assert!(
+            update_training_flag(State(state.clone()), Json(req))
+                .await
+                .is_ok()
+        );
+        let file_dao = file_dao::FileDao::new(&state.db_state).await.unwrap();
+        let file = file_dao.get_file_by_name("a.mp3").await.unwrap();
+        assert_eq!(file.is_training_data, Some(true));
+
+        let req = file_dao::UpdateTrainingFlagRequest {
+            id: 999,
+            is_training_data: true,
+        };
+        let res = update_training_flag(State(state.clone()), Json(req)).await;
+        assert!(matches!(res, Err(GetFileListError::NotFound)));
+    }
+}
@@ -1,5 +1,5 @@
 use axum::Router;
-use axum::routing::post;
+use axum::routing::{get, post};
 use std::sync::Arc;
 
 use crate::api::filelist::filelist;
@@ -8,6 +8,15 @@ use crate::app_state::AppState;
 pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", post(filelist::get_file_list))
+        .route(
+            "/count",
+            get(filelist::count_files).post(filelist::count_files),
+        )
         .route("/update-class", post(filelist::update_class))
+        .route("/class", post(filelist::update_class))
+        .route("/training", post(filelist::update_training_flag))
+        .route("/bulk", post(filelist::bulk_update))
+        .route("/{id}/cover", get(filelist::get_cover))
+        .route("/{id}/probe", get(filelist::probe_file))
         .with_state(app_state.clone())
 }
@@ -0,0 +1,62 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::api::utils::SuccessResponse;
+use crate::app_state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct HealthStatus {
+    pub db: bool,
+    pub audio: bool,
+    pub led: bool,
+}
+
+impl HealthStatus {
+    /// The LED strip is optional, the server is only ready when the DB and audio output are up
+    pub fn is_ready(&self) -> bool {
+        self.db && self.audio
+    }
+}
+
+pub async fn health(state: State<Arc<AppState>>) -> Response {
+    let pool = state.db_state.get_pool();
+    let db = sqlx::query("SELECT 1").execute(&pool).await.is_ok();
+    let audio = state.player_state.get_music_player().is_output_available();
+    let led = state.led_strip_state.is_initialized();
+
+    let health = HealthStatus { db, audio, led };
+    if health.is_ready() {
+        return SuccessResponse::new(health, "Healthy").into_response();
+    }
+
+    let res = SuccessResponse::new_with_code(-1, health, "Unhealthy");
+    (StatusCode::SERVICE_UNAVAILABLE, Json(json!(res))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_body_keys() {
+        // Neither an audio output nor a strip is attached under test
+        let state = Arc::new(AppState::open_temp("health").await);
+        let res = health(State(state)).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["code"], json!(-1));
+        assert_eq!(value["data"]["db"], json!(true));
+        assert_eq!(value["data"]["audio"], json!(false));
+        assert_eq!(value["data"]["led"], json!(false));
+    }
+}
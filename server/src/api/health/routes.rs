@@ -0,0 +1,12 @@
+use axum::Router;
+use axum::routing::get;
+use std::sync::Arc;
+
+use crate::api::health::health;
+use crate::app_state::AppState;
+
+pub fn routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(health::health))
+        .with_state(app_state.clone())
+}
@@ -15,7 +15,9 @@ use crate::dao::player_led_dao;
 pub async fn get_led_strip_status(
     state: State<Arc<AppState>>,
 ) -> Result<SuccessResponse<player_led_dao::PlayerLedEntry>, LedStripError> {
-    let player_led_dao = player_led_dao::PlayerLedDao::new(&state.db_state).await;
+    let Ok(player_led_dao) = player_led_dao::PlayerLedDao::new(&state.db_state).await else {
+        return Err(LedStripError::DatabaseError);
+    };
     let led_strip = player_led_dao.get_led_strip_status().await;
     if led_strip.is_err() {
         return Err(LedStripError::DatabaseError);
@@ -29,7 +31,9 @@ pub async fn set_led_strip_status(
     state: State<Arc<AppState>>,
     Json(req): Json<player_led_dao::PlayerLedEntry>,
 ) -> Result<SuccessResponse<()>, LedStripError> {
-    let player_led_dao = player_led_dao::PlayerLedDao::new(&state.db_state).await;
+    let Ok(player_led_dao) = player_led_dao::PlayerLedDao::new(&state.db_state).await else {
+        return Err(LedStripError::DatabaseError);
+    };
     let led_strip = player_led_dao.set_led_strip_status(req).await;
     if led_strip.is_err() {
         return Err(LedStripError::DatabaseError);
@@ -46,14 +50,53 @@ pub async fn set_led_strip_status(
     Ok(SuccessResponse::new((), "Success"))
 }
 
+/// Switch the strip off, keeping the stored color for [`turn_on`]
+pub async fn turn_off(state: State<Arc<AppState>>) -> SuccessResponse<()> {
+    let event_chan_sender = state.led_strip_state.get_event_chan_sender();
+    let event_str = json!(SetLedStripStatusEvent {
+        enable: false,
+        status: None,
+    })
+    .to_string();
+    let _ = event_chan_sender.send(event_str);
+
+    SuccessResponse::new((), "Success")
+}
+
+/// Switch the strip back on with the stored color
+pub async fn turn_on(
+    state: State<Arc<AppState>>,
+) -> Result<SuccessResponse<player_led_dao::PlayerLedEntry>, LedStripError> {
+    let Ok(player_led_dao) = player_led_dao::PlayerLedDao::new(&state.db_state).await else {
+        return Err(LedStripError::DatabaseError);
+    };
+    let led_strip = match player_led_dao.get_led_strip_status().await {
+        Ok(led_strip) => led_strip,
+        Err(sqlx::Error::RowNotFound) => return Err(LedStripError::NoStoredStatus),
+        Err(_) => return Err(LedStripError::DatabaseError),
+    };
+
+    let event_chan_sender = state.led_strip_state.get_event_chan_sender();
+    let event_str = json!(SetLedStripStatusEvent {
+        enable: true,
+        status: Some(led_strip),
+    })
+    .to_string();
+    let _ = event_chan_sender.send(event_str);
+
+    Ok(SuccessResponse::new(led_strip, "Success"))
+}
+
 pub enum LedStripError {
     DatabaseError,
+    NoStoredStatus,
 }
 
 impl IntoResponse for LedStripError {
     fn into_response(self) -> Response {
         let (status, error_msg) = match self {
             LedStripError::DatabaseError => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
+            LedStripError::NoStoredStatus => (StatusCode::NOT_FOUND, "No LED strip color set yet"),
         };
 
         let res = FailureResponse::new(error_msg);
@@ -61,3 +104,75 @@ impl IntoResponse for LedStripError {
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws2812::{Color, MockSpi, SpiConfig, Ws2812, Ws2812StripTask};
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    fn frame_of(color: Color) -> Option<Vec<u8>> {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 1, 11), Box::new(spi.clone()));
+        strip.fill(color).unwrap();
+        strip.show().unwrap();
+        spi.last_frame()
+    }
+
+    #[tokio::test]
+    async fn test_off_then_on_restores_color() {
+        let app_state = Arc::new(AppState::open_temp("led-strip-off-on").await);
+        let stored = player_led_dao::PlayerLedEntry {
+            id: 1,
+            frequency: 0.0,
+            scale: 1.0,
+            red: 255,
+            green: 0,
+            blue: 0,
+        };
+        player_led_dao::PlayerLedDao::new(&app_state.db_state)
+            .await
+            .unwrap()
+            .set_led_strip_status(stored)
+            .await
+            .unwrap();
+
+        let spi = MockSpi::default();
+        let strip = Ws2812::with_transport(SpiConfig::new(0, 1, 11), Box::new(spi.clone()));
+        let task = Ws2812StripTask::with_strip(app_state.clone(), strip);
+        let shutdown_token = CancellationToken::new();
+        let running = tokio::spawn({
+            let shutdown_token = shutdown_token.clone();
+            async move { task.run(shutdown_token).await }
+        });
+
+        // Let the task subscribe before the first event goes out
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let _ = turn_on(State(app_state.clone())).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(spi.last_frame(), frame_of(Color::red()));
+
+        let _ = turn_off(State(app_state.clone())).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(spi.last_frame(), frame_of(Color::black()));
+
+        // Off leaves the stored color alone, so on brings it back
+        let res = turn_on(State(app_state.clone())).await.ok().unwrap();
+        assert_eq!(serde_json::to_value(res).unwrap()["data"]["red"], 255);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(spi.last_frame(), frame_of(Color::red()));
+
+        shutdown_token.cancel();
+        running.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_on_without_stored_color() {
+        let app_state = Arc::new(AppState::open_temp("led-strip-on-empty").await);
+        assert!(matches!(
+            turn_on(State(app_state)).await,
+            Err(LedStripError::NoStoredStatus)
+        ));
+    }
+}
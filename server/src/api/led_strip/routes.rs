@@ -9,5 +9,7 @@ pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/status", get(lib::get_led_strip_status))
         .route("/status", post(lib::set_led_strip_status))
+        .route("/off", post(lib::turn_off))
+        .route("/on", post(lib::turn_on))
         .with_state(app_state.clone())
 }
@@ -0,0 +1,61 @@
+use axum::extract::State;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::api::utils::SuccessResponse;
+use crate::app_state::AppState;
+use crate::player::PlayerStatus;
+
+#[derive(Debug, Serialize)]
+pub struct MetricsReport {
+    /// Unix timestamp in milliseconds of when the server started
+    pub started_at: i64,
+    pub uptime_sec: u64,
+    pub tracks_played: u64,
+    pub led_events: u64,
+    pub player: PlayerStatus,
+    pub led_strip: bool,
+}
+
+pub async fn metrics(state: State<Arc<AppState>>) -> SuccessResponse<MetricsReport> {
+    let metrics = &state.metrics;
+    let player = state
+        .player_state
+        .get_music_player()
+        .status()
+        .unwrap_or_default();
+
+    let report = MetricsReport {
+        started_at: metrics.started_at().timestamp_millis(),
+        uptime_sec: metrics.uptime().as_secs(),
+        tracks_played: metrics.tracks_played(),
+        led_events: metrics.led_events(),
+        player,
+        led_strip: state.led_strip_state.is_initialized(),
+    };
+    SuccessResponse::new(report, "Success")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::wav_track;
+
+    async fn report(state: &Arc<AppState>) -> serde_json::Value {
+        serde_json::to_value(metrics(State(state.clone())).await).unwrap()["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_playing_a_track_is_counted() {
+        let state = Arc::new(AppState::open_temp("metrics-tracks").await);
+        let player = state.player_state.get_music_player();
+        player.detach();
+        assert_eq!(report(&state).await["tracks_played"], 0);
+
+        player.play(&vec![wav_track("metrics", 1)], 0).unwrap();
+        let report = report(&state).await;
+        assert_eq!(report["tracks_played"], 1);
+        assert_eq!(report["player"]["current_track"], "metrics");
+        player.stop().unwrap();
+    }
+}
@@ -0,0 +1,12 @@
+use axum::Router;
+use axum::routing::get;
+use std::sync::Arc;
+
+use crate::api::metrics::metrics;
+use crate::app_state::AppState;
+
+pub fn routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(metrics::metrics))
+        .with_state(app_state.clone())
+}
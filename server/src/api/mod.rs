@@ -1,5 +1,7 @@
 pub mod filelist;
+pub mod health;
 mod led_strip;
+pub mod metrics;
 mod player;
 pub mod playlist;
 pub mod py_tasks;
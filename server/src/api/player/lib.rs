@@ -5,13 +5,14 @@ use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::app_state::AppState;
 use crate::dao::player_led_dao;
-use crate::player::{PlayerStatus, Track};
+use crate::player::{AudioLevels, EqSettings, PlayerStatus, Track};
 
 #[derive(Debug, Deserialize)]
 pub struct PlayRequest {
@@ -26,16 +27,72 @@ pub async fn play(
     let player = state.player_state.get_music_player();
 
     if let Err(e) = player.play(&req.playlist, req.selected_index) {
-        tracing::error!("Failed to play track: {}", e);
+        let track = req.playlist.get(req.selected_index).map(Track::name);
+        tracing::error!(
+            index = req.selected_index,
+            track = ?track,
+            playlist_len = req.playlist.len(),
+            "Failed to play track: {}",
+            e
+        );
         return Err(PlayError::InternalError);
     }
 
     Ok(SuccessResponse::new((), "Success"))
 }
 
+/// Load the selected track paused, `toggle` starts it
+pub async fn load(
+    state: State<Arc<AppState>>,
+    Json(req): Json<PlayRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+
+    if let Err(e) = player.load(&req.playlist, req.selected_index) {
+        let track = req.playlist.get(req.selected_index).map(Track::name);
+        tracing::error!(
+            index = req.selected_index,
+            track = ?track,
+            playlist_len = req.playlist.len(),
+            "Failed to load track: {}",
+            e
+        );
+        return Err(PlayError::InternalError);
+    }
+
+    Ok(SuccessResponse::new((), "Success"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnqueueRequest {
+    pub track: Track,
+}
+
+pub async fn enqueue(
+    state: State<Arc<AppState>>,
+    Json(req): Json<EnqueueRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+    let track_name = req.track.name().to_string();
+    if let Err(e) = player.enqueue(req.track) {
+        tracing::error!(track = %track_name, "Failed to enqueue track: {}", e);
+        return Err(PlayError::InternalError);
+    }
+    Ok(SuccessResponse::new((), "Success"))
+}
+
+pub async fn clear_queue(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+    if let Err(e) = player.clear_queue() {
+        tracing::error!("Failed to clear queue: {}", e);
+        return Err(PlayError::InternalError);
+    }
+    Ok(SuccessResponse::new((), "Success"))
+}
+
 pub async fn stop(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
-    if let Err(e) = player.stop() {
+    if let Err(e) = player.fade_out_and_stop() {
         tracing::error!("Failed to stop track: {}", e);
         return Err(PlayError::InternalError);
     }
@@ -53,12 +110,15 @@ pub async fn stop(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, Pl
 
 pub async fn toggle(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
-    if let Err(e) = player.toggle() {
-        tracing::error!("Failed to toggle track: {}", e);
-        return Err(PlayError::InternalError);
-    }
+    let paused = match player.toggle() {
+        Ok(paused) => paused,
+        Err(e) => {
+            tracing::error!("Failed to toggle track: {}", e);
+            return Err(PlayError::InternalError);
+        }
+    };
 
-    if player.is_paused() {
+    if paused {
         let event_chan_sender = state.led_strip_state.get_event_chan_sender();
         let event_str = json!(SetLedStripStatusEvent {
             enable: false,
@@ -79,26 +139,19 @@ pub async fn status(
         Ok(status) => status,
         Err(e) => {
             tracing::error!("Failed to get status: {}", e);
-            PlayerStatus {
-                paused: true,
-                position: None,
-                position_sec: None,
-                duration: None,
-                duration_sec: None,
-                volume: 0.0,
-                current_track: None,
-                track: None,
-            }
+            PlayerStatus::default()
         }
     };
 
-    let led_strip_dao = player_led_dao::PlayerLedDao::new(&state.db_state).await;
-    let led_strip = led_strip_dao.get_led_strip_status().await;
-    if led_strip.is_err() {
-        return Err(PlayError::DatabaseError);
+    // LED brightness is reported alongside, but a missing LED config must not hide the player state
+    let led_strip = match player_led_dao::PlayerLedDao::new(&state.db_state).await {
+        Ok(led_strip_dao) => led_strip_dao.get_led_strip_status().await,
+        Err(e) => Err(e),
+    };
+    match led_strip {
+        Ok(led_strip) => status.led_scale = Some(led_strip.scale as f32),
+        Err(e) => tracing::warn!("Failed to get led strip status: {}", e),
     }
-    let led_strip = led_strip.unwrap();
-    status.volume = led_strip.scale as f32;
 
     Ok(SuccessResponse::new(status, "Success"))
 }
@@ -108,13 +161,32 @@ pub struct SetVolumeRequest {
     pub volume: f32,
 }
 
+#[derive(Debug, Serialize)]
+pub struct VolumeResponse {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+pub async fn get_volume(
+    state: State<Arc<AppState>>,
+) -> Result<SuccessResponse<VolumeResponse>, PlayError> {
+    let player = state.player_state.get_music_player();
+    Ok(SuccessResponse::new(
+        VolumeResponse {
+            volume: player.volume(),
+            muted: player.is_muted(),
+        },
+        "Success",
+    ))
+}
+
 pub async fn set_volume(
     state: State<Arc<AppState>>,
     Json(req): Json<SetVolumeRequest>,
 ) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
     if let Err(e) = player.set_volume(req.volume) {
-        tracing::error!("Failed to set volume: {}", e);
+        tracing::error!(volume = req.volume, "Failed to set volume: {}", e);
         return Err(PlayError::InternalError);
     }
     Ok(SuccessResponse::new((), "Success"))
@@ -131,7 +203,7 @@ pub async fn seek(
 ) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
     if let Err(e) = player.seek(req.delta) {
-        tracing::error!("Failed to seek: {}", e);
+        tracing::error!(delta = req.delta, "Failed to seek: {}", e);
         return Err(PlayError::InternalError);
     }
     Ok(SuccessResponse::new((), "Success"))
@@ -142,18 +214,76 @@ pub struct SeekToRequest {
     pub seconds: f32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MuteRequest {
+    pub muted: bool,
+}
+
+pub async fn mute(
+    state: State<Arc<AppState>>,
+    Json(req): Json<MuteRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+    if let Err(e) = player.set_muted(req.muted) {
+        tracing::error!(muted = req.muted, "Failed to set mute: {}", e);
+        return Err(PlayError::InternalError);
+    }
+    Ok(SuccessResponse::new((), "Success"))
+}
+
 pub async fn seek_to(
     state: State<Arc<AppState>>,
     Json(req): Json<SeekToRequest>,
 ) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
     if let Err(e) = player.seek_to(req.seconds) {
-        tracing::error!("Failed to seek to: {}", e);
+        tracing::error!(seconds = req.seconds, "Failed to seek to: {}", e);
         return Err(PlayError::InternalError);
     }
     Ok(SuccessResponse::new((), "Success"))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PlayIndexRequest {
+    pub index: usize,
+}
+
+pub async fn play_index(
+    state: State<Arc<AppState>>,
+    Json(req): Json<PlayIndexRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+    match player.play_index(req.index) {
+        Ok(true) => Ok(SuccessResponse::new((), "Success")),
+        Ok(false) => Err(PlayError::InvalidTrackIndex),
+        Err(e) => {
+            tracing::error!(index = req.index, "Failed to play track: {}", e);
+            Err(PlayError::InternalError)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveTrackRequest {
+    pub from: usize,
+    pub to: usize,
+}
+
+pub async fn move_track(
+    state: State<Arc<AppState>>,
+    Json(req): Json<MoveTrackRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let player = state.player_state.get_music_player();
+    match player.move_track(req.from, req.to) {
+        Ok(true) => Ok(SuccessResponse::new((), "Success")),
+        Ok(false) => Err(PlayError::InvalidTrackIndex),
+        Err(e) => {
+            tracing::error!(from = req.from, to = req.to, "Failed to move track: {}", e);
+            Err(PlayError::InternalError)
+        }
+    }
+}
+
 pub async fn next(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, PlayError> {
     let player = state.player_state.get_music_player();
     if let Err(e) = player.next() {
@@ -172,16 +302,78 @@ pub async fn prev(state: State<Arc<AppState>>) -> Result<SuccessResponse<()>, Pl
     Ok(SuccessResponse::new((), "Success"))
 }
 
+pub async fn get_levels(
+    state: State<Arc<AppState>>,
+) -> Result<SuccessResponse<AudioLevels>, PlayError> {
+    let player = state.player_state.get_music_player();
+    Ok(SuccessResponse::new(player.levels(), "Success"))
+}
+
+pub async fn get_eq(state: State<Arc<AppState>>) -> Result<SuccessResponse<EqSettings>, PlayError> {
+    let player = state.player_state.get_music_player();
+    Ok(SuccessResponse::new(player.eq(), "Success"))
+}
+
+pub async fn set_eq(
+    state: State<Arc<AppState>>,
+    Json(req): Json<EqSettings>,
+) -> Result<SuccessResponse<EqSettings>, PlayError> {
+    let player = state.player_state.get_music_player();
+    match player.set_eq(req) {
+        Ok(settings) => Ok(SuccessResponse::new(settings, "Success")),
+        Err(e) => {
+            tracing::error!(?req, "Failed to set EQ: {}", e);
+            Err(PlayError::InternalError)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SleepTimerRequest {
+    /// Minutes until playback stops, 0 cancels the timer
+    pub minutes: f32,
+    #[serde(default = "default_sleep_fade")]
+    pub fade: bool,
+}
+
+fn default_sleep_fade() -> bool {
+    true
+}
+
+pub async fn sleep_timer(
+    state: State<Arc<AppState>>,
+    Json(req): Json<SleepTimerRequest>,
+) -> Result<SuccessResponse<()>, PlayError> {
+    let after = Duration::try_from_secs_f32(req.minutes * 60.0)
+        .map_err(|_| PlayError::InvalidSleepTimer)?;
+    let after = Some(after).filter(|after| !after.is_zero());
+
+    let player = state.player_state.get_music_player();
+    if let Err(e) = player.set_sleep_timer(after, req.fade) {
+        tracing::error!(minutes = req.minutes, "Failed to set sleep timer: {}", e);
+        return Err(PlayError::InternalError);
+    }
+    Ok(SuccessResponse::new((), "Success"))
+}
+
 pub enum PlayError {
     InternalError,
-    DatabaseError,
+    InvalidSleepTimer,
+    InvalidTrackIndex,
 }
 
 impl IntoResponse for PlayError {
     fn into_response(self) -> Response {
         let (status, error_msg) = match self {
-            PlayError::DatabaseError => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to play track"),
             PlayError::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
+            PlayError::InvalidSleepTimer => (
+                StatusCode::BAD_REQUEST,
+                "Sleep timer minutes must be a non-negative number",
+            ),
+            PlayError::InvalidTrackIndex => (
+                StatusCode::BAD_REQUEST,
+                "Track index is outside the playlist",
+            ),
         };
 
         let res = FailureResponse::new(error_msg);
@@ -189,3 +381,103 @@ impl IntoResponse for PlayError {
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::Mutex;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    /// Keeps everything the fmt subscriber writes so a test can read it back
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_volume_round_trip_ignores_led_scale() {
+        let state = Arc::new(AppState::open_temp("player-volume").await);
+        player_led_dao::PlayerLedDao::new(&state.db_state)
+            .await
+            .unwrap()
+            .set_led_strip_status(player_led_dao::PlayerLedEntry {
+                id: 1,
+                frequency: 0.5,
+                scale: 0.3,
+                red: 255,
+                green: 255,
+                blue: 255,
+            })
+            .await
+            .unwrap();
+        fn data<T: Serialize>(res: SuccessResponse<T>) -> serde_json::Value {
+            serde_json::to_value(res).unwrap()["data"].clone()
+        }
+
+        let req = SetVolumeRequest { volume: 0.7 };
+        assert!(set_volume(State(state.clone()), Json(req)).await.is_ok());
+        let Ok(volume) = get_volume(State(state.clone())).await else {
+            panic!("Getting the volume failed");
+        };
+        assert_eq!(data(volume), json!({ "volume": 0.7f32, "muted": false }));
+        let Ok(status) = status(State(state.clone())).await else {
+            panic!("Getting the status failed");
+        };
+        let status = data(status);
+        assert_eq!(status["volume"], json!(0.7f32));
+        assert_eq!(status["led_scale"], json!(0.3f32));
+
+        // Muting is reported beside the volume instead of zeroing it
+        let req = MuteRequest { muted: true };
+        assert!(mute(State(state.clone()), Json(req)).await.is_ok());
+        let Ok(volume) = get_volume(State(state.clone())).await else {
+            panic!("Getting the volume failed");
+        };
+        assert_eq!(data(volume), json!({ "volume": 0.7f32, "muted": true }));
+    }
+
+    #[tokio::test]
+    async fn test_failed_play_logs_attempted_index() {
+        let state = Arc::new(AppState::open_temp("player-play-log").await);
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let req = PlayRequest {
+            playlist: Vec::new(),
+            selected_index: 3,
+        };
+        assert!(play(State(state), Json(req)).await.is_err());
+
+        let logs = logs.contents();
+        assert!(logs.contains("index=3"), "{}", logs);
+        assert!(logs.contains("playlist_len=0"), "{}", logs);
+    }
+}
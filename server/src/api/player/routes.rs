@@ -8,13 +8,24 @@ use crate::app_state::AppState;
 pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/play", post(lib::play))
+        .route("/load", post(lib::load))
+        .route("/play_index", post(lib::play_index))
         .route("/stop", post(lib::stop))
         .route("/toggle", post(lib::toggle))
         .route("/status", get(lib::status))
+        .route("/volume", get(lib::get_volume))
         .route("/volume", post(lib::set_volume))
+        .route("/mute", post(lib::mute))
         .route("/seek", post(lib::seek))
         .route("/seek_to", post(lib::seek_to))
         .route("/next", post(lib::next))
         .route("/prev", post(lib::prev))
+        .route("/enqueue", post(lib::enqueue))
+        .route("/clear_queue", post(lib::clear_queue))
+        .route("/move", post(lib::move_track))
+        .route("/sleep_timer", post(lib::sleep_timer))
+        .route("/levels", get(lib::get_levels))
+        .route("/eq", get(lib::get_eq))
+        .route("/eq", post(lib::set_eq))
         .with_state(app_state.clone())
 }
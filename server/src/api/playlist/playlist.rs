@@ -16,7 +16,9 @@ pub async fn get_playlist(
 ) -> Result<SuccessResponse<GetEntryResponse<file_dao::FileEntry>>, GetPlaylistError> {
     // ) -> Result<SuccessResponse<()>, GetPlaylistError> {
     // tracing::info!("get_playlist req: {:?}", req);
-    let file_dao = file_dao::FileDao::new(&state.db_state).await;
+    let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+        return Err(GetPlaylistError::DatabaseError);
+    };
     let get_result = file_dao.get_files(&req).await;
     if get_result.is_err() {
         return Err(GetPlaylistError::DatabaseError);
@@ -24,11 +26,7 @@ pub async fn get_playlist(
 
     let (files, count) = get_result.unwrap();
     Ok(SuccessResponse::new(
-        GetEntryResponse {
-            entries: files,
-            entries_per_page: req.page_size,
-            total_entries: count as i32,
-        },
+        GetEntryResponse::new(files, req.page, req.page_size, count as i32),
         "Success",
     ))
 }
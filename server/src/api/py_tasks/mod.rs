@@ -1,2 +1,3 @@
 pub mod py_tasks;
 pub mod routes;
+pub mod training;
@@ -1,31 +1,228 @@
+use axum::Json;
 use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyModule;
+use pyo3::types::{PyList, PyModule};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::Path;
 use std::sync::Arc;
 
+use crate::api::utils::{FailureResponse, SuccessResponse};
 use crate::app_state::AppState;
 
 pub async fn greet(State(app_state): State<Arc<AppState>>) -> String {
-    let g = Python::with_gil(|py| {
-        let greet = PyModule::import(py, "greet");
-        if let Err(e) = greet {
-            tracing::error!("Error importing greet: {}", e);
-            return e.to_string();
+    let g = tokio::task::spawn_blocking(|| {
+        Python::with_gil(|py| {
+            let greet = PyModule::import(py, "greet");
+            if let Err(e) = greet {
+                tracing::error!("Error importing greet: {}", e);
+                return e.to_string();
+            }
+            let greet = greet.unwrap();
+            let greet_func = greet.getattr("greet");
+            if let Err(e) = greet_func {
+                tracing::error!("Error getting greet function: {}", e);
+                return e.to_string();
+            }
+            let greet_func = greet_func.unwrap();
+            let result = greet_func.call0();
+            if let Err(e) = result {
+                tracing::error!("Error calling greet function: {}", e);
+                return e.to_string();
+            }
+            let result = result.unwrap();
+            result.to_string()
+        })
+    })
+    .await;
+    match g {
+        Ok(g) => g,
+        Err(e) => {
+            tracing::error!("Greet task failed: {}", e);
+            e.to_string()
         }
-        let greet = greet.unwrap();
-        let greet_func = greet.getattr("greet");
-        if let Err(e) = greet_func {
-            tracing::error!("Error getting greet function: {}", e);
-            return e.to_string();
-        }
-        let greet_func = greet_func.unwrap();
-        let result = greet_func.call0();
-        if let Err(e) = result {
-            tracing::error!("Error calling greet function: {}", e);
-            return e.to_string();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunTaskRequest {
+    pub module: String,
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunTaskResponse {
+    pub result: Value,
+}
+
+pub async fn run(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<RunTaskRequest>,
+) -> Result<SuccessResponse<RunTaskResponse>, PyTaskError> {
+    if !app_state
+        .config
+        .python
+        .is_function_allowed(&req.module, &req.function)
+    {
+        tracing::warn!(module = %req.module, function = %req.function, "Rejected python task");
+        return Err(PyTaskError::NotAllowed);
+    }
+
+    match call_function_blocking(req.module.clone(), req.function.clone(), req.args).await {
+        Ok(result) => Ok(SuccessResponse::new(RunTaskResponse { result }, "Success")),
+        Err(e) => {
+            tracing::error!(module = %req.module, function = %req.function, "Python task failed: {}", e);
+            Err(PyTaskError::CallFailed(e))
         }
-        let result = result.unwrap();
-        result.to_string()
-    });
-    g
+    }
+}
+
+/// Put `dir` at the front of `sys.path` so its modules can be imported.
+pub fn add_to_sys_path(dir: &Path) -> PyResult<()> {
+    Python::with_gil(|py| {
+        let path = py.import("sys")?.getattr("path")?;
+        // `sys.path` entries must be plain strings, not `pathlib.Path`
+        path.call_method1("insert", (0, dir.to_string_lossy().into_owned()))?;
+        Ok(())
+    })
+}
+
+/// Run [`call_function`] on the blocking pool so holding the GIL never stalls
+/// a runtime worker thread.
+async fn call_function_blocking(
+    module: String,
+    function: String,
+    args: Vec<Value>,
+) -> Result<Value, String> {
+    tokio::task::spawn_blocking(move || call_function(&module, &function, &args))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Import `module` and call `function` with `args`, converting both ways through JSON.
+fn call_function(module: &str, function: &str, args: &[Value]) -> PyResult<Value> {
+    Python::with_gil(|py| {
+        let json = PyModule::import(py, "json")?;
+        let args = serde_json::to_string(args).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let args = json
+            .call_method1("loads", (args,))?
+            .downcast_into::<PyList>()?
+            .to_tuple();
+
+        let result = PyModule::import(py, module)?
+            .getattr(function)?
+            .call1(args)?;
+        let result: String = json.call_method1("dumps", (result,))?.extract()?;
+        serde_json::from_str(&result).map_err(|e| PyValueError::new_err(e.to_string()))
+    })
+}
+
+pub enum PyTaskError {
+    NotAllowed,
+    CallFailed(String),
+}
+
+impl IntoResponse for PyTaskError {
+    fn into_response(self) -> Response {
+        let (status, error_msg) = match self {
+            PyTaskError::NotAllowed => (
+                StatusCode::FORBIDDEN,
+                "Python module or function is not allowed".to_string(),
+            ),
+            PyTaskError::CallFailed(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Python task failed: {}", e),
+            ),
+        };
+
+        let res = FailureResponse::new(&error_msg);
+        let body = Json(json!(res));
+        (status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::PythonConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn fixtures_dir() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("python/fixtures")
+    }
+
+    fn add_fixture_path() {
+        add_to_sys_path(&fixtures_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_call_fixture_function() {
+        add_fixture_path();
+        let result = call_function(
+            "py_tasks_fixture",
+            "add",
+            &[serde_json::json!(2), serde_json::json!(40)],
+        )
+        .unwrap();
+        assert_eq!(result, serde_json::json!({ "sum": 42 }));
+    }
+
+    #[test]
+    fn test_import_from_resolved_dir() {
+        let python = PythonConfig {
+            dir: Some(fixtures_dir()),
+            ..PythonConfig::default()
+        };
+        add_to_sys_path(&python.resolve_dir()).unwrap();
+        Python::with_gil(|py| {
+            assert!(PyModule::import(py, "py_tasks_fixture").is_ok());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_slow_task_does_not_block_status() {
+        add_fixture_path();
+        let app_state = Arc::new(AppState::open_temp("py-tasks-slow").await);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new().nest("/api", crate::api::routes::routes(app_state));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let slow = tokio::spawn(call_function_blocking(
+            "py_tasks_fixture".to_string(),
+            "slow".to_string(),
+            vec![json!(0.5)],
+        ));
+        tokio::task::yield_now().await;
+
+        let started = std::time::Instant::now();
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /api/player/status HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(started.elapsed() < std::time::Duration::from_millis(400));
+        assert!(!slow.is_finished());
+        assert_eq!(slow.await.unwrap().unwrap(), json!(0.5));
+    }
+
+    #[test]
+    fn test_call_missing_function_errors() {
+        add_fixture_path();
+        assert!(call_function("py_tasks_fixture", "missing", &[]).is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use axum::Router;
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
 
 use crate::api::py_tasks::py_tasks;
@@ -8,5 +8,6 @@ use crate::app_state::AppState;
 pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/greet", get(py_tasks::greet))
+        .route("/run", post(py_tasks::run))
         .with_state(app_state.clone())
 }
@@ -0,0 +1,90 @@
+use pyo3::prelude::*;
+use pyo3::types::PyModule;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Name of the module Python training code imports to report back
+const BRIDGE_MODULE: &str = "emes";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrainingProgress {
+    pub epoch: u32,
+    pub loss: f64,
+}
+
+#[derive(Clone)]
+pub struct TrainingState {
+    progress_chan: broadcast::Sender<TrainingProgress>,
+}
+
+impl TrainingState {
+    pub fn new() -> Self {
+        Self {
+            progress_chan: broadcast::channel(100).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TrainingProgress> {
+        self.progress_chan.subscribe()
+    }
+
+    /// Expose `emes.report_training_progress(epoch, loss)` to Python code,
+    /// forwarding each call to the progress subscribers.
+    pub fn register_python_callback(&self) -> PyResult<()> {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, BRIDGE_MODULE)?;
+            module.add(
+                "report_training_progress",
+                ProgressReporter {
+                    sender: self.progress_chan.clone(),
+                },
+            )?;
+            py.import("sys")?
+                .getattr("modules")?
+                .set_item(BRIDGE_MODULE, module)?;
+            Ok(())
+        })
+    }
+}
+
+#[pyclass]
+struct ProgressReporter {
+    sender: broadcast::Sender<TrainingProgress>,
+}
+
+#[pymethods]
+impl ProgressReporter {
+    fn __call__(&self, epoch: u32, loss: f64) {
+        // An error only means no client is listening right now
+        let _ = self.sender.send(TrainingProgress { epoch, loss });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_callback_forwards_progress() {
+        let training_state = TrainingState::new();
+        let mut progress = training_state.subscribe();
+        training_state.register_python_callback().unwrap();
+
+        Python::with_gil(|py| {
+            py.run(
+                c"import emes; emes.report_training_progress(3, 0.25)",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+
+        assert_eq!(
+            progress.try_recv().unwrap(),
+            TrainingProgress {
+                epoch: 3,
+                loss: 0.25
+            }
+        );
+    }
+}
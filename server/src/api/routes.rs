@@ -2,7 +2,9 @@ use axum::Router;
 use std::sync::Arc;
 
 use crate::api::filelist::routes::routes as filelist_routes;
+use crate::api::health::routes::routes as health_routes;
 use crate::api::led_strip::routes as led_strip_routes;
+use crate::api::metrics::routes::routes as metrics_routes;
 use crate::api::player::routes as player_routes;
 use crate::api::playlist::routes::routes as playlist_routes;
 use crate::api::py_tasks::routes::routes as py_tasks_routes;
@@ -12,7 +14,9 @@ use crate::app_state::AppState;
 pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .nest("/filelist", filelist_routes(app_state.clone()))
+        .nest("/health", health_routes(app_state.clone()))
         .nest("/led-strip", led_strip_routes(app_state.clone()))
+        .nest("/metrics", metrics_routes(app_state.clone()))
         .nest("/py-tasks", py_tasks_routes(app_state.clone()))
         .nest("/upload", upload_routes(app_state.clone()))
         .nest("/playlist", playlist_routes(app_state.clone()))
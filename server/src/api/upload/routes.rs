@@ -9,7 +9,8 @@ use crate::app_state::AppState;
 pub fn routes(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", post(upload::upload_file))
-        // Set a large max upload file size (e.g., 100 MB)
-        .layer(DefaultBodyLimit::max(usize::MAX))
+        .layer(DefaultBodyLimit::max(
+            app_state.config.upload.max_body_bytes,
+        ))
         .with_state(app_state.clone())
 }
@@ -23,7 +23,15 @@ pub async fn upload_file(
     let mut file_name = None;
     let mut file_bytes = None;
 
-    while let Ok(Some(field)) = multipart.next_field().await {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(UploadError::PayloadTooLarge);
+            }
+            Err(_) => break,
+        };
         let name = field.name().unwrap_or("");
 
         match name {
@@ -31,8 +39,12 @@ pub async fn upload_file(
                 let filename = field.file_name().unwrap_or("");
                 file_name = Some(filename.to_string());
                 let data = field.bytes().await;
-                if let Ok(bytes) = data {
-                    file_bytes = Some(bytes);
+                match data {
+                    Ok(bytes) => file_bytes = Some(bytes),
+                    Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                        return Err(UploadError::PayloadTooLarge);
+                    }
+                    Err(_) => {}
                 }
             }
             "class" => {
@@ -50,9 +62,12 @@ pub async fn upload_file(
         (&class, &file_name, &file_bytes)
     {
         // tracing::info!("class: {}, file_name: {}", class_val, file_name_val);
-        let file_dao = file_dao::FileDao::new(&state.db_state).await;
+        let Ok(file_dao) = file_dao::FileDao::new(&state.db_state).await else {
+            return Err(UploadError::UploadFailed);
+        };
         let file_entry = file_dao.get_file_by_name(file_name_val).await;
         if file_entry.is_some() {
+            tracing::warn!(file_name = %file_name_val, "File already exists");
             return Err(UploadError::FileAlreadyExists);
         }
         if let Ok(size) =
@@ -65,6 +80,7 @@ pub async fn upload_file(
     Err(UploadError::UploadFailed)
 }
 
+#[tracing::instrument(skip(file_dao, file_bytes), fields(size = file_bytes.len()))]
 async fn process_upload_stream(
     file_dao: &file_dao::FileDao,
     class: &str,
@@ -142,6 +158,7 @@ async fn process_upload_stream(
 pub enum UploadError {
     UploadFailed,
     FileAlreadyExists,
+    PayloadTooLarge,
 }
 
 impl IntoResponse for UploadError {
@@ -154,9 +171,67 @@ impl IntoResponse for UploadError {
             UploadError::FileAlreadyExists => {
                 (StatusCode::CONFLICT, "File already exists".to_string())
             }
+            UploadError::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Upload exceeds the maximum allowed size".to_string(),
+            ),
         };
 
         let failure_response = FailureResponse::new(&error_msg);
         (status, axum::Json(failure_response)).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::upload::routes;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_payload_too_large_status() {
+        let res = UploadError::PayloadTooLarge.into_response();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_upload_over_the_limit_is_rejected() {
+        let mut app_state = AppState::open_temp("upload-too-large").await;
+        app_state.config.upload.max_body_bytes = 1024;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = routes::routes(Arc::new(app_state));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let boundary = "emes-upload-boundary";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"class\"\r\n\r\n1\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"big.mp3\"\r\n\
+             Content-Type: audio/mpeg\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        body.extend(std::iter::repeat_n(b'x', 4096));
+        body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+        let head = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: multipart/form-data; boundary={}\r\nContent-Length: {}\r\n\r\n",
+            boundary,
+            body.len()
+        );
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        let json = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(json["message"], "Upload exceeds the maximum allowed size");
+
+        server.abort();
+    }
+}
@@ -82,4 +82,51 @@ pub struct GetEntryResponse<T> {
     pub entries: Vec<T>,
     pub entries_per_page: i32,
     pub total_entries: i32,
+    pub total_pages: i32,
+    pub has_next: bool,
+    pub has_prev: bool,
+}
+
+impl<T> GetEntryResponse<T> {
+    /// One page of `total` entries, `page` counting from 0 like [`PaginationRequest`]
+    pub fn new(entries: Vec<T>, page: i32, page_size: i32, total: i32) -> Self {
+        let total_pages = if page_size > 0 {
+            (total + page_size - 1) / page_size
+        } else {
+            0
+        };
+        Self {
+            entries,
+            entries_per_page: page_size,
+            total_entries: total,
+            total_pages,
+            has_next: page + 1 < total_pages,
+            has_prev: page > 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_counts() {
+        let first = GetEntryResponse::<()>::new(vec![], 0, 10, 25);
+        assert_eq!(first.total_pages, 3);
+        assert!(first.has_next);
+        assert!(!first.has_prev);
+
+        let last = GetEntryResponse::<()>::new(vec![], 2, 10, 25);
+        assert!(!last.has_next);
+        assert!(last.has_prev);
+
+        let exact = GetEntryResponse::<()>::new(vec![], 1, 10, 20);
+        assert_eq!(exact.total_pages, 2);
+        assert!(!exact.has_next);
+
+        let empty = GetEntryResponse::<()>::new(vec![], 0, 10, 0);
+        assert_eq!(empty.total_pages, 0);
+        assert!(!empty.has_next && !empty.has_prev);
+    }
 }
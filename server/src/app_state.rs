@@ -1,20 +1,63 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::py_tasks::training::TrainingState;
+use crate::config::Config;
 use crate::dao::db_state::DBClientState;
+use crate::metrics::Metrics;
 use crate::player::PlayerState;
+use crate::sock_io::SocketRegistry;
 use crate::ws2812::LedStripState;
 
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Config,
     pub db_state: DBClientState,
     pub player_state: PlayerState,
     pub led_strip_state: LedStripState,
+    pub training_state: TrainingState,
+    pub metrics: Arc<Metrics>,
+    /// Socket.io clients connected right now
+    pub socket_registry: SocketRegistry,
+}
+
+impl AppState {
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        let db_state = DBClientState::open(&config.database.resolve_path()).await?;
+        let metrics = Arc::new(Metrics::new());
+        let player_state = PlayerState::new(metrics.clone());
+        let player = player_state.get_music_player();
+        player.set_fade_duration(Duration::from_millis(config.player.fade_ms));
+        player.set_check_interval(Duration::from_millis(config.player.check_interval_ms));
+        if let Err(e) = player.set_volume(config.player.default_volume) {
+            tracing::error!("Failed to set the default volume: {}", e);
+        }
+
+        Ok(Self {
+            config,
+            db_state,
+            player_state,
+            led_strip_state: LedStripState::new(),
+            training_state: TrainingState::new(),
+            metrics,
+            socket_registry: SocketRegistry::new(),
+        })
+    }
 }
 
+#[cfg(test)]
 impl AppState {
-    pub async fn new() -> Self {
+    /// State backed by a throwaway database under the temp directory
+    pub async fn open_temp(name: &str) -> Self {
+        let metrics = Arc::new(Metrics::new());
         Self {
-            db_state: DBClientState::new().await,
-            player_state: PlayerState::new(),
+            config: Config::default(),
+            db_state: DBClientState::open_temp(name).await,
+            player_state: PlayerState::new(metrics.clone()),
             led_strip_state: LedStripState::new(),
+            training_state: TrainingState::new(),
+            metrics,
+            socket_registry: SocketRegistry::new(),
         }
     }
 }
@@ -0,0 +1,177 @@
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use crate::dao::db_state::DBClientState;
+use crate::dao::file_dao::{FileDao, FileEntry};
+use crate::dao::player_led_dao::{PlayerLedDao, PlayerLedEntry};
+
+/// Extensions picked up by `seed --tracks`
+const TRACK_EXTENSIONS: [&str; 5] = ["mp3", "flac", "wav", "ogg", "m4a"];
+
+/// LED strip settings written by `seed` when none are stored yet
+const DEFAULT_PLAYER_LED: PlayerLedEntry = PlayerLedEntry {
+    id: 1,
+    frequency: 0.5,
+    scale: 0.5,
+    red: 255,
+    green: 255,
+    blue: 255,
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Music player and LED strip server")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Default, PartialEq)]
+pub enum Command {
+    /// Start the web server (the default)
+    #[default]
+    Run,
+    /// Create or upgrade the database tables, then exit
+    Migrate,
+    /// Insert the default LED strip settings and, optionally, tracks from a directory
+    Seed {
+        /// Directory whose audio files are added to the file table
+        #[arg(long)]
+        tracks: Option<PathBuf>,
+    },
+}
+
+/// Run every DAO's table setup
+pub async fn migrate(db_state: &DBClientState) -> Result<(), sqlx::Error> {
+    FileDao::new(db_state).await?;
+    PlayerLedDao::new(db_state).await?;
+    Ok(())
+}
+
+/// Migrate, then fill in the rows a fresh install needs. Rows that already
+/// exist are left alone, so seeding twice is harmless.
+pub async fn seed(db_state: &DBClientState, tracks: Option<&Path>) -> anyhow::Result<()> {
+    migrate(db_state).await?;
+
+    let player_led_dao = PlayerLedDao::new(db_state).await?;
+    if player_led_dao.get_led_strip_status().await.is_err() {
+        player_led_dao
+            .set_led_strip_status(DEFAULT_PLAYER_LED)
+            .await?;
+        tracing::info!("Inserted default LED strip settings");
+    }
+
+    let Some(dir) = tracks else {
+        return Ok(());
+    };
+    let file_dao = FileDao::new(db_state).await?;
+    let created_at = chrono::Utc::now().timestamp_millis() as f64;
+    let mut inserted = 0;
+    for path in track_files(dir)? {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if file_dao.get_file_by_name(&name).await.is_some() {
+            continue;
+        }
+
+        let file_entry = FileEntry {
+            id: None,
+            name,
+            size: std::fs::metadata(&path)?.len() as f64,
+            path: path.to_string_lossy().to_string(),
+            class: 0,
+            is_training_data: Some(false),
+            created_at,
+        };
+        file_dao.insert_file(file_entry).await?;
+        inserted += 1;
+    }
+    tracing::info!("Inserted {} tracks from {}", inserted, dir.display());
+
+    Ok(())
+}
+
+/// Audio files directly inside `dir`, sorted by name
+fn track_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| TRACK_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn table_names(db_state: &DBClientState) -> Vec<String> {
+        sqlx::query_as::<_, (String,)>(
+            "SELECT name FROM sqlite_master WHERE type='table' ORDER BY name",
+        )
+        .fetch_all(&db_state.get_pool())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(name,)| name)
+        .collect()
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["server"]).unwrap();
+        assert_eq!(cli.command.unwrap_or_default(), Command::Run);
+        let cli = Cli::try_parse_from(["server", "migrate"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Migrate));
+        let cli = Cli::try_parse_from(["server", "seed", "--tracks", "music"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Seed {
+                tracks: Some(PathBuf::from("music"))
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_creates_tables() {
+        let db_state = DBClientState::open_temp("cli-migrate").await;
+        migrate(&db_state).await.unwrap();
+
+        let tables = table_names(&db_state).await;
+        assert!(tables.contains(&"file".to_string()), "{:?}", tables);
+        assert!(tables.contains(&"player_led".to_string()), "{:?}", tables);
+    }
+
+    #[tokio::test]
+    async fn test_seed_inserts_defaults_once() {
+        let db_state = DBClientState::open_temp("cli-seed").await;
+        let dir = std::env::temp_dir().join(format!("emes-seed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.mp3"), b"not really audio").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"skipped").unwrap();
+
+        seed(&db_state, Some(&dir)).await.unwrap();
+        seed(&db_state, Some(&dir)).await.unwrap();
+
+        let led = PlayerLedDao::new(&db_state)
+            .await
+            .unwrap()
+            .get_led_strip_status()
+            .await
+            .unwrap();
+        assert_eq!(led.id, DEFAULT_PLAYER_LED.id);
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file")
+            .fetch_one(&db_state.get_pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,361 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::ws2812::StripChip;
+
+const CONFIG_PATH_ENV: &str = "EMES_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+const DB_PATH_ENV: &str = "EMES_DB_PATH";
+const DEFAULT_DB_PATH: &str = "data.db";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub cors: CorsConfig,
+    pub upload: UploadConfig,
+    pub python: PythonConfig,
+    pub player: PlayerConfig,
+    pub led_strip: LedStripConfig,
+    pub database: DatabaseConfig,
+}
+
+impl Config {
+    /// Load the config from `$EMES_CONFIG` (or `config.json`), falling back to defaults
+    pub fn load() -> Self {
+        let path =
+            std::env::var(CONFIG_PATH_ENV).unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        Self::load_from(Path::new(&path))
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        if !path.exists() {
+            tracing::info!("No config file at {}, using defaults", path.display());
+            return Self::default();
+        }
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::error!("Failed to read config {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_str(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("Failed to parse config {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API. An entry without a port matches any port.
+    pub allowed_origins: Vec<String>,
+    /// Accept requests from any origin, only when explicitly opted in
+    pub allow_any_origin: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![
+                "http://localhost".to_string(),
+                "http://127.0.0.1".to_string(),
+            ],
+            allow_any_origin: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        if self.allow_any_origin {
+            return true;
+        }
+
+        self.allowed_origins.iter().any(|allowed| {
+            if origin == allowed {
+                return true;
+            }
+            match origin
+                .strip_prefix(allowed.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+            {
+                Some(port) => !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()),
+                None => false,
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    /// Largest accepted upload request body, raise it for large FLAC libraries
+    pub max_body_bytes: usize,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 200 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    /// Volume ramp on play, pause and stop to avoid clicks, 0 to switch instantly
+    pub fade_ms: u64,
+    /// Volume (0.0 to 1.0) the player starts with, kept across track changes
+    pub default_volume: f32,
+    /// Longest wait before auto-advancing past a track whose end can't be predicted
+    pub check_interval_ms: u64,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            fade_ms: 50,
+            default_volume: 1.0,
+            check_interval_ms: 1000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LedStripConfig {
+    /// spidev node the strip is wired to, e.g. `/dev/spidev0.1`
+    pub device: String,
+    pub num_leds: usize,
+    /// LED controller on the strip: `ws2812b`, `ws2815` or `sk6812`
+    pub chip: StripChip,
+    /// How long to wait at startup for the spidev node to appear, 0 to fail right away
+    pub device_wait_ms: u64,
+    /// Show the playback state on the strip with these colors, off when unset
+    pub follow_player: Option<PlaybackLedConfig>,
+    /// Played once at startup before the saved strip settings are applied
+    pub boot_animation: Option<BootAnimationConfig>,
+    /// Correction for the tint of the strip's white
+    pub white_balance: WhiteBalancePreset,
+}
+
+impl Default for LedStripConfig {
+    fn default() -> Self {
+        Self {
+            device: "/dev/spidev0.1".to_string(),
+            num_leds: 11,
+            chip: StripChip::default(),
+            device_wait_ms: 10_000,
+            follow_player: None,
+            boot_animation: None,
+            white_balance: WhiteBalancePreset::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WhiteBalancePreset {
+    /// Colors are sent as they are
+    #[default]
+    Uncorrected,
+    /// Tones down the green and blue cast of a typical WS2812 white
+    Neutral,
+    /// A yellower white, closer to an incandescent bulb
+    Warm,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct BootAnimationConfig {
+    #[serde(rename = "type")]
+    pub kind: BootAnimationKind,
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BootAnimationKind {
+    Chase,
+    Breathe,
+}
+
+/// Strip color for each playback state, `None` turns the strip off
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PlaybackLedConfig {
+    pub playing: Option<LedColorConfig>,
+    pub paused: Option<LedColorConfig>,
+    pub stopped: Option<LedColorConfig>,
+}
+
+impl Default for PlaybackLedConfig {
+    fn default() -> Self {
+        Self {
+            playing: Some(LedColorConfig {
+                red: 0,
+                green: 255,
+                blue: 0,
+                frequency: 0.5,
+                scale: 0.5,
+            }),
+            paused: Some(LedColorConfig {
+                red: 255,
+                green: 160,
+                blue: 0,
+                frequency: 0.0,
+                scale: 0.5,
+            }),
+            stopped: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct LedColorConfig {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// Breathe rate in Hz, 0 for a steady color
+    pub frequency: f64,
+    pub scale: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// SQLite file, see [`DatabaseConfig::resolve_path`]
+    pub path: Option<PathBuf>,
+}
+
+impl DatabaseConfig {
+    /// `$EMES_DB_PATH`, else the configured path, else `data.db` in the working directory
+    pub fn resolve_path(&self) -> PathBuf {
+        if let Some(path) = std::env::var_os(DB_PATH_ENV) {
+            return PathBuf::from(path);
+        }
+        self.path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DB_PATH))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PythonConfig {
+    /// Directory holding the Python modules, see [`PythonConfig::resolve_dir`]
+    pub dir: Option<PathBuf>,
+    /// Modules under the python dir that `/api/py-tasks/run` may import
+    pub allowed_modules: Vec<String>,
+}
+
+impl Default for PythonConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            allowed_modules: vec!["greet".to_string()],
+        }
+    }
+}
+
+impl PythonConfig {
+    /// The configured dir, else `python/` next to the executable, else the
+    /// source tree's `python/` for `cargo run`
+    pub fn resolve_dir(&self) -> PathBuf {
+        if let Some(dir) = &self.dir {
+            return dir.clone();
+        }
+
+        let beside_exe = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("python")));
+        match beside_exe {
+            Some(dir) if dir.is_dir() => dir,
+            _ => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("python"),
+        }
+    }
+
+    /// Only public functions of allowlisted modules can be called
+    pub fn is_function_allowed(&self, module: &str, function: &str) -> bool {
+        self.allowed_modules.iter().any(|allowed| allowed == module)
+            && !function.is_empty()
+            && !function.starts_with('_')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_cors_is_localhost_only() {
+        let cors = CorsConfig::default();
+        assert!(cors.is_origin_allowed("http://localhost"));
+        assert!(cors.is_origin_allowed("http://localhost:5173"));
+        assert!(cors.is_origin_allowed("http://127.0.0.1:8642"));
+        assert!(!cors.is_origin_allowed("http://192.168.1.20:5173"));
+        assert!(!cors.is_origin_allowed("http://localhost.example.com"));
+    }
+
+    #[test]
+    fn test_cors_allowlist() {
+        let cors: CorsConfig =
+            serde_json::from_str(r#"{ "allowed_origins": ["http://emes.lan:8080"] }"#).unwrap();
+        assert!(cors.is_origin_allowed("http://emes.lan:8080"));
+        assert!(!cors.is_origin_allowed("http://emes.lan:9090"));
+        assert!(!cors.is_origin_allowed("http://localhost:5173"));
+
+        let any: CorsConfig = serde_json::from_str(r#"{ "allow_any_origin": true }"#).unwrap();
+        assert!(any.is_origin_allowed("http://192.168.1.20:5173"));
+    }
+
+    #[test]
+    fn test_python_allowlist() {
+        let python = PythonConfig::default();
+        assert!(python.is_function_allowed("greet", "greet"));
+        assert!(!python.is_function_allowed("greet", "__import__"));
+        assert!(!python.is_function_allowed("os", "system"));
+    }
+
+    #[test]
+    fn test_python_dir_prefers_config() {
+        let python: PythonConfig =
+            serde_json::from_str(r#"{ "dir": "/opt/emes/python" }"#).unwrap();
+        assert_eq!(python.resolve_dir(), PathBuf::from("/opt/emes/python"));
+    }
+
+    #[test]
+    fn test_boot_animation_type() {
+        let led_strip: LedStripConfig = serde_json::from_str(
+            r#"{ "boot_animation": { "type": "chase", "duration_ms": 1500 } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            led_strip.boot_animation,
+            Some(BootAnimationConfig {
+                kind: BootAnimationKind::Chase,
+                duration_ms: 1500,
+            })
+        );
+    }
+
+    #[test]
+    fn test_follow_player_fills_in_default_colors() {
+        assert_eq!(LedStripConfig::default().follow_player, None);
+
+        let led_strip: LedStripConfig = serde_json::from_str(
+            r#"{ "follow_player": { "stopped": { "red": 0, "green": 0, "blue": 8, "frequency": 0, "scale": 1 } } }"#,
+        )
+        .unwrap();
+        let playback = led_strip.follow_player.unwrap();
+        assert_eq!(playback.paused, PlaybackLedConfig::default().paused);
+        assert_eq!(playback.stopped.unwrap().blue, 8);
+    }
+}
@@ -1,27 +1,153 @@
-use std::sync::{Arc, Mutex};
+use anyhow::Context;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::Duration;
 
 pub type DBPool = sqlx::sqlite::SqlitePool;
 
+const DB_BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// The pool is shared by cloning it, every clone hands out connections from
+/// the same set and each connection is used by one task at a time
 #[derive(Clone)]
 pub struct DBClientState {
-    pub inner: Arc<Mutex<DBPool>>,
+    pool: DBPool,
 }
 
 impl DBClientState {
-    pub async fn new() -> Self {
-        let db_uri = "data.db";
-        let db_file_path = std::path::Path::new(db_uri);
-        if !db_file_path.exists() {
-            std::fs::File::create(db_file_path).expect("Failed to create SQLite database file");
-        }
-        let conn = sqlx::sqlite::SqlitePool::connect(db_uri)
+    /// Open the database at `path`, creating it and any missing parent directories
+    pub async fn open(path: &Path) -> anyhow::Result<Self> {
+        ensure_writable(path)?;
+
+        // WAL lets readers and a writer work concurrently, busy_timeout waits out short locks
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(DB_BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
             .await
-            .expect("Failed to connect to SQLite database");
-        let inner = Arc::new(Mutex::new(conn));
-        Self { inner }
+            .with_context(|| format!("Failed to open the database {}", path.display()))?;
+        Ok(Self { pool })
     }
 
     pub fn get_pool(&self) -> DBPool {
-        self.inner.lock().unwrap().clone()
+        self.pool.clone()
+    }
+}
+
+#[cfg(test)]
+impl DBClientState {
+    /// A fresh database file in the temp dir, unique per test name.
+    pub async fn open_temp(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("emes-{}-{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        Self::open(&path)
+            .await
+            .expect("Failed to open the temp database")
+    }
+}
+
+/// Create the parent directories of `path` and check the file can be written,
+/// without touching what's already in it
+pub fn ensure_writable(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create the directory {}", parent.display()))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("{} is not writable", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Acquire as _;
+
+    #[tokio::test]
+    async fn test_concurrent_writes_under_wal() {
+        let path = std::env::temp_dir().join(format!("emes-wal-{}.db", std::process::id()));
+        let db_state = DBClientState::open(&path).await.unwrap();
+        let pool = db_state.get_pool();
+
+        let (mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(mode.to_lowercase(), "wal");
+
+        sqlx::query("CREATE TABLE IF NOT EXISTS t (v INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let writers = (0..2).map(|v| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                let mut conn = pool.acquire().await?;
+                let mut tx = conn.begin().await?;
+                sqlx::query("INSERT INTO t (v) VALUES (?)")
+                    .bind(v)
+                    .execute(&mut *tx)
+                    .await?;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                tx.commit().await
+            })
+        });
+        for writer in futures::future::join_all(writers).await {
+            assert!(writer.unwrap().is_ok());
+        }
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM t")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_creates_missing_parent_dirs() {
+        let root = std::env::temp_dir().join(format!("emes-db-dirs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("nested").join("deeper").join("data.db");
+
+        let db_state = DBClientState::open(&path).await.unwrap();
+        sqlx::query("CREATE TABLE t (v INTEGER)")
+            .execute(&db_state.get_pool())
+            .await
+            .unwrap();
+        assert!(path.is_file());
+
+        db_state.get_pool().close().await;
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_unwritable_path_is_an_error() {
+        // A directory exists at the path, so it can't be opened as the database file
+        let path = std::env::temp_dir().join(format!("emes-db-is-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+
+        let err = DBClientState::open(&path).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("{} is not writable", path.display())
+        );
+
+        let _ = std::fs::remove_dir_all(&path);
     }
 }
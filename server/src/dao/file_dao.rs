@@ -1,41 +1,53 @@
 use serde::Deserialize;
 use serde::Serialize;
 use sqlx::Acquire as _;
+use sqlx::Connection as _;
 use sqlx::Row;
+use sqlx::{QueryBuilder, Sqlite};
 
 use crate::api::utils::PaginationRequest;
 use crate::dao::db_state::DBClientState;
+use crate::dao::retry::with_retry;
+use crate::player::metadata::FileProbe;
+
+// Columns added to the file table after its first release, with their types
+const ADDED_COLUMNS: [(&str, &str); 3] = [
+    ("playable", "BOOLEAN"),
+    ("format", "TEXT"),
+    ("duration", "REAL"),
+];
 
 pub struct FileDao {
     db_client_state: DBClientState,
 }
 
 impl FileDao {
-    pub async fn new(db_client_state: &DBClientState) -> Self {
+    pub async fn new(db_client_state: &DBClientState) -> Result<Self, sqlx::Error> {
         let file_dao = FileDao {
             db_client_state: db_client_state.clone(),
         };
 
-        file_dao.init().await;
+        if let Err(e) = file_dao.init().await {
+            tracing::error!("Failed to initialize the file table: {}", e);
+            return Err(e);
+        }
 
-        file_dao
+        Ok(file_dao)
     }
 
     pub async fn get_file_by_name(&self, name: &str) -> Option<FileEntry> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
-
         let file_query = sqlx::query("SELECT * FROM file WHERE name = ?")
             .bind(name)
-            .fetch_one(&mut *tx)
+            .fetch_optional(&pool)
             .await;
-        if let Err(e) = file_query {
-            // tracing::error!("Failed to query file by name: {}", e);
-            return None;
-        }
-
-        let file_query = file_query.unwrap();
+        let file_query = match file_query {
+            Ok(file_query) => file_query?,
+            Err(e) => {
+                tracing::error!("Failed to query file by name: {}", e);
+                return None;
+            }
+        };
 
         Some(FileEntry {
             id: file_query.get("id"),
@@ -48,17 +60,39 @@ impl FileDao {
         })
     }
 
+    pub async fn get_file_by_id(&self, id: i64) -> Result<Option<FileEntry>, sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let row = sqlx::query("SELECT * FROM file WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await?;
+
+        Ok(row.map(|row| FileEntry {
+            id: row.get("id"),
+            name: row.get("name"),
+            size: row.get("size"),
+            path: row.get("path"),
+            class: row.get("class"),
+            is_training_data: row.get("is_training_data"),
+            created_at: row.get("created_at"),
+        }))
+    }
+
     pub async fn insert_file(&self, file_entry: FileEntry) -> Result<(), sqlx::Error> {
+        with_retry(|| self.try_insert_file(&file_entry)).await
+    }
+
+    async fn try_insert_file(&self, file_entry: &FileEntry) -> Result<(), sqlx::Error> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
 
         let insert_query = sqlx::query(
             "INSERT INTO file (name, size, path, class, created_at) VALUES (?, ?, ?, ?, ?)",
         )
-        .bind(file_entry.name)
+        .bind(file_entry.name.as_str())
         .bind(file_entry.size)
-        .bind(file_entry.path)
+        .bind(file_entry.path.as_str())
         .bind(file_entry.class)
         .bind(file_entry.created_at);
 
@@ -80,49 +114,27 @@ impl FileDao {
         &self,
         request: &PaginationRequest<FileEntryFilter>,
     ) -> Result<(Vec<FileEntry>, i64), sqlx::Error> {
+        // One read transaction so the total and the page come from the same snapshot
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
-
-        let mut query_str = String::from("SELECT * FROM file");
-        let mut query_count_str = String::from("SELECT COUNT(*) FROM file");
-        let mut conditions = Vec::new();
-        match &request.condition {
-            Some(filter) => {
-                if let Some(name) = &filter.name {
-                    conditions.push(format!("name = '{}'", name));
-                }
-
-                if let Some(class) = &filter.class {
-                    conditions.push(format!("class = '{}'", class));
-                }
-
-                if let Some(is_training_data) = &filter.is_training_data {
-                    conditions.push(format!("is_training_data = {}", is_training_data));
-                }
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
 
-                if !conditions.is_empty() {
-                    query_str += " WHERE ";
-                    query_str += &conditions.join(" AND ");
-                    query_count_str += " WHERE ";
-                    query_count_str += &conditions.join(" AND ");
-                }
-            }
-            None => {}
-        };
-
-        query_count_str += " ORDER BY id DESC";
-        let count_query = sqlx::query(&query_count_str).fetch_one(&mut *tx).await?;
+        let count_query = count_query(request.condition.as_ref())
+            .build()
+            .fetch_one(&mut *tx)
+            .await?;
         let count = count_query.get::<i64, _>(0);
 
-        query_str += &format!(
-            " ORDER BY id DESC LIMIT {} OFFSET {}",
-            request.page_size,
-            request.page * request.page_size
-        );
+        let mut paged_query = QueryBuilder::<Sqlite>::new("SELECT * FROM file");
+        push_conditions(&mut paged_query, request.condition.as_ref());
+        push_order(&mut paged_query, request.condition.as_ref());
+        paged_query
+            .push(" LIMIT ")
+            .push_bind(request.page_size)
+            .push(" OFFSET ")
+            .push_bind(request.page * request.page_size);
 
-        let paged_query = sqlx::query(&query_str);
-        let files_row = paged_query.fetch_all(&mut *tx).await?;
+        let files_row = paged_query.build().fetch_all(&mut *tx).await?;
         let files: Vec<FileEntry> = files_row
             .into_iter()
             .map(|row| FileEntry {
@@ -135,46 +147,119 @@ impl FileDao {
                 created_at: row.get("created_at"),
             })
             .collect();
+        tx.commit().await?;
 
         Ok((files, count))
     }
 
-    async fn init(&self) {
+    /// Count the files matching `filter` without fetching any rows.
+    pub async fn count_files(&self, filter: Option<&FileEntryFilter>) -> Result<i64, sqlx::Error> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
+        let row = count_query(filter).build().fetch_one(&pool).await?;
+        Ok(row.get::<i64, _>(0))
+    }
 
-        let table_query: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='file'")
-                .fetch_one(&mut *tx)
-                .await
-                .expect("Failed to check if file table exists");
-
-        if table_query.0 == 0 {
-            sqlx::query(
-                "CREATE TABLE file (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    name TEXT KEY NOT NULL,
-                    size REAL NOT NULL,
-                    path TEXT NOT NULL,
-                    class INTEGER NOT NULL,
-                    is_training_data BOOLEAN NOT NULL DEFAULT 0,
-                    created_at REAL NOT NULL,
-                    UNIQUE (name)
-                )",
-            )
-            .execute(&mut *tx)
-            .await
-            .expect("Failed to create file table");
+    async fn init(&self) -> Result<(), sqlx::Error> {
+        with_retry(|| self.try_init()).await
+    }
+
+    /// Create the table and add missing columns. The transaction takes the
+    /// write lock up front, so DAOs created at the same time can't both see a
+    /// column missing and race to add it.
+    async fn try_init(&self) -> Result<(), sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin_with("BEGIN IMMEDIATE").await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS file (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT KEY NOT NULL,
+                size REAL NOT NULL,
+                path TEXT NOT NULL,
+                class INTEGER NOT NULL,
+                is_training_data BOOLEAN NOT NULL DEFAULT 0,
+                created_at REAL NOT NULL,
+                UNIQUE (name)
+            )",
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (column, column_type) in ADDED_COLUMNS {
+            let (exists,): (i64,) =
+                sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info('file') WHERE name = ?")
+                    .bind(column)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            if exists == 0 {
+                sqlx::query(&format!(
+                    "ALTER TABLE file ADD COLUMN {} {}",
+                    column, column_type
+                ))
+                .execute(&mut *tx)
+                .await?;
+            }
         }
 
-        tx.commit().await.expect("Failed to commit transaction");
+        tx.commit().await
+    }
+
+    /// The cached probe of a file, `None` when it was never probed or doesn't
+    /// exist.
+    pub async fn get_probe(&self, id: i64) -> Result<Option<FileProbe>, sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let row = sqlx::query(
+            "SELECT playable, format, duration FROM file WHERE id = ? AND playable IS NOT NULL",
+        )
+        .bind(id)
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(row.map(|row| FileProbe {
+            playable: row.get("playable"),
+            format: row.get("format"),
+            duration_sec: row.get("duration"),
+        }))
+    }
+
+    pub async fn set_probe(&self, id: i64, probe: &FileProbe) -> Result<(), sqlx::Error> {
+        with_retry(|| self.try_set_probe(id, probe)).await
     }
 
-    pub async fn update_class(&self, req: &UpdateClassRequest) -> Result<(), sqlx::Error> {
+    async fn try_set_probe(&self, id: i64, probe: &FileProbe) -> Result<(), sqlx::Error> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
+
+        let update_query =
+            sqlx::query("UPDATE file SET playable = ?, format = ?, duration = ? WHERE id = ?")
+                .bind(probe.playable)
+                .bind(probe.format.as_deref())
+                .bind(probe.duration_sec)
+                .bind(id);
+        if let Err(e) = update_query.execute(&mut *tx).await {
+            tracing::error!("Failed to cache file probe: {}", e);
+            return Err(e);
+        }
+
+        if let Err(e) = tx.commit().await {
+            tracing::error!("Failed to commit transaction: {}", e);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Returns `false` when no file has the given id.
+    pub async fn update_class(&self, req: &UpdateClassRequest) -> Result<bool, sqlx::Error> {
+        with_retry(|| self.try_update_class(req)).await
+    }
+
+    async fn try_update_class(&self, req: &UpdateClassRequest) -> Result<bool, sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
 
         let update_query = sqlx::query("UPDATE file SET class = ? WHERE id = ?")
             .bind(req.class)
@@ -184,16 +269,172 @@ impl FileDao {
             tracing::error!("Failed to update class: {}", e);
             return Err(e);
         }
+        let updated = update_query.unwrap().rows_affected() > 0;
 
         if let Err(e) = tx.commit().await {
             tracing::error!("Failed to commit transaction: {}", e);
             return Err(e);
         }
 
-        Ok(())
+        Ok(updated)
+    }
+
+    /// Returns `false` when no file has the given id.
+    pub async fn update_training_flag(
+        &self,
+        req: &UpdateTrainingFlagRequest,
+    ) -> Result<bool, sqlx::Error> {
+        with_retry(|| self.try_update_training_flag(req)).await
+    }
+
+    async fn try_update_training_flag(
+        &self,
+        req: &UpdateTrainingFlagRequest,
+    ) -> Result<bool, sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
+
+        let update_query = sqlx::query("UPDATE file SET is_training_data = ? WHERE id = ?")
+            .bind(req.is_training_data)
+            .bind(req.id);
+        let update_query = update_query.execute(&mut *tx).await;
+        if let Err(e) = update_query {
+            tracing::error!("Failed to update training flag: {}", e);
+            return Err(e);
+        }
+        let updated = update_query.unwrap().rows_affected() > 0;
+
+        if let Err(e) = tx.commit().await {
+            tracing::error!("Failed to commit transaction: {}", e);
+            return Err(e);
+        }
+
+        Ok(updated)
+    }
+
+    /// Apply `action` to every id in one transaction, `false` for ids that
+    /// match no file. Deleting only removes the rows, not the audio files.
+    pub async fn bulk_update(&self, req: &BulkRequest) -> Result<Vec<bool>, sqlx::Error> {
+        with_retry(|| self.try_bulk_update(req)).await
+    }
+
+    async fn try_bulk_update(&self, req: &BulkRequest) -> Result<Vec<bool>, sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
+
+        let mut updated = Vec::with_capacity(req.ids.len());
+        for id in &req.ids {
+            let query = match req.action {
+                BulkAction::SetClass(class) => {
+                    sqlx::query("UPDATE file SET class = ? WHERE id = ?").bind(class)
+                }
+                BulkAction::SetTraining(is_training_data) => {
+                    sqlx::query("UPDATE file SET is_training_data = ? WHERE id = ?")
+                        .bind(is_training_data)
+                }
+                BulkAction::Delete => sqlx::query("DELETE FROM file WHERE id = ?"),
+            };
+            match query.bind(*id).execute(&mut *tx).await {
+                Ok(result) => updated.push(result.rows_affected() > 0),
+                Err(e) => {
+                    tracing::error!("Failed to apply {:?} to file {}: {}", req.action, id, e);
+                    return Err(e);
+                }
+            }
+        }
+
+        if let Err(e) = tx.commit().await {
+            tracing::error!("Failed to commit transaction: {}", e);
+            return Err(e);
+        }
+
+        Ok(updated)
+    }
+}
+
+fn count_query(filter: Option<&FileEntryFilter>) -> QueryBuilder<'_, Sqlite> {
+    let mut query = QueryBuilder::new("SELECT COUNT(*) FROM file");
+    push_conditions(&mut query, filter);
+    query
+}
+
+/// Append the filter as bound `WHERE` conditions joined with `AND`.
+fn push_conditions<'a>(query: &mut QueryBuilder<'a, Sqlite>, filter: Option<&'a FileEntryFilter>) {
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return,
+    };
+
+    let mut first = true;
+    let mut next_condition = |query: &mut QueryBuilder<'a, Sqlite>| {
+        query.push(if first { " WHERE " } else { " AND " });
+        first = false;
+    };
+
+    if let Some(name) = &filter.name {
+        next_condition(query);
+        query.push("name = ").push_bind(name.as_str());
+    }
+
+    if let Some(class) = filter.class {
+        next_condition(query);
+        query.push("class = ").push_bind(class);
+    }
+
+    if let Some(is_training_data) = filter.is_training_data {
+        next_condition(query);
+        query
+            .push("is_training_data = ")
+            .push_bind(is_training_data);
+    }
+
+    if let Some(created_after) = filter.created_after {
+        next_condition(query);
+        query.push("created_at >= ").push_bind(created_after);
+    }
+
+    if let Some(created_before) = filter.created_before {
+        next_condition(query);
+        query.push("created_at < ").push_bind(created_before);
+    }
+
+    if let Some(search) = filter.search.as_deref().filter(|s| !s.is_empty()) {
+        next_condition(query);
+        query
+            .push("name LIKE ")
+            .push_bind(format!("%{}%", escape_like(search)))
+            .push(" ESCAPE '\\'");
     }
 }
 
+/// Append the `ORDER BY` clause, defaulting to newest first.
+fn push_order(query: &mut QueryBuilder<'_, Sqlite>, filter: Option<&FileEntryFilter>) {
+    let order_by = filter.and_then(|f| f.order_by).unwrap_or_default();
+    let order_dir = filter.and_then(|f| f.order_dir).unwrap_or_default();
+    query
+        .push(" ORDER BY ")
+        .push(order_by.column())
+        .push(" ")
+        .push(order_dir.keyword());
+    if order_by != FileOrderBy::Id {
+        // Keep pages stable when several rows share the sort key
+        query.push(", id ").push(order_dir.keyword());
+    }
+}
+
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileEntry {
     pub id: Option<i64>,
@@ -210,6 +451,68 @@ pub struct FileEntryFilter {
     pub name: Option<String>,
     pub class: Option<i32>,
     pub is_training_data: Option<bool>,
+    /// Inclusive lower bound on `created_at` (milliseconds since the epoch)
+    pub created_after: Option<f64>,
+    /// Exclusive upper bound on `created_at` (milliseconds since the epoch)
+    pub created_before: Option<f64>,
+    /// Case-insensitive substring match on `name`
+    pub search: Option<String>,
+    pub order_by: Option<FileOrderBy>,
+    pub order_dir: Option<OrderDirection>,
+}
+
+/// Columns the file list may be sorted by. Only these ever reach the SQL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrderBy {
+    #[default]
+    Id,
+    Name,
+    CreatedAt,
+    Size,
+}
+
+impl FileOrderBy {
+    fn column(self) -> &'static str {
+        match self {
+            FileOrderBy::Id => "id",
+            FileOrderBy::Name => "name",
+            FileOrderBy::CreatedAt => "created_at",
+            FileOrderBy::Size => "size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl OrderDirection {
+    fn keyword(self) -> &'static str {
+        match self {
+            OrderDirection::Asc => "ASC",
+            OrderDirection::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkRequest {
+    pub ids: Vec<i64>,
+    pub action: BulkAction,
+}
+
+/// `{ "set_class": 2 }`, `{ "set_training": true }` or `"delete"`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    SetClass(i32),
+    SetTraining(bool),
+    Delete,
 }
 
 #[derive(Debug, Deserialize)]
@@ -217,3 +520,299 @@ pub struct UpdateClassRequest {
     pub id: i64,
     pub class: i32,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTrainingFlagRequest {
+    pub id: i64,
+    pub is_training_data: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_entry(name: &str, class: i32, created_at: f64) -> FileEntry {
+        FileEntry {
+            id: None,
+            name: name.to_string(),
+            size: 1024.0,
+            path: format!("/music/{}", name),
+            class,
+            is_training_data: Some(false),
+            created_at,
+        }
+    }
+
+    fn filter() -> FileEntryFilter {
+        FileEntryFilter {
+            name: None,
+            class: None,
+            is_training_data: None,
+            created_after: None,
+            created_before: None,
+            search: None,
+            order_by: None,
+            order_dir: None,
+        }
+    }
+
+    fn page(condition: FileEntryFilter) -> PaginationRequest<FileEntryFilter> {
+        PaginationRequest {
+            page: 0,
+            page_size: 100,
+            condition: Some(condition),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_class_and_training_flag() {
+        let db_state = DBClientState::open_temp("file-dao-update").await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+        file_dao
+            .insert_file(file_entry("a.mp3", 0, 1.0))
+            .await
+            .unwrap();
+        let id = file_dao
+            .get_file_by_name("a.mp3")
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+
+        let updated = file_dao
+            .update_class(&UpdateClassRequest { id, class: 2 })
+            .await
+            .unwrap();
+        assert!(updated);
+        let updated = file_dao
+            .update_training_flag(&UpdateTrainingFlagRequest {
+                id,
+                is_training_data: true,
+            })
+            .await
+            .unwrap();
+        assert!(updated);
+
+        let (files, count) = file_dao
+            .get_files(&page(FileEntryFilter {
+                class: Some(2),
+                is_training_data: Some(true),
+                ..filter()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(files[0].name, "a.mp3");
+    }
+
+    async fn dao_with_timestamps(name: &str) -> FileDao {
+        let db_state = DBClientState::open_temp(name).await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+        for (i, created_at) in [100.0, 200.0, 300.0].into_iter().enumerate() {
+            file_dao
+                .insert_file(file_entry(&format!("{}.mp3", i), 0, created_at))
+                .await
+                .unwrap();
+        }
+        file_dao
+    }
+
+    #[tokio::test]
+    async fn test_get_files_created_after() {
+        let file_dao = dao_with_timestamps("file-dao-after").await;
+        let (files, count) = file_dao
+            .get_files(&page(FileEntryFilter {
+                created_after: Some(200.0),
+                ..filter()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(files.iter().all(|f| f.created_at >= 200.0));
+    }
+
+    #[tokio::test]
+    async fn test_get_files_created_range() {
+        let file_dao = dao_with_timestamps("file-dao-range").await;
+        let (files, count) = file_dao
+            .get_files(&page(FileEntryFilter {
+                class: Some(0),
+                created_after: Some(150.0),
+                created_before: Some(300.0),
+                ..filter()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(files[0].created_at, 200.0);
+    }
+
+    async fn dao_for_ordering(name: &str) -> FileDao {
+        let db_state = DBClientState::open_temp(name).await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+        for (file_name, size, created_at) in [
+            ("b_song.mp3", 300.0, 100.0),
+            ("c-song.mp3", 100.0, 200.0),
+            ("a_tune.mp3", 200.0, 300.0),
+        ] {
+            file_dao
+                .insert_file(FileEntry {
+                    size,
+                    ..file_entry(file_name, 0, created_at)
+                })
+                .await
+                .unwrap();
+        }
+        file_dao
+    }
+
+    async fn ordered_names(file_dao: &FileDao, condition: FileEntryFilter) -> Vec<String> {
+        let (files, _) = file_dao.get_files(&page(condition)).await.unwrap();
+        files.into_iter().map(|f| f.name).collect()
+    }
+
+    #[tokio::test]
+    async fn test_count_files_matches_rows() {
+        let file_dao = dao_for_ordering("file-dao-count").await;
+        let condition = FileEntryFilter {
+            search: Some("song".to_string()),
+            ..filter()
+        };
+
+        let count = file_dao.count_files(Some(&condition)).await.unwrap();
+        let (files, _) = file_dao.get_files(&page(condition)).await.unwrap();
+        assert_eq!(count, files.len() as i64);
+        assert_eq!(file_dao.count_files(None).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_files_ordering() {
+        let file_dao = dao_for_ordering("file-dao-order").await;
+
+        assert_eq!(
+            ordered_names(&file_dao, filter()).await,
+            ["a_tune.mp3", "c-song.mp3", "b_song.mp3"]
+        );
+        let cases = [
+            (
+                FileOrderBy::Name,
+                ["a_tune.mp3", "b_song.mp3", "c-song.mp3"],
+            ),
+            (
+                FileOrderBy::CreatedAt,
+                ["b_song.mp3", "c-song.mp3", "a_tune.mp3"],
+            ),
+            (
+                FileOrderBy::Size,
+                ["c-song.mp3", "a_tune.mp3", "b_song.mp3"],
+            ),
+        ];
+        for (order_by, ascending) in cases {
+            let asc = ordered_names(
+                &file_dao,
+                FileEntryFilter {
+                    order_by: Some(order_by),
+                    order_dir: Some(OrderDirection::Asc),
+                    ..filter()
+                },
+            )
+            .await;
+            assert_eq!(asc, ascending, "{:?} asc", order_by);
+
+            let mut desc = ordered_names(
+                &file_dao,
+                FileEntryFilter {
+                    order_by: Some(order_by),
+                    ..filter()
+                },
+            )
+            .await;
+            desc.reverse();
+            assert_eq!(desc, ascending, "{:?} desc", order_by);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_files_search() {
+        let file_dao = dao_for_ordering("file-dao-search").await;
+
+        let (files, count) = file_dao
+            .get_files(&page(FileEntryFilter {
+                search: Some("SONG".to_string()),
+                order_by: Some(FileOrderBy::Name),
+                order_dir: Some(OrderDirection::Asc),
+                ..filter()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(files[0].name, "b_song.mp3");
+
+        // `_` is matched literally rather than as a wildcard
+        let (_, count) = file_dao
+            .get_files(&page(FileEntryFilter {
+                search: Some("_song".to_string()),
+                ..filter()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_order_params_reject_unknown_values() {
+        assert!(serde_json::from_str::<FileOrderBy>(r#""created_at""#).is_ok());
+        assert!(serde_json::from_str::<FileOrderBy>(r#""id; DROP TABLE file""#).is_err());
+        assert!(serde_json::from_str::<OrderDirection>(r#""sideways""#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_missing_id() {
+        let db_state = DBClientState::open_temp("file-dao-missing").await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+
+        let updated = file_dao
+            .update_class(&UpdateClassRequest { id: 42, class: 1 })
+            .await
+            .unwrap();
+        assert!(!updated);
+        let updated = file_dao
+            .update_training_flag(&UpdateTrainingFlagRequest {
+                id: 42,
+                is_training_data: true,
+            })
+            .await
+            .unwrap();
+        assert!(!updated);
+    }
+
+    #[tokio::test]
+    async fn test_many_sequential_reads() {
+        let db_state = DBClientState::open_temp("file-dao-reads").await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+        file_dao
+            .insert_file(file_entry("a.mp3", 0, 0.0))
+            .await
+            .unwrap();
+
+        // Far more reads than the pool has connections, each must hand its back
+        let reads = async {
+            for _ in 0..200 {
+                assert!(file_dao.get_file_by_name("a.mp3").await.is_some());
+                assert!(file_dao.get_file_by_name("missing.mp3").await.is_none());
+                let (files, count) = file_dao.get_files(&page(filter())).await.unwrap();
+                assert_eq!((files.len(), count), (1, 1));
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(20), reads)
+            .await
+            .expect("reads stalled waiting for a connection");
+
+        // Nothing left open: a writer isn't blocked by a dangling read
+        let updated = file_dao
+            .update_class(&UpdateClassRequest { id: 1, class: 2 })
+            .await
+            .unwrap();
+        assert!(updated);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod db_state;
 pub mod file_dao;
 pub mod player_led_dao;
+pub mod retry;
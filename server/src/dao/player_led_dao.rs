@@ -4,40 +4,37 @@ use sqlx::Acquire as _;
 use sqlx::Row;
 
 use crate::dao::db_state::DBClientState;
+use crate::dao::retry::with_retry;
+use crate::ws2812::Color;
 
 pub struct PlayerLedDao {
     db_client_state: DBClientState,
 }
 
 impl PlayerLedDao {
-    pub async fn new(db_client_state: &DBClientState) -> Self {
+    pub async fn new(db_client_state: &DBClientState) -> Result<Self, sqlx::Error> {
         let player_led_dao = PlayerLedDao {
             db_client_state: db_client_state.clone(),
         };
 
-        player_led_dao.init().await;
+        if let Err(e) = player_led_dao.init().await {
+            tracing::error!("Failed to initialize the player_led table: {}", e);
+            return Err(e);
+        }
 
-        player_led_dao
+        Ok(player_led_dao)
     }
 
     pub async fn get_led_strip_status(&self) -> Result<PlayerLedEntry, sqlx::Error> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
-
         let led_strip_query = sqlx::query("SELECT * FROM player_led")
-            .fetch_one(&mut *tx)
+            .fetch_one(&pool)
             .await;
         if let Err(e) = led_strip_query {
             tracing::error!("Failed to query led strip: {}", e);
             return Err(e);
         }
 
-        if let Err(e) = tx.commit().await {
-            tracing::error!("Failed to commit transaction: {}", e);
-            return Err(e);
-        }
-
         let led_strip_query = led_strip_query.unwrap();
         let led_strip_entry = PlayerLedEntry {
             id: led_strip_query.get("id"),
@@ -52,54 +49,29 @@ impl PlayerLedDao {
     }
 
     pub async fn set_led_strip_status(&self, req: PlayerLedEntry) -> Result<(), sqlx::Error> {
-        let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
-
-        let check_query = sqlx::query("SELECT COUNT(*) FROM player_led WHERE id = ?")
-            .bind(req.id)
-            .fetch_one(&mut *tx)
-            .await;
-        if let Err(e) = check_query {
-            tracing::error!("Failed to check if led strip exists: {}", e);
-            return Err(e);
-        }
+        with_retry(|| self.try_set_led_strip_status(req)).await
+    }
 
-        // Properly extract the count from the row using get::<type, &str>("column_name")
-        let count: i64 = check_query.unwrap().get::<i64, _>("COUNT(*)");
-        // If the led strip does not exist, create it
-        if count == 0 {
-            let insert_query = sqlx::query("INSERT INTO player_led (id, frequency, scale, red, green, blue) VALUES (?, ?, ?, ?, ?, ?)")
-                .bind(req.id)
-                .bind(req.frequency)
-                .bind(req.scale)
-                .bind(req.red)
-                .bind(req.green)
-                .bind(req.blue);
-            let insert_query = insert_query.execute(&mut *tx).await;
-            if let Err(e) = insert_query {
-                tracing::error!("Failed to insert led strip: {}", e);
-                return Err(e);
-            }
-
-            if let Err(e) = tx.commit().await {
-                tracing::error!("Failed to commit transaction: {}", e);
-                return Err(e);
-            }
-
-            return Ok(());
-        }
+    /// Insert or update the row in one statement, so concurrent writers can't
+    /// both see it missing and race to insert it
+    async fn try_set_led_strip_status(&self, req: PlayerLedEntry) -> Result<(), sqlx::Error> {
+        let pool = self.db_client_state.get_pool();
+        let mut conn = pool.acquire().await?;
+        let mut tx = conn.begin().await?;
 
-        let update_query = sqlx::query("UPDATE player_led SET frequency = ?, scale = ?, red = ?, green = ?, blue = ? WHERE id = ?")
-            .bind(req.frequency)
-            .bind(req.scale)
-            .bind(req.red)
-            .bind(req.green)
-            .bind(req.blue)
-            .bind(req.id);
-        let update_query = update_query.execute(&mut *tx).await;
-        if let Err(e) = update_query {
-            tracing::error!("Failed to update led strip: {}", e);
+        let upsert_query = sqlx::query(
+            "INSERT INTO player_led (id, frequency, scale, red, green, blue) VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (id) DO UPDATE SET frequency = excluded.frequency, scale = excluded.scale,
+                 red = excluded.red, green = excluded.green, blue = excluded.blue",
+        )
+        .bind(req.id)
+        .bind(req.frequency)
+        .bind(req.scale)
+        .bind(req.red)
+        .bind(req.green)
+        .bind(req.blue);
+        if let Err(e) = upsert_query.execute(&mut *tx).await {
+            tracing::error!("Failed to set led strip: {}", e);
             return Err(e);
         }
 
@@ -111,36 +83,20 @@ impl PlayerLedDao {
         Ok(())
     }
 
-    async fn init(&self) {
+    /// One statement, so DAOs created at the same time don't race to create the table
+    async fn init(&self) -> Result<(), sqlx::Error> {
         let pool = self.db_client_state.get_pool();
-        let mut conn = pool.acquire().await.unwrap();
-        let mut tx = conn.begin().await.unwrap();
-
-        let table_query: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='player_led'",
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .expect("Failed to check if player_led table exists");
-
-        if table_query.0 == 0 {
-            sqlx::query(
-                "CREATE TABLE player_led (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    frequency REAL NOT NULL,
-                    scale REAL NOT NULL,
-                    red INTEGER NOT NULL,
-                    green INTEGER NOT NULL,
-                    blue INTEGER NOT NULL,
-                    UNIQUE (id)
-                )",
-            )
-            .execute(&mut *tx)
-            .await
-            .expect("Failed to create player_led table");
-        }
-
-        tx.commit().await.expect("Failed to commit transaction");
+        let create_table = "CREATE TABLE IF NOT EXISTS player_led (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            frequency REAL NOT NULL,
+            scale REAL NOT NULL,
+            red INTEGER NOT NULL,
+            green INTEGER NOT NULL,
+            blue INTEGER NOT NULL,
+            UNIQUE (id)
+        )";
+        with_retry(|| sqlx::query(create_table).execute(&pool)).await?;
+        Ok(())
     }
 }
 
@@ -153,3 +109,96 @@ pub struct PlayerLedEntry {
     pub green: u8,
     pub blue: u8,
 }
+
+impl From<PlayerLedEntry> for Color {
+    fn from(entry: PlayerLedEntry) -> Self {
+        Color::new(entry.red, entry.green, entry.blue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dao::file_dao::{FileDao, FileEntry, UpdateClassRequest};
+
+    const WRITERS: i64 = 4;
+    const ROUNDS: u8 = 20;
+
+    #[tokio::test]
+    async fn test_interleaved_writes_are_not_lost() {
+        let db_state = DBClientState::open_temp("player-led-concurrent").await;
+        let file_dao = FileDao::new(&db_state).await.unwrap();
+        let mut file_ids = Vec::new();
+        for i in 0..WRITERS {
+            let name = format!("concurrent-{}.mp3", i);
+            file_dao
+                .insert_file(FileEntry {
+                    id: None,
+                    name: name.clone(),
+                    size: 0.0,
+                    path: format!("/music/{}", name),
+                    class: 0,
+                    is_training_data: Some(false),
+                    created_at: 0.0,
+                })
+                .await
+                .unwrap();
+            file_ids.push(file_dao.get_file_by_name(&name).await.unwrap().id.unwrap());
+        }
+
+        // Each LED row and each file is written by one task, all tasks at once
+        let mut tasks = Vec::new();
+        for (i, file_id) in file_ids.into_iter().enumerate() {
+            let led_state = db_state.clone();
+            tasks.push(tokio::spawn(async move {
+                let player_led_dao = PlayerLedDao::new(&led_state).await.unwrap();
+                for round in 1..=ROUNDS {
+                    player_led_dao
+                        .set_led_strip_status(PlayerLedEntry {
+                            id: i as i64 + 1,
+                            frequency: 0.5,
+                            scale: 1.0,
+                            red: round,
+                            green: 0,
+                            blue: 0,
+                        })
+                        .await?;
+                }
+                Ok::<_, sqlx::Error>(())
+            }));
+            let file_state = db_state.clone();
+            tasks.push(tokio::spawn(async move {
+                let file_dao = FileDao::new(&file_state).await.unwrap();
+                for round in 1..=ROUNDS {
+                    file_dao
+                        .update_class(&UpdateClassRequest {
+                            id: file_id,
+                            class: round as i32,
+                        })
+                        .await?;
+                }
+                Ok(())
+            }));
+        }
+        for task in futures::future::join_all(tasks).await {
+            task.unwrap().unwrap();
+        }
+
+        let pool = db_state.get_pool();
+        let led_rows: Vec<(i64, u8)> = sqlx::query_as("SELECT id, red FROM player_led ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let expected: Vec<(i64, u8)> = (1..=WRITERS).map(|id| (id, ROUNDS)).collect();
+        assert_eq!(led_rows, expected);
+        let classes: Vec<(i32,)> = sqlx::query_as("SELECT class FROM file")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(
+            classes.iter().all(|&(class,)| class == ROUNDS as i32),
+            "{:?}",
+            classes
+        );
+    }
+}
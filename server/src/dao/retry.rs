@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::time::Duration;
+
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_millis(20);
+
+// Primary result codes, extended codes like SQLITE_BUSY_SNAPSHOT share the low byte
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+/// Whether the error is a transient "database is locked" failure worth retrying
+pub fn is_busy_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db_err) => {
+            let code = db_err
+                .code()
+                .and_then(|code| code.parse::<i32>().ok())
+                .map(|code| code & 0xff);
+            matches!(code, Some(SQLITE_BUSY) | Some(SQLITE_LOCKED))
+                || db_err.message().contains("database is locked")
+        }
+        _ => false,
+    }
+}
+
+/// Run a write operation, retrying with exponential backoff while SQLite reports busy/locked.
+pub async fn with_retry<T, F, Fut>(mut op: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if attempt < MAX_RETRIES && is_busy_error(&e) => {
+                attempt += 1;
+                let backoff = BASE_BACKOFF * 2u32.pow(attempt - 1);
+                tracing::warn!(attempt, "Database busy, retrying in {:?}: {}", backoff, e);
+                tokio::time::sleep(backoff).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    #[tokio::test]
+    async fn test_with_retry_waits_out_a_lock() {
+        let path = std::env::temp_dir().join(format!("emes-retry-{}.db", std::process::id()));
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .busy_timeout(Duration::ZERO);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(2)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE IF NOT EXISTS t (v INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Hold an exclusive lock on one connection and release it shortly after
+        let mut locker = pool.acquire().await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE")
+            .execute(&mut *locker)
+            .await
+            .unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sqlx::query("COMMIT").execute(&mut *locker).await.unwrap();
+        });
+
+        let res = with_retry(|| sqlx::query("INSERT INTO t (v) VALUES (1)").execute(&pool)).await;
+        assert!(res.is_ok());
+
+        release.await.unwrap();
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
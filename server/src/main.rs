@@ -1,4 +1,5 @@
-use pyo3::prelude::*;
+use axum::http::HeaderValue;
+use clap::Parser;
 use socketioxide::SocketIo;
 use std::sync::Arc;
 use tokio::signal;
@@ -11,19 +12,31 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 // Remove unresolved imports and fix module usage
 mod api;
 mod app_state;
+mod cli;
+mod config;
 mod dao;
+mod metrics;
 mod player;
 mod sock_io;
+mod startup;
+mod supervisor;
 mod ws2812;
 
+use api::py_tasks::py_tasks;
 use app_state::AppState;
+use cli::{Cli, Command};
+use config::{Config, CorsConfig};
+use dao::db_state::DBClientState;
+use supervisor::RestartPolicy;
 
-use crate::ws2812::Ws2812StripTask;
+use crate::ws2812::{Ws2812StripTask, follow_player};
 
 const SERVER_ADDR: &str = "0.0.0.0:8642";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -32,19 +45,45 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Ensure Python can import modules from the crate's `python` directory
-    Python::with_gil(|py| {
-        let result: PyResult<()> = (|| {
-            let sys = py.import("sys")?;
-            let path = sys.getattr("path")?;
-            let python_dir = format!("{}/python", env!("CARGO_MANIFEST_DIR"));
-            let _ = path.call_method1("insert", (0, python_dir));
+    match cli.command.unwrap_or_default() {
+        Command::Run => run(Config::load()).await,
+        Command::Migrate => {
+            cli::migrate(&open_database(&Config::load()).await?).await?;
+            tracing::info!("Database tables are up to date");
             Ok(())
-        })();
-        if let Err(e) = result {
-            tracing::error!("Failed to extend sys.path for Python modules: {}", e);
         }
-    });
+        Command::Seed { tracks } => {
+            cli::seed(&open_database(&Config::load()).await?, tracks.as_deref()).await
+        }
+    }
+}
+
+async fn open_database(config: &Config) -> anyhow::Result<DBClientState> {
+    DBClientState::open(&config.database.resolve_path()).await
+}
+
+async fn run(config: Config) -> anyhow::Result<()> {
+    // Report missing hardware up front; only a fatal issue stops the server,
+    // everything else runs with the affected feature disabled
+    let issues = startup::validate_config(&config);
+    startup::report(&issues);
+    if issues.iter().any(|issue| issue.fatal) {
+        anyhow::bail!("Startup checks failed, see the errors above");
+    }
+
+    // Ensure Python can import modules from the `python` directory
+    let python_dir = config.python.resolve_dir();
+    if !python_dir.is_dir() {
+        tracing::warn!("Python directory {} does not exist", python_dir.display());
+    }
+    match py_tasks::add_to_sys_path(&python_dir) {
+        Ok(()) => tracing::info!("Loading Python modules from {}", python_dir.display()),
+        Err(e) => tracing::error!(
+            "Failed to add {} to sys.path for Python modules: {}",
+            python_dir.display(),
+            e
+        ),
+    }
 
     // let service = StreamableHttpService::new(
     //     || Ok(Qwen2VLService::new()),
@@ -52,13 +91,9 @@ async fn main() -> anyhow::Result<()> {
     //     Default::default(),
     // );
 
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .allow_origin(AllowOrigin::any())
-        .expose_headers(Any);
+    let cors = cors_layer(&config.cors);
 
-    let app_state = Arc::new(AppState::new().await);
+    let app_state = Arc::new(AppState::new(config).await?);
     let tracker = TaskTracker::new();
     let shutdown_token = CancellationToken::new();
     background_tasks(app_state.clone(), tracker.clone(), shutdown_token.clone()).await;
@@ -68,6 +103,19 @@ async fn main() -> anyhow::Result<()> {
         .build_layer();
 
     sock_io::io_ai_ns(&io).await;
+    sock_io::io_player_ns(&io).await;
+    sock_io::forward_now_playing(
+        io.clone(),
+        app_state.clone(),
+        app_state
+            .player_state
+            .get_music_player()
+            .subscribe_now_playing(),
+    );
+    sock_io::forward_training_progress(io.clone(), app_state.training_state.subscribe());
+    if let Err(e) = app_state.training_state.register_python_callback() {
+        tracing::error!("Failed to register Python training callback: {}", e);
+    }
 
     let router = axum::Router::new()
         .nest("/api", api::routes::routes(app_state.clone()))
@@ -84,22 +132,84 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn cors_layer(cors_config: &CorsConfig) -> CorsLayer {
+    let allow_origin = if cors_config.allow_any_origin {
+        AllowOrigin::any()
+    } else {
+        let cors_config = cors_config.clone();
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .map(|origin| cors_config.is_origin_allowed(origin))
+                .unwrap_or(false)
+        })
+    };
+
+    CorsLayer::new()
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .allow_origin(allow_origin)
+        .expose_headers(Any)
+}
+
 async fn background_tasks(
     app_state: Arc<AppState>,
     tracker: TaskTracker,
     shutdown_token: CancellationToken,
 ) {
-    let led_strip_task_shutdown_token = shutdown_token.clone();
-
     let player = app_state.player_state.get_music_player();
-    tracker.spawn(async move {
-        player.run(shutdown_token).await;
-    });
-
-    let led_strip_task = Ws2812StripTask::new(app_state.clone());
-    tracker.spawn(async move {
-        led_strip_task.run(led_strip_task_shutdown_token).await;
-    });
+    let player_shutdown_token = shutdown_token.clone();
+    supervisor::spawn_supervised(
+        &tracker,
+        "music_player",
+        RestartPolicy::default(),
+        shutdown_token.clone(),
+        move || {
+            let player = player.clone();
+            let shutdown_token = player_shutdown_token.clone();
+            async move {
+                player.run(shutdown_token).await;
+            }
+        },
+    );
+
+    if let Some(playback_leds) = app_state.config.led_strip.follow_player.clone() {
+        let app_state = app_state.clone();
+        let playback_shutdown_token = shutdown_token.clone();
+        supervisor::spawn_supervised(
+            &tracker,
+            "playback_leds",
+            RestartPolicy::default(),
+            shutdown_token.clone(),
+            move || {
+                follow_player(
+                    app_state.clone(),
+                    playback_leds.clone(),
+                    playback_shutdown_token.clone(),
+                )
+            },
+        );
+    }
+
+    // The strip is opened inside the task, which waits for a late spidev node
+    // and otherwise keeps the server running without the strip
+    let led_strip_shutdown_token = shutdown_token.clone();
+    supervisor::spawn_supervised(
+        &tracker,
+        "led_strip",
+        RestartPolicy::default(),
+        shutdown_token,
+        move || {
+            let app_state = app_state.clone();
+            let shutdown_token = led_strip_shutdown_token.clone();
+            async move {
+                match Ws2812StripTask::open(app_state).await {
+                    Ok(led_strip_task) => led_strip_task.run(shutdown_token).await,
+                    Err(e) => tracing::error!("LED strip unavailable, running without it: {}", e),
+                }
+            }
+        },
+    );
 }
 
 async fn graceful_shutdown(tracker: TaskTracker, shutdown_token: CancellationToken) {
@@ -121,3 +231,49 @@ async fn graceful_shutdown(tracker: TaskTracker, shutdown_token: CancellationTok
 
     tracing::info!("Shutdown complete");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Headers of the response to a GET from `origin`, lowercased
+    async fn response_headers(addr: std::net::SocketAddr, origin: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nOrigin: {}\r\nConnection: close\r\n\r\n",
+            origin
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        response.split("\r\n\r\n").next().unwrap().to_lowercase()
+    }
+
+    #[tokio::test]
+    async fn test_cors_layer_checks_origin() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new()
+            .route("/", axum::routing::get(|| async { "ok" }))
+            .layer(cors_layer(&CorsConfig::default()));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let allowed = response_headers(addr, "http://localhost:5173").await;
+        assert!(
+            allowed.contains("access-control-allow-origin: http://localhost:5173"),
+            "{}",
+            allowed
+        );
+        let denied = response_headers(addr, "http://evil.example").await;
+        assert!(
+            !denied.contains("access-control-allow-origin"),
+            "{}",
+            denied
+        );
+
+        server.abort();
+    }
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counters for the lifetime of the process, bumped by the player and the LED strip task
+pub struct Metrics {
+    started_at: DateTime<Utc>,
+    started: Instant,
+    tracks_played: AtomicU64,
+    led_events: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            started: Instant::now(),
+            tracks_played: AtomicU64::new(0),
+            led_events: AtomicU64::new(0),
+        }
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn record_track_played(&self) {
+        self.tracks_played.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn tracks_played(&self) -> u64 {
+        self.tracks_played.load(Ordering::Relaxed)
+    }
+
+    pub fn record_led_event(&self) {
+        self.led_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn led_events(&self) -> u64 {
+        self.led_events.load(Ordering::Relaxed)
+    }
+}
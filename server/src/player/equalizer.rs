@@ -0,0 +1,347 @@
+use rodio::{Sample, Source, source::SeekError};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const LOW_SHELF_HZ: f32 = 200.0;
+const MID_PEAK_HZ: f32 = 1000.0;
+const HIGH_SHELF_HZ: f32 = 4000.0;
+// Butterworth Q for the shelves, a broad bell for the mid band
+const SHELF_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const MID_Q: f32 = 0.7;
+// Largest boost or cut of a band, in dB
+const MAX_GAIN_DB: f32 = 12.0;
+// How many samples pass between checks for new settings
+const SETTINGS_CHECK_INTERVAL: usize = 1024;
+
+/// Gains of the three EQ bands in dB, 0 leaves a band untouched
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub low: f32,
+    pub mid: f32,
+    pub high: f32,
+}
+
+impl EqSettings {
+    fn clamped(self) -> Self {
+        let clamp = |gain: f32| {
+            if gain.is_finite() {
+                gain.clamp(-MAX_GAIN_DB, MAX_GAIN_DB)
+            } else {
+                0.0
+            }
+        };
+        Self {
+            low: clamp(self.low),
+            mid: clamp(self.mid),
+            high: clamp(self.high),
+        }
+    }
+
+    fn is_flat(&self) -> bool {
+        self.low == 0.0 && self.mid == 0.0 && self.high == 0.0
+    }
+}
+
+/// EQ settings shared between the player and the sources it is playing, so a
+/// change applies to the current track without reloading it.
+#[derive(Clone, Default)]
+pub struct SharedEq {
+    settings: Arc<Mutex<EqSettings>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl SharedEq {
+    pub fn get(&self) -> EqSettings {
+        *self.settings.lock().unwrap()
+    }
+
+    /// Store `settings`, limiting each band to ±12 dB
+    pub fn set(&self, settings: EqSettings) -> EqSettings {
+        let settings = settings.clamped();
+        *self.settings.lock().unwrap() = settings;
+        self.generation.fetch_add(1, Ordering::Release);
+        settings
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    // Shelf and peaking filters from the RBJ audio EQ cookbook
+    fn low_shelf(sample_rate: f32, freq: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, alpha) = Self::angle(sample_rate, freq, SHELF_Q);
+        let beta = 2.0 * a.sqrt() * alpha;
+        Self::normalized(
+            a * ((a + 1.0) - (a - 1.0) * cos + beta),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - beta),
+            (a + 1.0) + (a - 1.0) * cos + beta,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - beta,
+        )
+    }
+
+    fn high_shelf(sample_rate: f32, freq: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, alpha) = Self::angle(sample_rate, freq, SHELF_Q);
+        let beta = 2.0 * a.sqrt() * alpha;
+        Self::normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos + beta),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - beta),
+            (a + 1.0) - (a - 1.0) * cos + beta,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - beta,
+        )
+    }
+
+    fn peaking(sample_rate: f32, freq: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, alpha) = Self::angle(sample_rate, freq, MID_Q);
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    fn angle(sample_rate: f32, freq: f32, q: f32) -> (f32, f32) {
+        // Keep the centre below Nyquist for low sample rates
+        let w0 = 2.0 * PI * freq.min(sample_rate * 0.45) / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+}
+
+/// One second-order section with its direct form I history
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    coefficients: Coefficients,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn new(coefficients: Coefficients) -> Self {
+        Self {
+            coefficients,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefficients;
+        let y = c.b0 * x + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Applies the low/mid/high EQ to every sample of `source`, each channel
+/// filtered separately. A flat EQ passes samples through untouched.
+pub struct EqSource<S> {
+    source: S,
+    eq: SharedEq,
+    generation: u64,
+    flat: bool,
+    // low, mid and high filters for each channel
+    filters: Vec<[Biquad; 3]>,
+    channel: usize,
+    until_check: usize,
+}
+
+impl<S: Source> EqSource<S> {
+    pub fn new(source: S, eq: SharedEq) -> Self {
+        let mut eq_source = Self {
+            source,
+            eq,
+            generation: 0,
+            flat: true,
+            filters: Vec::new(),
+            channel: 0,
+            until_check: 0,
+        };
+        eq_source.reload_settings();
+        eq_source
+    }
+
+    fn reload_settings(&mut self) {
+        self.generation = self.eq.generation.load(Ordering::Acquire);
+        let settings = self.eq.get();
+        self.flat = settings.is_flat();
+
+        let sample_rate = self.source.sample_rate() as f32;
+        let bands = [
+            Coefficients::low_shelf(sample_rate, LOW_SHELF_HZ, settings.low),
+            Coefficients::peaking(sample_rate, MID_PEAK_HZ, settings.mid),
+            Coefficients::high_shelf(sample_rate, HIGH_SHELF_HZ, settings.high),
+        ];
+        let channels = usize::from(self.source.channels().max(1));
+        if self.filters.len() != channels {
+            self.filters = vec![bands.map(Biquad::new); channels];
+        } else {
+            // Keep the filter history so a change doesn't click
+            for filters in &mut self.filters {
+                for (filter, coefficients) in filters.iter_mut().zip(bands) {
+                    filter.coefficients = coefficients;
+                }
+            }
+        }
+    }
+}
+
+impl<S: Source> Iterator for EqSource<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if self.until_check == 0 {
+            self.until_check = SETTINGS_CHECK_INTERVAL;
+            if self.eq.generation.load(Ordering::Acquire) != self.generation {
+                self.reload_settings();
+            }
+        }
+        self.until_check -= 1;
+
+        let sample = self.source.next()?;
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.filters.len();
+        if self.flat {
+            return Some(sample);
+        }
+
+        let filters = &mut self.filters[channel];
+        let output = filters
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.process(sample));
+        Some(output.clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for EqSource<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)?;
+        // The history belongs to the old position
+        self.filters.clear();
+        self.channel = 0;
+        self.reload_settings();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const RATE: u32 = 44100;
+
+    fn sine(freq: f32) -> SamplesBuffer {
+        let samples = (0..RATE)
+            .map(|i| 0.5 * (2.0 * PI * freq * i as f32 / RATE as f32).sin())
+            .collect::<Vec<_>>();
+        SamplesBuffer::new(1, RATE, samples)
+    }
+
+    /// RMS after skipping the filter's settling time
+    fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+        let samples: Vec<f32> = samples.skip(RATE as usize / 10).collect();
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn eq(low: f32, mid: f32, high: f32) -> SharedEq {
+        let eq = SharedEq::default();
+        eq.set(EqSettings { low, mid, high });
+        eq
+    }
+
+    #[test]
+    fn test_low_cut_attenuates_bass_tone() {
+        let dry = rms(sine(60.0));
+        let wet = rms(EqSource::new(sine(60.0), eq(-12.0, 0.0, 0.0)));
+        // -12 dB is a factor of about 0.25
+        assert!(wet < dry * 0.35, "dry {} wet {}", dry, wet);
+
+        // The treble is left alone
+        let dry = rms(sine(10000.0));
+        let wet = rms(EqSource::new(sine(10000.0), eq(-12.0, 0.0, 0.0)));
+        assert!((wet / dry - 1.0).abs() < 0.1, "dry {} wet {}", dry, wet);
+    }
+
+    #[test]
+    fn test_flat_eq_passes_samples_through() {
+        let dry: Vec<f32> = sine(440.0).collect();
+        let wet: Vec<f32> = EqSource::new(sine(440.0), SharedEq::default()).collect();
+        assert_eq!(dry, wet);
+    }
+
+    #[test]
+    fn test_settings_are_clamped() {
+        let eq = SharedEq::default();
+        let stored = eq.set(EqSettings {
+            low: 40.0,
+            mid: f32::NAN,
+            high: -3.0,
+        });
+        assert_eq!(
+            stored,
+            EqSettings {
+                low: 12.0,
+                mid: 0.0,
+                high: -3.0
+            }
+        );
+        assert_eq!(eq.get(), stored);
+    }
+}
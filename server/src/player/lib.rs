@@ -3,15 +3,48 @@ use cpal::traits::{DeviceTrait, HostTrait};
 use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source};
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
     fs::File,
     io::BufReader,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
+use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 
-const CHECK_SINK_EMPTY_INTERVAL: Duration = Duration::from_secs(1);
+use crate::metrics::Metrics;
+use crate::player::equalizer::{EqSettings, EqSource, SharedEq};
+use crate::player::meter::{AudioLevels, MeterSource, SharedLevels};
+
+// Find the output device with the name contains "es8388"
+const OUTPUT_DEVICE_NAME: &str = "es8388";
+// Default longest wait between checks for the end of the track
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// Shortest wait between checks once a track is about to end
+const TRACK_END_POLL_INTERVAL: Duration = Duration::from_millis(20);
+// Default length of the volume ramp on play, pause and stop
+const DEFAULT_FADE_DURATION: Duration = Duration::from_millis(50);
+const FADE_STEPS: u32 = 5;
+// How long before the sleep timer ends the volume starts fading out
+const SLEEP_FADE_DURATION: Duration = Duration::from_secs(30);
+
+/// Direction of a volume ramp and what to do once it is done
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Fade {
+    In,
+    OutAndPause,
+    OutAndStop,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SleepTimer {
+    deadline: Instant,
+    fade: bool,
+}
 
 struct Inner {
     sink: Option<Sink>,
@@ -19,9 +52,27 @@ struct Inner {
     current_track: Option<String>,
     current_index: Option<usize>,
     volume: f32,
+    /// Sink stays silent while `volume` keeps the level to restore
+    muted: bool,
     position: Duration,
     duration: Option<Duration>,
     playlist: Option<Playlist>,
+    sleep_timer: Option<SleepTimer>,
+    eq: SharedEq,
+    levels: SharedLevels,
+    fade_duration: Duration,
+    /// Longest wait between checks for the end of the track
+    check_interval: Duration,
+    /// Fading out towards a pause, toggling now resumes
+    pausing: bool,
+    /// Last state sent to the playback state subscribers
+    published_state: PlaybackState,
+    /// Load tracks into sinks without an output device
+    #[cfg(test)]
+    detached: bool,
+    /// Output of the last detached sink, tests pull samples from it to play
+    #[cfg(test)]
+    detached_output: Option<rodio::queue::SourcesQueueOutput>,
 }
 
 impl Inner {
@@ -32,78 +83,319 @@ impl Inner {
             current_track: None,
             current_index: None,
             volume: 1.0,
+            muted: false,
             position: Duration::from_secs(0),
             duration: None,
             playlist: None,
+            sleep_timer: None,
+            eq: SharedEq::default(),
+            levels: SharedLevels::default(),
+            fade_duration: DEFAULT_FADE_DURATION,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            pausing: false,
+            published_state: PlaybackState::Stopped,
+            #[cfg(test)]
+            detached: false,
+            #[cfg(test)]
+            detached_output: None,
+        }
+    }
+
+    /// The volume actually sent to the sink
+    fn output_volume(&self) -> f32 {
+        if self.muted { 0.0 } else { self.volume }
+    }
+
+    /// A new sink on the output device, opening the stream on first use
+    fn connect_sink(&mut self) -> Result<Sink> {
+        #[cfg(test)]
+        if self.detached {
+            let (sink, output) = Sink::new();
+            self.detached_output = Some(output);
+            return Ok(sink);
+        }
+
+        if self.stream.is_none() {
+            let device = find_output_device()
+                .ok_or_else(|| anyhow::anyhow!("Output device {} not found", OUTPUT_DEVICE_NAME))?;
+            let stream = OutputStreamBuilder::from_device(device)?.open_stream()?;
+            // let stream = OutputStreamBuilder::open_default_stream()?;
+            self.stream = Some(stream);
+        }
+        let stream_handle = self.stream.as_ref().unwrap();
+        Ok(Sink::connect_new(stream_handle.mixer()))
+    }
+
+    fn stop(&mut self) {
+        // Dropped rather than cleared, `Sink::clear` waits for the output to
+        // pull the queue empty and hangs if nothing is playing it
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+
+        self.current_track = None;
+        self.position = Duration::from_secs(0);
+        self.duration = None;
+        self.pausing = false;
+        self.levels.reset();
+    }
+
+    fn playback_state(&self) -> PlaybackState {
+        match self.sink {
+            Some(ref sink) if !sink.empty() => {
+                if sink.is_paused() || self.pausing {
+                    PlaybackState::Paused
+                } else {
+                    PlaybackState::Playing
+                }
+            }
+            _ => PlaybackState::Stopped,
+        }
+    }
+
+    /// Send the playback state to `state_changes` if it moved since the last send
+    fn publish_state(&mut self, state_changes: &broadcast::Sender<PlaybackState>) {
+        let state = self.playback_state();
+        if state != self.published_state {
+            self.published_state = state;
+            let _ = state_changes.send(state);
         }
     }
 }
 
 pub struct MusicPlayer {
     inner: Arc<Mutex<Inner>>,
+    /// Bumped by every fade and stop so an older fade gives up instead of stacking
+    fade_generation: Arc<AtomicU64>,
+    now_playing: broadcast::Sender<TrackStarted>,
+    state_changes: broadcast::Sender<PlaybackState>,
+    metrics: Arc<Metrics>,
 }
 
 impl MusicPlayer {
     pub fn new() -> Self {
+        Self::with_metrics(Arc::new(Metrics::new()))
+    }
+
+    /// A player counting the tracks it plays in `metrics`
+    pub fn with_metrics(metrics: Arc<Metrics>) -> Self {
+        let (now_playing, _) = broadcast::channel(16);
+        let (state_changes, _) = broadcast::channel(16);
         Self {
             inner: Arc::new(Mutex::new(Inner::new())),
+            fade_generation: Arc::new(AtomicU64::new(0)),
+            now_playing,
+            state_changes,
+            metrics,
         }
     }
 
-    pub async fn run(&self, shutdown_token: CancellationToken) {
-        let mut check_sink_interval = tokio::time::interval(CHECK_SINK_EMPTY_INTERVAL);
-        check_sink_interval.tick().await;
+    /// Receive a [`TrackStarted`] every time a track is loaded
+    pub fn subscribe_now_playing(&self) -> broadcast::Receiver<TrackStarted> {
+        self.now_playing.subscribe()
+    }
+
+    /// Receive the [`PlaybackState`] every time playback starts, pauses or stops
+    pub fn subscribe_playback_state(&self) -> broadcast::Receiver<PlaybackState> {
+        self.state_changes.subscribe()
+    }
+
+    fn publish_state(&self) {
+        self.inner
+            .lock()
+            .unwrap()
+            .publish_state(&self.state_changes);
+    }
+
+    /// Length of the volume ramp on play, pause and stop, zero switches instantly
+    pub fn set_fade_duration(&self, duration: Duration) {
+        self.inner.lock().unwrap().fade_duration = duration;
+    }
+
+    /// Longest wait before noticing a track ended when its end can't be
+    /// predicted, shorter advances sooner at the cost of more wake-ups
+    pub fn set_check_interval(&self, interval: Duration) {
+        self.inner.lock().unwrap().check_interval = interval.max(TRACK_END_POLL_INTERVAL);
+    }
 
+    /// Ramp the sink volume on a short-lived thread, replacing any fade in progress
+    fn start_fade(&self, fade: Fade) {
+        let generation = self.fade_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let step_delay = self.inner.lock().unwrap().fade_duration / FADE_STEPS;
+        let inner = self.inner.clone();
+        let fade_generation = self.fade_generation.clone();
+        let state_changes = self.state_changes.clone();
+
+        let run = move || {
+            for factor in fade_factors(FADE_STEPS, fade == Fade::In) {
+                std::thread::sleep(step_delay);
+                let mut inner = inner.lock().unwrap();
+                if fade_generation.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                if let Some(ref sink) = inner.sink {
+                    sink.set_volume(inner.output_volume() * factor);
+                }
+                if factor == 0.0 {
+                    match fade {
+                        Fade::OutAndPause => {
+                            inner.pausing = false;
+                            if let Some(ref sink) = inner.sink {
+                                sink.pause();
+                            }
+                        }
+                        Fade::OutAndStop => inner.stop(),
+                        Fade::In => {}
+                    }
+                    // Paused or stopped, so the next start ramps up from the real level
+                    if let Some(ref sink) = inner.sink {
+                        sink.set_volume(inner.output_volume());
+                    }
+                    inner.publish_state(&state_changes);
+                }
+            }
+        };
+
+        if step_delay.is_zero() {
+            run();
+        } else {
+            std::thread::spawn(run);
+        }
+    }
+
+    pub async fn run(&self, shutdown_token: CancellationToken) {
         while !shutdown_token.is_cancelled() {
+            let delay = self.next_check_delay();
             tokio::select! {
                 () = shutdown_token.cancelled() => {
                     tracing::info!("Shutting down music player");
                 },
-                _ = check_sink_interval.tick() => {
+                () = tokio::time::sleep(delay) => {
+                    self.check_sleep_timer(Instant::now());
                     self.play_next();
+                    // Catches a playlist running out, which no call announces
+                    self.publish_state();
                 },
             }
         }
+
+        if let Err(e) = self.stop() {
+            tracing::error!("Failed to stop playback on shutdown: {}", e);
+        }
     }
 
-    fn play_next(&self) {
-        let mut should_play_next = false;
+    /// Wake up right when the current track is expected to end rather than on a fixed tick.
+    fn next_check_delay(&self) -> Duration {
+        let inner = self.inner.lock().unwrap();
+        let remaining = match (inner.sink.as_ref(), inner.duration) {
+            (Some(sink), Some(duration)) if !sink.is_paused() => {
+                Some(duration.saturating_sub(sink.get_pos()))
+            }
+            _ => None,
+        };
+        let delay = track_end_delay(remaining, inner.check_interval);
+        match inner.sleep_timer {
+            Some(timer) => delay.min(timer.deadline.saturating_duration_since(Instant::now())),
+            None => delay,
+        }
+    }
+
+    /// Stop playback once `after` has passed, fading out over the last 30 seconds
+    /// if `fade` is set. `None` cancels the timer.
+    pub fn set_sleep_timer(&self, after: Option<Duration>, fade: bool) -> Result<()> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+        inner.sleep_timer = after.map(|after| SleepTimer {
+            deadline: Instant::now() + after,
+            fade,
+        });
+        // Undo any fade already applied
+        if let Some(ref sink) = inner.sink {
+            sink.set_volume(inner.output_volume());
+        }
+        Ok(())
+    }
+
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .sleep_timer
+            .map(|timer| timer.deadline.saturating_duration_since(Instant::now()))
+    }
+
+    fn check_sleep_timer(&self, now: Instant) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let timer = match inner.sleep_timer {
+                Some(timer) => timer,
+                None => return,
+            };
+
+            let remaining = timer.deadline.saturating_duration_since(now);
+            if let Some(ref sink) = inner.sink {
+                let volume = if timer.fade && !remaining.is_zero() {
+                    inner.output_volume() * sleep_fade_factor(remaining)
+                } else {
+                    inner.output_volume()
+                };
+                sink.set_volume(volume);
+            }
+            if !remaining.is_zero() {
+                return;
+            }
+            inner.sleep_timer = None;
+        }
 
+        tracing::info!("Sleep timer elapsed, stopping playback");
+        if let Err(e) = self.stop() {
+            tracing::error!("Failed to stop playback for the sleep timer: {}", e);
+        }
+    }
+
+    fn play_next(&self) {
         {
             let inner = self.inner.lock().unwrap();
             let playlist = match inner.playlist {
                 Some(ref playlist) => playlist,
                 None => return,
             };
-            if let Some(ref sink) = inner.sink {
-                if sink.empty() && !sink.is_paused() && playlist.tracks.len() > 0 {
-                    should_play_next = true;
-                }
+            if inner.sink.is_none() || playlist.tracks.is_empty() {
+                return;
+            }
+            // An empty sink without a current track was stopped on purpose
+            if inner.current_track.is_none() {
+                return;
             }
         }
 
-        if should_play_next {
-            let _ = self.next();
+        if !self.is_playing() && !self.is_paused() {
+            self.advance_skipping_unplayable();
         }
     }
 
-    fn load_track(&self, track_name: &str, path: &Path) -> Result<()> {
-        self.stop()?;
+    /// Play the next track, skipping any that fail to open or decode. Gives up
+    /// after one pass over the playlist so a playlist of bad files can't spin.
+    fn advance_skipping_unplayable(&self) {
+        let attempts = {
+            let inner = self.inner.lock().unwrap();
+            inner.playlist.as_ref().map_or(0, |p| p.tracks.len())
+        };
+        for _ in 0..attempts {
+            match self.next() {
+                Ok(()) => return,
+                Err(e) => tracing::warn!("Skipping unplayable track: {:#}", e),
+            }
+        }
+        tracing::error!("No playable track left in the playlist");
+    }
 
+    fn load_track(&self, track_name: &str, path: &Path) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        if inner.stream.is_none() {
-            let host = cpal::default_host();
-            let mut devices = host.output_devices().expect("No output devices found");
-            // Find the output device with the name contains "es3288"
-            let device = devices
-                .find(|d| d.name().unwrap().contains("es8388"))
-                .unwrap();
-            let stream = OutputStreamBuilder::from_device(device)
-                .unwrap()
-                .open_stream()?;
-            // let stream = OutputStreamBuilder::open_default_stream()?;
-            inner.stream = Some(stream);
-        }
+        // Not `stop`, which would announce a stop between the two tracks
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+        inner.stop();
 
         // Load and decode the audio file
         let file = File::open(&path)
@@ -117,17 +409,21 @@ impl MusicPlayer {
         // Get duration if available
         inner.duration = source.total_duration();
 
-        if let Some(ref stream_handle) = inner.stream {
-            let sink = Sink::connect_new(stream_handle.mixer());
+        let sink = inner.connect_sink()?;
+        sink.set_volume(inner.output_volume());
+        let source = EqSource::new(source, inner.eq.clone());
+        sink.append(MeterSource::new(source, inner.levels.clone()));
+        sink.pause(); // Start paused
 
-            sink.set_volume(inner.volume);
-            sink.append(source);
-            sink.pause(); // Start paused
-
-            inner.sink = Some(sink);
-            inner.current_track = Some(track_name.to_string());
-            inner.position = Duration::from_secs(0);
-        }
+        inner.sink = Some(sink);
+        inner.current_track = Some(track_name.to_string());
+        inner.position = Duration::from_secs(0);
+        self.metrics.record_track_played();
+        let _ = self.now_playing.send(TrackStarted {
+            name: track_name.to_string(),
+            path: path.to_path_buf(),
+            duration: inner.duration,
+        });
         Ok(())
     }
 
@@ -138,12 +434,147 @@ impl MusicPlayer {
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
         inner.volume = volume.clamp(0.0, 1.0);
         if let Some(ref sink) = inner.sink {
-            sink.set_volume(inner.volume);
+            sink.set_volume(inner.output_volume());
         }
         Ok(())
     }
 
+    /// Silence the output without forgetting the volume, which comes back on
+    /// unmute. Stays in effect across track changes.
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+        inner.muted = muted;
+        if let Some(ref sink) = inner.sink {
+            sink.set_volume(inner.output_volume());
+        }
+        Ok(())
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.inner.lock().unwrap().muted
+    }
+
+    /// RMS and peak of the audio playing right now, silence while paused or stopped
+    pub fn levels(&self) -> AudioLevels {
+        if !self.is_playing() {
+            return AudioLevels::default();
+        }
+        self.inner.lock().unwrap().levels.get()
+    }
+
+    /// Change the EQ, applied to the current track right away. Returns the
+    /// settings stored after limiting each band.
+    pub fn set_eq(&self, settings: EqSettings) -> Result<EqSettings> {
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+        Ok(inner.eq.set(settings))
+    }
+
+    pub fn eq(&self) -> EqSettings {
+        self.inner.lock().unwrap().eq.get()
+    }
+
+    pub fn is_output_available(&self) -> bool {
+        let has_stream = match self.inner.lock() {
+            Ok(inner) => inner.stream.is_some(),
+            Err(_) => false,
+        };
+        has_stream || find_output_device().is_some()
+    }
+
+    /// The volume as last set, unaffected by mute and by fades in progress
+    pub fn volume(&self) -> f32 {
+        self.inner.lock().unwrap().volume
+    }
+
+    /// What the sink currently plays at, following mute and fades
+    #[cfg(test)]
+    fn sink_volume(&self) -> f32 {
+        let inner = self.inner.lock().unwrap();
+        match inner.sink {
+            Some(ref sink) => sink.volume(),
+            None => inner.volume,
+        }
+    }
+
     pub fn play(&self, playlist: &Vec<Track>, selected_index: usize) -> Result<()> {
+        // Asking for the track that's already on only resumes it, reloading
+        // would restart it from the top
+        if self.is_current(playlist, selected_index) {
+            if self.playback_state() == PlaybackState::Paused {
+                self.toggle()?;
+            }
+            return Ok(());
+        }
+
+        self.load(playlist, selected_index)?;
+        self.start_loaded()
+    }
+
+    /// Whether `selected_index` of `playlist` is the track loaded right now
+    fn is_current(&self, playlist: &[Track], selected_index: usize) -> bool {
+        let inner = self.inner.lock().unwrap();
+        let loaded =
+            matches!(inner.sink, Some(ref sink) if !sink.empty()) && inner.current_track.is_some();
+        loaded
+            && inner.current_index == Some(selected_index)
+            && inner
+                .playlist
+                .as_ref()
+                .is_some_and(|current| current.tracks == playlist)
+    }
+
+    /// Jump to `index` of the current playlist and play it. Returns `false`
+    /// when the index is outside the playlist.
+    pub fn play_index(&self, index: usize) -> Result<bool> {
+        let len = {
+            let inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+            inner.playlist.as_ref().map_or(0, |p| p.tracks.len())
+        };
+        if index >= len {
+            return Ok(false);
+        }
+
+        self.load_index(index)?;
+        self.start_loaded()?;
+        Ok(true)
+    }
+
+    /// Start the freshly loaded track, fading in from silence
+    fn start_loaded(&self) -> Result<()> {
+        {
+            let inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+            if let Some(ref sink) = inner.sink {
+                sink.set_volume(0.0);
+                sink.play();
+            }
+        }
+        self.start_fade(Fade::In);
+        self.publish_state();
+        Ok(())
+    }
+
+    /// Replace the playlist and load `selected_index` paused, ready for `toggle`
+    pub fn load(&self, playlist: &Vec<Track>, selected_index: usize) -> Result<()> {
+        if selected_index >= playlist.len() {
+            return Err(anyhow::anyhow!(
+                "Track index {} out of range (playlist has {} tracks)",
+                selected_index,
+                playlist.len()
+            ));
+        }
+
         {
             let mut inner = self.inner.lock().unwrap();
             inner.playlist = Some(Playlist {
@@ -154,47 +585,148 @@ impl MusicPlayer {
 
         let track_name = playlist[selected_index].name.clone();
         let path = PathBuf::from(playlist[selected_index].path.clone());
-        self.load_track(&track_name, &path)?;
+        self.load_track(&track_name, &path)
+    }
 
-        let inner = self
+    pub fn enqueue(&self, track: Track) -> Result<()> {
+        let start_index = {
+            let mut inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+            let idle = match inner.sink {
+                Some(ref sink) => sink.empty(),
+                None => true,
+            };
+            let playlist = inner
+                .playlist
+                .get_or_insert_with(|| Playlist { tracks: Vec::new() });
+            playlist.tracks.push(track);
+            let index = playlist.tracks.len() - 1;
+            if idle { Some(index) } else { None }
+        };
+
+        // Nothing is playing, so start with the track we just appended
+        if let Some(index) = start_index {
+            self.load_index(index)?;
+            self.start_loaded()?;
+        }
+        Ok(())
+    }
+
+    /// Move the track at `from` to `to`, keeping `current_index` on the track
+    /// that is loaded so playback carries on. Returns `false` when either index
+    /// is outside the playlist.
+    pub fn move_track(&self, from: usize, to: usize) -> Result<bool> {
+        let mut inner = self
             .inner
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
-        if let Some(ref sink) = inner.sink {
-            sink.play();
+        let tracks = match inner.playlist {
+            Some(ref mut playlist) => &mut playlist.tracks,
+            None => return Ok(false),
+        };
+        if from >= tracks.len() || to >= tracks.len() {
+            return Ok(false);
         }
-        Ok(())
+
+        let track = tracks.remove(from);
+        tracks.insert(to, track);
+        inner.current_index = inner
+            .current_index
+            .map(|current| moved_index(current, from, to));
+        Ok(true)
     }
 
-    pub fn stop(&self) -> Result<()> {
+    /// Drop every queued track except the one currently loaded.
+    pub fn clear_queue(&self) -> Result<()> {
         let mut inner = self
             .inner
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
-        if let Some(ref sink) = inner.sink {
-            sink.stop();
-            sink.clear();
+        let current = match (inner.current_index, inner.playlist.take()) {
+            (Some(index), Some(playlist)) => playlist.tracks.into_iter().nth(index),
+            _ => None,
+        };
+        match current {
+            Some(track) => {
+                inner.playlist = Some(Playlist {
+                    tracks: vec![track],
+                });
+                inner.current_index = Some(0);
+            }
+            None => {
+                inner.current_index = None;
+            }
         }
-
-        inner.current_track = None;
-        inner.position = Duration::from_secs(0);
-        inner.duration = None;
         Ok(())
     }
 
-    pub fn toggle(&self) -> Result<()> {
-        let inner = self
+    fn load_index(&self, index: usize) -> Result<()> {
+        let (track_name, path) = {
+            let mut inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+            let track = inner
+                .playlist
+                .as_ref()
+                .and_then(|playlist| playlist.tracks.get(index))
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Track index {} out of range", index))?;
+            inner.current_index = Some(index);
+            (track.name, PathBuf::from(track.path))
+        };
+
+        self.load_track(&track_name, &path)
+    }
+
+    /// Stop right away, cancelling any fade in progress
+    pub fn stop(&self) -> Result<()> {
+        let mut inner = self
             .inner
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
-        if let Some(ref sink) = inner.sink {
-            if sink.is_paused() {
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+        inner.stop();
+        inner.publish_state(&self.state_changes);
+        Ok(())
+    }
+
+    /// Fade out, then stop
+    pub fn fade_out_and_stop(&self) -> Result<()> {
+        self.start_fade(Fade::OutAndStop);
+        Ok(())
+    }
+
+    /// Resume with a fade-in or fade out and pause. Returns whether playback
+    /// ends up paused, which is already decided while the fade-out runs.
+    pub fn toggle(&self) -> Result<bool> {
+        let fade = {
+            let mut inner = self
+                .inner
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
+            let sink = match inner.sink {
+                Some(ref sink) => sink,
+                None => return Ok(true),
+            };
+            let fade = if sink.is_paused() {
+                sink.set_volume(0.0);
                 sink.play();
+                Fade::In
+            } else if inner.pausing {
+                // Still audible, ramp back up from where the fade-out got to
+                Fade::In
             } else {
-                sink.pause();
-            }
-        }
-        Ok(())
+                Fade::OutAndPause
+            };
+            inner.pausing = fade == Fade::OutAndPause;
+            fade
+        };
+        self.start_fade(fade);
+        self.publish_state();
+        Ok(fade == Fade::OutAndPause)
     }
 
     pub fn is_paused(&self) -> bool {
@@ -254,171 +786,98 @@ impl MusicPlayer {
     pub fn next(&self) -> Result<()> {
         self.load_next_track()?;
 
-        let inner = self
+        let mut inner = self
             .inner
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
         if let Some(ref sink) = inner.sink {
             sink.play();
         }
+        inner.publish_state(&self.state_changes);
         Ok(())
     }
 
     pub fn prev(&self) -> Result<()> {
         self.load_prev_track()?;
 
-        let inner = self
+        let mut inner = self
             .inner
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to lock inner"))?;
         if let Some(ref sink) = inner.sink {
             sink.play();
         }
+        inner.publish_state(&self.state_changes);
         Ok(())
     }
 
     fn load_next_track(&self) -> Result<()> {
-        self.stop()?;
-
-        let mut inner = self.inner.lock().unwrap();
-
-        if inner.current_index.is_none() {
-            return Ok(());
-        }
-
-        let current_index = inner.current_index.unwrap();
-        if current_index + 1 < inner.playlist.as_ref().unwrap().tracks.len() {
-            inner.current_index = Some(current_index + 1);
-        } else {
-            inner.current_index = Some(0);
-        }
-        let current_index = inner.current_index.unwrap();
-
-        let track_name = inner.playlist.as_ref().unwrap().tracks[current_index]
-            .name
-            .clone();
-        let path = PathBuf::from(
-            inner.playlist.as_ref().unwrap().tracks[current_index]
-                .path
-                .clone(),
-        );
-
-        if inner.stream.is_none() {
-            let stream = OutputStreamBuilder::open_default_stream()?;
-            inner.stream = Some(stream);
-        }
-
-        // Load and decode the audio file
-        let file = File::open(&path)
-            .with_context(|| format!("Failed to open file: {}", path.display()))?;
-        let reader = BufReader::new(file);
-
-        // Try to decode with rodio (which uses symphonia internally for many formats)
-        let source = Decoder::new(reader)
-            .with_context(|| format!("Failed to decode audio file: {}", path.display()))?;
-
-        // Get duration if available
-        inner.duration = source.total_duration();
-
-        if let Some(ref stream_handle) = inner.stream {
-            let sink = Sink::connect_new(stream_handle.mixer());
-
-            sink.set_volume(inner.volume);
-            sink.append(source);
-            sink.pause(); // Start paused
-
-            inner.sink = Some(sink);
-            inner.current_track = Some(track_name.to_string());
-            inner.position = Duration::from_secs(0);
-        }
+        let index = {
+            let inner = self.inner.lock().unwrap();
+            let current_index = match inner.current_index {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+            if current_index + 1 < inner.playlist.as_ref().unwrap().tracks.len() {
+                current_index + 1
+            } else {
+                0
+            }
+        };
 
-        Ok(())
+        self.load_index(index)
     }
 
     fn load_prev_track(&self) -> Result<()> {
-        self.stop()?;
-
-        let mut inner = self.inner.lock().unwrap();
-        if inner.current_index.is_none() {
-            return Ok(());
-        }
-
-        let current_index = inner.current_index.unwrap();
-        if current_index > 0 {
-            inner.current_index = Some(current_index - 1);
-        } else {
-            inner.current_index = Some(inner.playlist.as_ref().unwrap().tracks.len() - 1);
-        }
-
-        let current_index = inner.current_index.unwrap();
-        let track_name = inner.playlist.as_ref().unwrap().tracks[current_index]
-            .name
-            .clone();
-        let path = PathBuf::from(
-            inner.playlist.as_ref().unwrap().tracks[current_index]
-                .path
-                .clone(),
-        );
-
-        if inner.stream.is_none() {
-            let stream = OutputStreamBuilder::open_default_stream()?;
-            inner.stream = Some(stream);
-        }
-
-        // Load and decode the audio file
-        let file = File::open(&path)
-            .with_context(|| format!("Failed to open file: {}", path.display()))?;
-        let reader = BufReader::new(file);
-
-        // Try to decode with rodio (which uses symphonia internally for many formats)
-        let source = Decoder::new(reader)
-            .with_context(|| format!("Failed to decode audio file: {}", path.display()))?;
-
-        // Get duration if available
-        inner.duration = source.total_duration();
+        let index = {
+            let inner = self.inner.lock().unwrap();
+            let current_index = match inner.current_index {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+            if current_index > 0 {
+                current_index - 1
+            } else {
+                inner.playlist.as_ref().unwrap().tracks.len() - 1
+            }
+        };
 
-        if let Some(ref stream_handle) = inner.stream {
-            let sink = Sink::connect_new(stream_handle.mixer());
+        self.load_index(index)
+    }
 
-            sink.set_volume(inner.volume);
-            sink.append(source);
-            sink.pause(); // Start paused
+    /// Whether a track is playing, paused (or pausing) or there is none
+    pub fn playback_state(&self) -> PlaybackState {
+        self.inner.lock().unwrap().playback_state()
+    }
 
-            inner.sink = Some(sink);
-            inner.current_track = Some(track_name.to_string());
-            inner.position = Duration::from_secs(0);
+    pub fn is_playing(&self) -> bool {
+        let inner = self.inner.lock();
+        match inner {
+            Ok(inner) => {
+                if let Some(ref sink) = inner.sink {
+                    !sink.is_paused() && !sink.empty()
+                } else {
+                    false
+                }
+            }
+            Err(_) => false,
         }
-
-        Ok(())
     }
 
-    // pub fn is_playing(&self) -> bool {
-    //     let inner = self.inner.lock().unwrap();
-    //     if let Some(ref sink) = inner.sink {
-    //         !sink.is_paused() && !sink.empty()
-    //     } else {
-    //         false
-    //     }
-    // }
-
     pub fn status(&self) -> Result<PlayerStatus> {
         let inner = self.inner.lock().unwrap();
         if inner.sink.is_none() {
             return Ok(PlayerStatus {
-                paused: true,
-                position: None,
-                position_sec: None,
-                duration: None,
-                duration_sec: None,
-                volume: 0.0,
-                current_track: None,
-                track: None,
+                volume: inner.volume,
+                muted: inner.muted,
+                ..PlayerStatus::default()
             });
         }
 
         let sink = inner.sink.as_ref().unwrap();
 
-        let is_playing = !sink.is_paused() && !sink.empty();
+        // Fading out towards a pause already counts as paused, like `playback_state`
+        let is_playing = !sink.is_paused() && !sink.empty() && !inner.pausing;
         let pos = sink.get_pos().as_secs();
         let position = format!("{:02}:{:02}", pos / 60, pos % 60);
         let duration = inner
@@ -429,36 +888,807 @@ impl MusicPlayer {
 
         Ok(PlayerStatus {
             paused: !is_playing,
+            playing: is_playing,
             position: Some(position),
             position_sec: Some(pos),
             duration: duration,
             duration_sec: Some(inner.duration.map(|d| d.as_secs()).unwrap_or(0)),
             volume: volume,
+            muted: inner.muted,
+            led_scale: None,
             current_track: current_track,
             track: Some(0),
         })
     }
+
+    /// Decode `path` without playing it, stopping after `limit` if given, to
+    /// measure a track ahead of time
+    pub fn analyze(path: &Path, limit: Option<Duration>) -> Result<AnalysisResult> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let source = Decoder::new(BufReader::new(file))
+            .with_context(|| format!("Failed to decode audio file: {}", path.display()))?;
+
+        let sample_rate = source.sample_rate();
+        let channels = source.channels();
+        let samples_per_second = sample_rate as f64 * channels.max(1) as f64;
+        let max_samples = limit.map(|limit| (limit.as_secs_f64() * samples_per_second) as usize);
+
+        let mut count = 0usize;
+        let mut sum_squares = 0.0f64;
+        let mut peak = 0.0f32;
+        for sample in source.take(max_samples.unwrap_or(usize::MAX)) {
+            sum_squares += sample as f64 * sample as f64;
+            peak = peak.max(sample.abs());
+            count += 1;
+        }
+
+        let rms = if count == 0 {
+            0.0
+        } else {
+            (sum_squares / count as f64).sqrt() as f32
+        };
+        Ok(AnalysisResult {
+            duration: Duration::from_secs_f64(count as f64 / samples_per_second),
+            peak,
+            rms,
+            sample_rate,
+            channels,
+        })
+    }
+}
+
+/// Whether the es8388 output the player opens is connected
+pub fn is_output_device_present() -> bool {
+    find_output_device().is_some()
+}
+
+fn find_output_device() -> Option<cpal::Device> {
+    let host = cpal::default_host();
+    let mut devices = host.output_devices().ok()?;
+    devices.find(|d| {
+        d.name()
+            .map(|name| name.contains(OUTPUT_DEVICE_NAME))
+            .unwrap_or(false)
+    })
+}
+
+fn track_end_delay(remaining: Option<Duration>, check_interval: Duration) -> Duration {
+    match remaining {
+        Some(remaining) => remaining.clamp(TRACK_END_POLL_INTERVAL, check_interval),
+        None => check_interval,
+    }
+}
+
+/// Where the track at `index` ends up after the track at `from` moves to `to`
+fn moved_index(index: usize, from: usize, to: usize) -> usize {
+    if index == from {
+        to
+    } else if from < index && index <= to {
+        index - 1
+    } else if to <= index && index < from {
+        index + 1
+    } else {
+        index
+    }
+}
+
+/// Volume factors for each step of a fade, ending at 1.0 for a fade-in and 0.0 for a fade-out
+fn fade_factors(steps: u32, fade_in: bool) -> impl Iterator<Item = f32> {
+    (1..=steps).map(move |step| {
+        let progress = step as f32 / steps as f32;
+        if fade_in { progress } else { 1.0 - progress }
+    })
+}
+
+/// Volume factor while fading out, from 1.0 down to 0.0 at the sleep timer's deadline
+fn sleep_fade_factor(remaining: Duration) -> f32 {
+    (remaining.as_secs_f32() / SLEEP_FADE_DURATION.as_secs_f32()).min(1.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Stopped,
 }
 
 #[derive(Debug, Serialize)]
 pub struct PlayerStatus {
     pub paused: bool,
+    pub playing: bool,
     pub position: Option<String>,
     pub position_sec: Option<u64>,
     pub duration: Option<String>,
     pub duration_sec: Option<u64>,
     pub volume: f32,
+    pub muted: bool,
+    pub led_scale: Option<f32>,
     pub current_track: Option<String>,
     pub track: Option<u64>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl Default for PlayerStatus {
+    /// Nothing loaded: paused, silent and without a track
+    fn default() -> Self {
+        PlayerStatus {
+            paused: true,
+            playing: false,
+            position: None,
+            position_sec: None,
+            duration: None,
+            duration_sec: None,
+            volume: 0.0,
+            muted: false,
+            led_scale: None,
+            current_track: None,
+            track: None,
+        }
+    }
+}
+
+impl fmt::Display for PlayerStatus {
+    /// `track – 01:23/04:56 (vol 50%)`, with placeholders for what is unknown
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} – {}/{} (vol {:.0}%)",
+            self.current_track.as_deref().unwrap_or("no track"),
+            self.position.as_deref().unwrap_or("--:--"),
+            self.duration.as_deref().unwrap_or("--:--"),
+            self.volume * 100.0
+        )
+    }
+}
+
+/// Measurements of a decoded file, see [`MusicPlayer::analyze`]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct AnalysisResult {
+    /// Length of the audio that was decoded
+    pub duration: Duration,
+    pub peak: f32,
+    pub rms: f32,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// A track that was just loaded into the sink
+#[derive(Clone, Debug)]
+pub struct TrackStarted {
+    pub name: String,
+    pub path: PathBuf,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Track {
     name: String,
     path: String,
 }
 
+impl Track {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(test)]
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Playlist {
     tracks: Vec<Track>,
 }
+
+#[cfg(test)]
+impl MusicPlayer {
+    /// Decode into sinks without an output device from now on
+    pub(crate) fn detach(&self) {
+        self.inner.lock().unwrap().detached = true;
+    }
+}
+
+/// A silent 16-bit mono WAV of `seconds` at 8kHz, as a track
+#[cfg(test)]
+pub(crate) fn wav_track(name: &str, seconds: u32) -> Track {
+    let rate = 8000u32;
+    wav_file(name, rate, &vec![0; (rate * seconds) as usize])
+}
+
+/// A 16-bit mono WAV of `samples` at `rate`, as a track
+#[cfg(test)]
+fn wav_file(name: &str, rate: u32, samples: &[i16]) -> Track {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::new();
+    wav.extend(b"RIFF");
+    wav.extend((36 + data_len).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes()); // PCM
+    wav.extend(1u16.to_le_bytes()); // mono
+    wav.extend(rate.to_le_bytes());
+    wav.extend((rate * 2).to_le_bytes());
+    wav.extend(2u16.to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend(data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend(sample.to_le_bytes());
+    }
+
+    let path = std::env::temp_dir().join(format!("emes-{}-{}.wav", name, std::process::id()));
+    std::fs::write(&path, wav).unwrap();
+    Track {
+        name: name.to_string(),
+        path: path.to_string_lossy().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn player_with_sink(sink: Sink) -> MusicPlayer {
+        let player = MusicPlayer::new();
+        player.inner.lock().unwrap().sink = Some(sink);
+        player
+    }
+
+    #[test]
+    fn test_status_display() {
+        let status = PlayerStatus {
+            paused: false,
+            playing: true,
+            position: Some("01:23".to_string()),
+            position_sec: Some(83),
+            duration: Some("04:56".to_string()),
+            duration_sec: Some(296),
+            volume: 0.5,
+            current_track: Some("song.mp3".to_string()),
+            track: Some(0),
+            ..PlayerStatus::default()
+        };
+        assert_eq!(status.to_string(), "song.mp3 – 01:23/04:56 (vol 50%)");
+        assert_eq!(
+            PlayerStatus::default().to_string(),
+            "no track – --:--/--:-- (vol 0%)"
+        );
+    }
+
+    #[test]
+    fn test_is_playing_without_sink() {
+        let player = MusicPlayer::new();
+        assert!(!player.is_playing());
+    }
+
+    #[test]
+    fn test_is_playing_states() {
+        let (sink, mut output) = Sink::new();
+        sink.append(SamplesBuffer::new(1, 44100, vec![0.0f32; 16]));
+        sink.pause();
+        let player = player_with_sink(sink);
+
+        // Loaded but paused
+        assert!(!player.is_playing());
+
+        // Playing
+        player.toggle().unwrap();
+        assert!(player.is_playing());
+
+        // Finished: drain the queued source so the sink becomes empty
+        for _ in output.by_ref().take(1024) {}
+        assert!(!player.is_playing());
+        assert!(!player.is_paused());
+    }
+
+    #[test]
+    fn test_track_end_delay() {
+        // The wake-up follows the end of the track, so the gap stays well under a second
+        assert_eq!(
+            track_end_delay(Some(Duration::from_millis(100)), DEFAULT_CHECK_INTERVAL),
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            track_end_delay(Some(Duration::ZERO), DEFAULT_CHECK_INTERVAL),
+            TRACK_END_POLL_INTERVAL
+        );
+        assert_eq!(
+            track_end_delay(Some(Duration::from_secs(600)), DEFAULT_CHECK_INTERVAL),
+            DEFAULT_CHECK_INTERVAL
+        );
+        assert_eq!(
+            track_end_delay(None, DEFAULT_CHECK_INTERVAL),
+            DEFAULT_CHECK_INTERVAL
+        );
+    }
+
+    #[tokio::test]
+    async fn test_short_check_interval_advances_sooner() {
+        let player = Arc::new(detached_player());
+        player.set_fade_duration(Duration::ZERO);
+        player.set_check_interval(Duration::from_millis(100));
+        assert_eq!(player.next_check_delay(), Duration::from_millis(100));
+
+        let playlist = vec![wav_track("tick0", 1), wav_track("tick1", 1)];
+        player.play(&playlist, 0).unwrap();
+        {
+            // Without a known length only the interval decides when the end is noticed
+            let mut inner = player.inner.lock().unwrap();
+            inner.duration = None;
+            let mut output = inner.detached_output.take().unwrap();
+            for _ in 0..100 {
+                if inner.sink.as_ref().unwrap().empty() {
+                    break;
+                }
+                output.by_ref().take(800).for_each(drop);
+            }
+            assert!(inner.sink.as_ref().unwrap().empty());
+        }
+
+        let started = Instant::now();
+        let shutdown_token = CancellationToken::new();
+        let running = tokio::spawn({
+            let player = player.clone();
+            let shutdown_token = shutdown_token.clone();
+            async move { player.run(shutdown_token).await }
+        });
+        while player.inner.lock().unwrap().current_index != Some(1) {
+            assert!(
+                started.elapsed() < Duration::from_millis(600),
+                "Still on the first track after {:?}",
+                started.elapsed()
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        shutdown_token.cancel();
+        running.await.unwrap();
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_stopped_sink_does_not_advance() {
+        let (sink, _output) = Sink::new();
+        let player = player_with_sink(sink);
+        {
+            let mut inner = player.inner.lock().unwrap();
+            inner.playlist = Some(Playlist {
+                tracks: vec![track("a"), track("b")],
+            });
+            inner.current_index = Some(0);
+        }
+
+        player.stop().unwrap();
+        player.play_next();
+
+        assert_eq!(player.inner.lock().unwrap().current_index, Some(0));
+    }
+
+    #[test]
+    fn test_volume_round_trip() {
+        let player = MusicPlayer::new();
+        player.set_volume(0.3).unwrap();
+        assert_eq!(player.sink_volume(), 0.3);
+
+        let (sink, _output) = Sink::new();
+        player.inner.lock().unwrap().sink = Some(sink);
+        player.set_volume(0.7).unwrap();
+        assert_eq!(player.sink_volume(), 0.7);
+        assert_eq!(player.status().unwrap().volume, 0.7);
+    }
+
+    #[test]
+    fn test_status_reports_volume_and_led_scale_independently() {
+        let player = MusicPlayer::new();
+        player.set_volume(0.25).unwrap();
+
+        let mut status = player.status().unwrap();
+        status.led_scale = Some(0.8);
+
+        let value = serde_json::to_value(&status).unwrap();
+        assert_eq!(value["volume"], serde_json::json!(0.25));
+        assert_eq!(value["led_scale"], serde_json::json!(0.8f32));
+    }
+
+    #[test]
+    fn test_fade_factors() {
+        let fade_in: Vec<f32> = fade_factors(5, true).collect();
+        assert_eq!(fade_in, [0.2, 0.4, 0.6, 0.8, 1.0]);
+        let fade_out: Vec<f32> = fade_factors(4, false).collect();
+        assert_eq!(fade_out, [0.75, 0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_toggle_fades_in_toward_volume() {
+        let (sink, _output) = Sink::new();
+        sink.append(SamplesBuffer::new(1, 44100, vec![0.0f32; 44100]));
+        sink.pause();
+        let player = player_with_sink(sink);
+        player.set_volume(0.6).unwrap();
+
+        assert!(!player.toggle().unwrap());
+        // The ramp starts from silence and climbs to the volume
+        assert!(player.sink_volume() < 0.6);
+        std::thread::sleep(DEFAULT_FADE_DURATION * 4);
+        assert_eq!(player.sink_volume(), 0.6);
+        assert!(player.is_playing());
+    }
+
+    #[test]
+    fn test_rapid_toggles_do_not_stack() {
+        let (sink, _output) = Sink::new();
+        sink.append(SamplesBuffer::new(1, 44100, vec![0.0f32; 44100]));
+        let player = player_with_sink(sink);
+        player.set_volume(0.6).unwrap();
+
+        // Fade out, then back in before the fade-out could pause
+        assert!(player.toggle().unwrap());
+        assert!(!player.toggle().unwrap());
+        std::thread::sleep(DEFAULT_FADE_DURATION * 4);
+
+        assert!(player.is_playing());
+        assert_eq!(player.sink_volume(), 0.6);
+    }
+
+    #[test]
+    fn test_sleep_timer_stops_at_deadline() {
+        // Nothing pulls the output here, so stopping must not wait on it
+        let (sink, output) = Sink::new();
+        drop(output);
+        sink.append(SamplesBuffer::new(1, 44100, vec![0.0f32; 44100]));
+        let player = player_with_sink(sink);
+        player.set_volume(0.8).unwrap();
+        player.inner.lock().unwrap().current_track = Some("bedtime".to_string());
+        player
+            .set_sleep_timer(Some(Duration::from_secs(60)), true)
+            .unwrap();
+        let start = Instant::now();
+
+        // Halfway through the fade
+        player.check_sleep_timer(start + Duration::from_secs(45));
+        assert!((player.sink_volume() - 0.4).abs() < 0.05);
+        assert!(player.is_playing());
+
+        player.check_sleep_timer(start + Duration::from_secs(61));
+        assert!(!player.is_playing());
+        assert_eq!(player.sleep_remaining(), None);
+        assert_eq!(player.sink_volume(), 0.8);
+        assert_eq!(player.inner.lock().unwrap().current_track, None);
+    }
+
+    #[test]
+    fn test_sleep_timer_cancel() {
+        let player = MusicPlayer::new();
+        player
+            .set_sleep_timer(Some(Duration::from_secs(600)), false)
+            .unwrap();
+        assert!(player.sleep_remaining().is_some());
+
+        player.set_sleep_timer(None, false).unwrap();
+        assert_eq!(player.sleep_remaining(), None);
+    }
+
+    #[test]
+    fn test_play_rejects_out_of_range_index() {
+        let player = MusicPlayer::new();
+        assert!(player.play(&vec![track("a")], 3).is_err());
+        assert_eq!(player.inner.lock().unwrap().current_index, None);
+    }
+
+    fn track(name: &str) -> Track {
+        Track {
+            name: name.to_string(),
+            path: format!("/music/{}.mp3", name),
+        }
+    }
+
+    fn detached_player() -> MusicPlayer {
+        let player = MusicPlayer::new();
+        player.detach();
+        player
+    }
+
+    #[test]
+    fn test_detached_player_stops_without_a_reader() {
+        // Nothing pulls the detached output, so a stop that waited for the
+        // queue to drain would hang here
+        let player = detached_player();
+        player.set_fade_duration(Duration::ZERO);
+        let playlist = vec![wav_track("first", 1), wav_track("second", 1)];
+        player.play(&playlist, 0).unwrap();
+        player.next().unwrap();
+        assert_eq!(player.playback_state(), PlaybackState::Playing);
+
+        player.stop().unwrap();
+        assert_eq!(player.playback_state(), PlaybackState::Stopped);
+        assert_eq!(player.inner.lock().unwrap().current_track, None);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_auto_advance_skips_unplayable_track() {
+        let player = detached_player();
+        let bad = Track {
+            name: "bad".to_string(),
+            path: std::env::temp_dir()
+                .join(format!("emes-bad-{}.mp3", std::process::id()))
+                .to_string_lossy()
+                .to_string(),
+        };
+        std::fs::write(&bad.path, b"definitely not audio").unwrap();
+        let playlist = vec![wav_track("first", 1), bad, wav_track("third", 1)];
+        player.load(&playlist, 0).unwrap();
+
+        player.advance_skipping_unplayable();
+
+        let inner = player.inner.lock().unwrap();
+        assert_eq!(inner.current_index, Some(2));
+        assert_eq!(inner.current_track.as_deref(), Some("third"));
+        assert!(!inner.sink.as_ref().unwrap().is_paused());
+        drop(inner);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_auto_advance_gives_up_without_playable_tracks() {
+        let player = detached_player();
+        let good = wav_track("only-good", 1);
+        player.load(&vec![good.clone()], 0).unwrap();
+        std::fs::write(&good.path, b"no longer audio").unwrap();
+
+        player.advance_skipping_unplayable();
+
+        assert_eq!(player.inner.lock().unwrap().current_track, None);
+        let _ = std::fs::remove_file(&good.path);
+    }
+
+    #[test]
+    fn test_load_leaves_track_paused() {
+        let player = detached_player();
+        let track = wav_track("load", 2);
+        player.load(&vec![track.clone()], 0).unwrap();
+
+        let status = player.status().unwrap();
+        assert!(status.paused);
+        assert!(!status.playing);
+        assert_eq!(status.current_track.as_deref(), Some("load"));
+        assert_eq!(status.duration.as_deref(), Some("00:02"));
+        assert_eq!(status.duration_sec, Some(2));
+        let _ = std::fs::remove_file(&track.path);
+    }
+
+    #[test]
+    fn test_enqueue_mid_playback_appends() {
+        let player = detached_player();
+        player.set_fade_duration(Duration::ZERO);
+        let first = wav_track("enqueue-a", 1);
+        let second = wav_track("enqueue-b", 1);
+
+        // Idle, so the first track starts right away
+        player.enqueue(first.clone()).unwrap();
+        assert_eq!(player.playback_state(), PlaybackState::Playing);
+
+        player.enqueue(second.clone()).unwrap();
+        {
+            let inner = player.inner.lock().unwrap();
+            let tracks = &inner.playlist.as_ref().unwrap().tracks;
+            assert_eq!(tracks.len(), 2);
+            assert_eq!(tracks[1].name, "enqueue-b");
+            assert_eq!(inner.current_index, Some(0));
+        }
+
+        // Play the first track out, then the enqueued one follows it
+        let mut output = player.inner.lock().unwrap().detached_output.take().unwrap();
+        for _ in 0..100 {
+            if player.playback_state() == PlaybackState::Stopped {
+                break;
+            }
+            output.by_ref().take(800).for_each(drop);
+        }
+        player.play_next();
+
+        assert_eq!(player.playback_state(), PlaybackState::Playing);
+        let inner = player.inner.lock().unwrap();
+        assert_eq!(inner.current_index, Some(1));
+        assert_eq!(inner.current_track.as_deref(), Some("enqueue-b"));
+        drop(inner);
+        let _ = std::fs::remove_file(&first.path);
+        let _ = std::fs::remove_file(&second.path);
+    }
+
+    #[test]
+    fn test_moved_index() {
+        // Moving the current track itself
+        assert_eq!(moved_index(1, 1, 3), 3);
+        // A later track moved in front of the current one pushes it back
+        assert_eq!(moved_index(1, 3, 0), 2);
+        // An earlier track moved behind it pulls it forward
+        assert_eq!(moved_index(2, 0, 3), 1);
+        // Moves entirely on one side leave it alone
+        assert_eq!(moved_index(0, 1, 3), 0);
+        assert_eq!(moved_index(3, 0, 2), 3);
+    }
+
+    #[test]
+    fn test_move_current_track_keeps_playing() {
+        let player = detached_player();
+        let playlist = vec![wav_track("m0", 1), wav_track("m1", 1), wav_track("m2", 1)];
+        player.play(&playlist, 1).unwrap();
+
+        assert!(player.move_track(1, 2).unwrap());
+        assert!(!player.move_track(0, 3).unwrap());
+        assert!(!player.move_track(5, 0).unwrap());
+
+        let inner = player.inner.lock().unwrap();
+        assert_eq!(inner.current_index, Some(2));
+        assert_eq!(inner.current_track.as_deref(), Some("m1"));
+        let names: Vec<&str> = inner
+            .playlist
+            .as_ref()
+            .unwrap()
+            .tracks
+            .iter()
+            .map(Track::name)
+            .collect();
+        assert_eq!(names, ["m0", "m2", "m1"]);
+        assert!(!inner.sink.as_ref().unwrap().is_paused());
+        drop(inner);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_volume_carries_over_track_changes() {
+        let player = detached_player();
+        player.set_fade_duration(Duration::ZERO);
+        let playlist = vec![wav_track("v0", 1), wav_track("v1", 1), wav_track("v2", 1)];
+        player.play(&playlist, 0).unwrap();
+
+        player.set_volume(0.3).unwrap();
+        player.next().unwrap();
+        assert_eq!(player.sink_volume(), 0.3);
+        player.prev().unwrap();
+        assert_eq!(player.sink_volume(), 0.3);
+        assert!(player.play_index(2).unwrap());
+        assert_eq!(player.sink_volume(), 0.3);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_mute_survives_track_change() {
+        let player = detached_player();
+        player.set_fade_duration(Duration::ZERO);
+        let playlist = vec![wav_track("mute0", 1), wav_track("mute1", 1)];
+        player.set_volume(0.6).unwrap();
+        player.play(&playlist, 0).unwrap();
+
+        player.set_muted(true).unwrap();
+        assert_eq!(player.sink_volume(), 0.0);
+        player.advance_skipping_unplayable();
+        assert_eq!(player.inner.lock().unwrap().current_index, Some(1));
+        assert_eq!(player.sink_volume(), 0.0);
+        let status = player.status().unwrap();
+        assert!(status.muted);
+        assert_eq!(status.volume, 0.6);
+
+        player.set_muted(false).unwrap();
+        assert_eq!(player.sink_volume(), 0.6);
+        assert!(!player.status().unwrap().muted);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_play_index_jumps_within_playlist() {
+        let player = detached_player();
+        let playlist: Vec<Track> = (0..4).map(|i| wav_track(&format!("j{}", i), 1)).collect();
+        player.play(&playlist, 0).unwrap();
+
+        assert!(player.play_index(3).unwrap());
+        assert!(!player.play_index(4).unwrap());
+
+        let status = player.status().unwrap();
+        assert_eq!(status.current_track.as_deref(), Some("j3"));
+        assert!(status.playing);
+        assert_eq!(player.inner.lock().unwrap().current_index, Some(3));
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_replaying_current_track_keeps_position() {
+        let player = detached_player();
+        player.set_fade_duration(Duration::ZERO);
+        let playlist = vec![wav_track("replay0", 5), wav_track("replay1", 1)];
+        player.play(&playlist, 0).unwrap();
+
+        // Play a second or so of the track
+        let mut output = player.inner.lock().unwrap().detached_output.take().unwrap();
+        for _ in 0..100 {
+            if player.status().unwrap().position_sec >= Some(1) {
+                break;
+            }
+            output.by_ref().take(800).for_each(drop);
+        }
+
+        player.play(&playlist, 0).unwrap();
+        let status = player.status().unwrap();
+        assert!(status.playing);
+        assert!(status.position_sec >= Some(1), "{}", status);
+        assert_eq!(player.metrics.tracks_played(), 1);
+
+        // Paused, the same request resumes it where it was
+        player.toggle().unwrap();
+        player.play(&playlist, 0).unwrap();
+        assert_eq!(player.playback_state(), PlaybackState::Playing);
+        assert!(player.status().unwrap().position_sec >= Some(1));
+
+        // Another index still loads a new track
+        player.play(&playlist, 1).unwrap();
+        assert_eq!(player.status().unwrap().position_sec, Some(0));
+        assert_eq!(player.metrics.tracks_played(), 2);
+        for track in &playlist {
+            let _ = std::fs::remove_file(&track.path);
+        }
+    }
+
+    #[test]
+    fn test_play_index_without_playlist() {
+        let player = detached_player();
+        assert!(!player.play_index(0).unwrap());
+    }
+
+    #[test]
+    fn test_clear_queue_keeps_current_track() {
+        let player = MusicPlayer::new();
+        {
+            let mut inner = player.inner.lock().unwrap();
+            inner.playlist = Some(Playlist {
+                tracks: vec![track("a"), track("b"), track("c")],
+            });
+            inner.current_index = Some(1);
+        }
+
+        player.clear_queue().unwrap();
+
+        let inner = player.inner.lock().unwrap();
+        let tracks = &inner.playlist.as_ref().unwrap().tracks;
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].name, "b");
+        assert_eq!(inner.current_index, Some(0));
+    }
+
+    #[test]
+    fn test_analyze_square_wave() {
+        let samples: Vec<i16> = (0..16000)
+            .map(|i| if i % 2 == 0 { 16384 } else { -16384 })
+            .collect();
+        let track = wav_file("analyze", 8000, &samples);
+        let path = Path::new(&track.path);
+
+        let full = MusicPlayer::analyze(path, None).unwrap();
+        assert_eq!((full.sample_rate, full.channels), (8000, 1));
+        assert_eq!(full.duration, Duration::from_secs(2));
+        assert!((full.rms - 0.5).abs() < 1e-3, "{:?}", full);
+        assert!((full.peak - 0.5).abs() < 1e-3, "{:?}", full);
+
+        let start = MusicPlayer::analyze(path, Some(Duration::from_millis(500))).unwrap();
+        assert_eq!(start.duration, Duration::from_millis(500));
+        assert!((start.rms - full.rms).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,107 @@
+use lofty::file::TaggedFileExt;
+use lofty::picture::PictureType;
+use lofty::tag::Accessor;
+use serde::Serialize;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use super::MusicPlayer;
+
+/// How much audio `probe` decodes to confirm a file plays
+const PROBE_DECODE_LIMIT: Duration = Duration::from_millis(250);
+
+/// Tags read from an audio file, empty when the file has none or can't be read
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+pub fn read_tags(path: &Path) -> TrackTags {
+    let Ok(tagged_file) = lofty::read_from_path(path) else {
+        return TrackTags::default();
+    };
+    let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    else {
+        return TrackTags::default();
+    };
+
+    TrackTags {
+        title: tag.title().map(|s| s.into_owned()),
+        artist: tag.artist().map(|s| s.into_owned()),
+        album: tag.album().map(|s| s.into_owned()),
+    }
+}
+
+/// The embedded front cover, or the first picture, with its mime type
+pub fn read_cover(path: &Path) -> Option<(Vec<u8>, String)> {
+    let tagged_file = lofty::read_from_path(path).ok()?;
+    let picture = tagged_file
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures())
+        .min_by_key(|picture| picture.pic_type() != PictureType::CoverFront)?;
+    let mime_type = picture
+        .mime_type()
+        .map_or("application/octet-stream", |mime| mime.as_str());
+    Some((picture.data().to_vec(), mime_type.to_string()))
+}
+
+/// Whether a file can be played, from its headers and its first samples
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FileProbe {
+    pub playable: bool,
+    /// Short codec name, e.g. `mp3` or `flac`
+    pub format: Option<String>,
+    pub duration_sec: Option<f64>,
+}
+
+pub fn probe(path: &Path) -> FileProbe {
+    let Ok(file) = File::open(path) else {
+        return FileProbe::default();
+    };
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let Ok(probed) = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        return FileProbe::default();
+    };
+    let Some(track) = probed.format.default_track() else {
+        return FileProbe::default();
+    };
+
+    let codecs = symphonia::default::get_codecs();
+    let params = &track.codec_params;
+    // Headers can look fine on a file the player's decoder still rejects, or
+    // that holds no audio at all
+    let playable = codecs.make(params, &DecoderOptions::default()).is_ok()
+        && MusicPlayer::analyze(path, Some(PROBE_DECODE_LIMIT))
+            .is_ok_and(|analysis| !analysis.duration.is_zero());
+    FileProbe {
+        playable,
+        format: codecs
+            .get_codec(params.codec)
+            .map(|codec| codec.short_name.to_string()),
+        duration_sec: params
+            .n_frames
+            .zip(params.sample_rate)
+            .map(|(frames, rate)| frames as f64 / rate as f64),
+    }
+}
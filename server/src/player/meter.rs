@@ -0,0 +1,170 @@
+use rodio::{Sample, Source, source::SeekError};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// Length of the window each reading covers
+const METER_WINDOW: Duration = Duration::from_millis(50);
+
+/// Loudness of the last few milliseconds of audio, both from 0.0 to 1.0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct AudioLevels {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// The latest [`AudioLevels`], written by the audio thread and read by anyone
+#[derive(Clone, Default)]
+pub struct SharedLevels {
+    rms: Arc<AtomicU32>,
+    peak: Arc<AtomicU32>,
+}
+
+impl SharedLevels {
+    pub fn get(&self) -> AudioLevels {
+        AudioLevels {
+            rms: f32::from_bits(self.rms.load(Ordering::Relaxed)),
+            peak: f32::from_bits(self.peak.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn publish(&self, levels: AudioLevels) {
+        self.rms.store(levels.rms.to_bits(), Ordering::Relaxed);
+        self.peak.store(levels.peak.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Back to silence, e.g. once playback stops
+    pub fn reset(&self) {
+        self.publish(AudioLevels::default());
+    }
+}
+
+/// Passes `source` through untouched while publishing the RMS and peak of
+/// every [`METER_WINDOW`] of samples, all channels together
+pub struct MeterSource<S> {
+    source: S,
+    levels: SharedLevels,
+    window_len: usize,
+    count: usize,
+    sum_squares: f32,
+    peak: f32,
+}
+
+impl<S: Source> MeterSource<S> {
+    pub fn new(source: S, levels: SharedLevels) -> Self {
+        let samples_per_second = source.sample_rate() as f32 * f32::from(source.channels().max(1));
+        let window_len = (samples_per_second * METER_WINDOW.as_secs_f32()).max(1.0) as usize;
+        Self {
+            source,
+            levels,
+            window_len,
+            count: 0,
+            sum_squares: 0.0,
+            peak: 0.0,
+        }
+    }
+}
+
+impl<S: Source> Iterator for MeterSource<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let sample = self.source.next()?;
+        self.sum_squares += sample * sample;
+        self.peak = self.peak.max(sample.abs());
+        self.count += 1;
+
+        if self.count == self.window_len {
+            self.levels.publish(AudioLevels {
+                rms: (self.sum_squares / self.count as f32).sqrt(),
+                peak: self.peak,
+            });
+            self.count = 0;
+            self.sum_squares = 0.0;
+            self.peak = 0.0;
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for MeterSource<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)?;
+        self.count = 0;
+        self.sum_squares = 0.0;
+        self.peak = 0.0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 44100;
+
+    fn metered(samples: Vec<f32>) -> SharedLevels {
+        let levels = SharedLevels::default();
+        MeterSource::new(SamplesBuffer::new(1, RATE, samples), levels.clone()).for_each(drop);
+        levels
+    }
+
+    #[test]
+    fn test_square_wave_levels() {
+        let samples = (0..RATE)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        let levels = metered(samples).get();
+        assert!((levels.rms - 0.5).abs() < 1e-4, "{:?}", levels);
+        assert!((levels.peak - 0.5).abs() < 1e-6, "{:?}", levels);
+    }
+
+    #[test]
+    fn test_sine_rms() {
+        let samples = (0..RATE)
+            .map(|i| 0.8 * (2.0 * PI * 1000.0 * i as f32 / RATE as f32).sin())
+            .collect();
+        let levels = metered(samples).get();
+        // A sine's RMS is its amplitude over the square root of two
+        assert!(
+            (levels.rms - 0.8 / 2f32.sqrt()).abs() < 0.01,
+            "{:?}",
+            levels
+        );
+        assert!(levels.peak <= 0.8 && levels.peak > 0.79, "{:?}", levels);
+    }
+
+    #[test]
+    fn test_passes_samples_through() {
+        let samples = vec![0.25, -0.5, 0.75];
+        let out: Vec<f32> = MeterSource::new(
+            SamplesBuffer::new(1, RATE, samples.clone()),
+            SharedLevels::default(),
+        )
+        .collect();
+        assert_eq!(out, samples);
+    }
+}
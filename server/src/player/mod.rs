@@ -1,5 +1,14 @@
+mod equalizer;
 mod lib;
+pub mod metadata;
+mod meter;
 mod player_state;
 
-pub use lib::{MusicPlayer, PlayerStatus, Track};
+pub use equalizer::EqSettings;
+#[cfg(test)]
+pub(crate) use lib::wav_track;
+pub use lib::{
+    MusicPlayer, PlaybackState, PlayerStatus, Track, TrackStarted, is_output_device_present,
+};
+pub use meter::AudioLevels;
 pub use player_state::PlayerState;
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::metrics::Metrics;
 use crate::player::MusicPlayer;
 
 #[derive(Clone)]
@@ -9,9 +10,9 @@ pub struct PlayerState {
 }
 
 impl PlayerState {
-    pub fn new() -> Self {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
         Self {
-            music_player: Arc::new(MusicPlayer::new()),
+            music_player: Arc::new(MusicPlayer::with_metrics(metrics)),
         }
     }
 
@@ -1,15 +1,27 @@
+use serde::Serialize;
 use socketioxide::SocketIo;
-use socketioxide::extract::{Data, SocketRef, State};
+use socketioxide::extract::{Data, SocketRef, State, TryData};
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
+use crate::api::py_tasks::training::TrainingProgress;
 use crate::app_state::AppState;
+use crate::dao::file_dao::FileDao;
+use crate::player::TrackStarted;
+use crate::player::metadata;
 
 mod ns_ai;
+mod ns_player;
+mod registry;
+
+use ns_player::{PlayCommand, PlayerCommand};
+pub use registry::SocketRegistry;
 
 pub async fn io_ai_ns(io: &SocketIo) {
     io.ns(
         "/ai",
         async |s: SocketRef, State(app_state): State<Arc<AppState>>| {
+            app_state.socket_registry.track(&s, "/ai");
             // tracing::info!("ai namespace connected");
             // s.on("training:ack", ns_ai::on_training_ack);
             s.on(
@@ -25,3 +37,205 @@ pub async fn io_ai_ns(io: &SocketIo) {
         },
     );
 }
+
+/// Relay progress reported by Python training code as `training:progress` on `/ai`
+pub fn forward_training_progress(
+    io: SocketIo,
+    mut progress_receiver: broadcast::Receiver<TrainingProgress>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let progress = match progress_receiver.recv().await {
+                Ok(progress) => progress,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Dropped {} training progress events", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let Some(ns) = io.of("/ai") else {
+                continue;
+            };
+            if let Err(e) = ns.emit("training:progress", &progress).await {
+                tracing::error!("Failed to emit training progress: {}", e);
+            }
+        }
+    });
+}
+
+/// Builds the command an argument-less `/player` event stands for
+type MakeCommand = fn() -> PlayerCommand;
+
+/// Playback commands on `/player`, each answered with `status` on the same socket
+pub async fn io_player_ns(io: &SocketIo) {
+    io.ns(
+        "/player",
+        async |s: SocketRef, State(app_state): State<Arc<AppState>>| {
+            app_state.socket_registry.track(&s, "/player");
+            let player = app_state.player_state.get_music_player();
+
+            let commands: [(&'static str, MakeCommand); 4] = [
+                ("pause", || PlayerCommand::Pause),
+                ("toggle", || PlayerCommand::Toggle),
+                ("next", || PlayerCommand::Next),
+                ("prev", || PlayerCommand::Prev),
+            ];
+            for (event, command) in commands {
+                let player = player.clone();
+                s.on(event, async move |s: SocketRef| {
+                    ns_player::reply(&s, &player, command());
+                });
+            }
+
+            let play_player = player.clone();
+            s.on(
+                "play",
+                async move |s: SocketRef, TryData(req): TryData<PlayCommand>| {
+                    ns_player::reply(&s, &play_player, PlayerCommand::Play(req.ok()));
+                },
+            );
+            let seek_player = player.clone();
+            s.on(
+                "seek",
+                async move |s: SocketRef, Data(seconds): Data<f32>| {
+                    ns_player::reply(&s, &seek_player, PlayerCommand::Seek(seconds));
+                },
+            );
+            s.on(
+                "volume",
+                async move |s: SocketRef, Data(volume): Data<f32>| {
+                    ns_player::reply(&s, &player, PlayerCommand::Volume(volume));
+                },
+            );
+        },
+    );
+}
+
+/// Everything a web client needs to render a player card
+#[derive(Debug, Serialize)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_sec: Option<u64>,
+    pub file_id: Option<i64>,
+    /// Only set when the file has embedded art to serve
+    pub cover_url: Option<String>,
+}
+
+/// Describe a started track from its tags and its `file` table entry
+async fn now_playing_event(app_state: &AppState, started: TrackStarted) -> NowPlaying {
+    let path = started.path.clone();
+    let (tags, has_cover) = tokio::task::spawn_blocking(move || {
+        (
+            metadata::read_tags(&path),
+            metadata::read_cover(&path).is_some(),
+        )
+    })
+    .await
+    .unwrap_or_default();
+
+    let file_id = match FileDao::new(&app_state.db_state).await {
+        Ok(file_dao) => file_dao
+            .get_file_by_name(&started.name)
+            .await
+            .and_then(|file| file.id),
+        Err(_) => None,
+    };
+
+    NowPlaying {
+        title: tags.title.unwrap_or(started.name),
+        artist: tags.artist,
+        album: tags.album,
+        duration_sec: started.duration.map(|d| d.as_secs()),
+        file_id,
+        cover_url: file_id
+            .filter(|_| has_cover)
+            .map(|id| format!("/api/filelist/{}/cover", id)),
+    }
+}
+
+/// Emit `now_playing` on `/player` whenever the music player starts a track
+pub fn forward_now_playing(
+    io: SocketIo,
+    app_state: Arc<AppState>,
+    mut started_receiver: broadcast::Receiver<TrackStarted>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let started = match started_receiver.recv().await {
+                Ok(started) => started,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Dropped {} now playing events", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            // Reading tags and the file row is wasted when nobody would get the event
+            if app_state.socket_registry.sockets_in("/player").is_empty() {
+                continue;
+            }
+            let event = now_playing_event(&app_state, started).await;
+            let Some(ns) = io.of("/player") else {
+                continue;
+            };
+            if let Err(e) = ns.emit("now_playing", &event).await {
+                tracing::error!("Failed to emit now playing: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dao::file_dao::FileEntry;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_now_playing_event_fields() {
+        let app_state = AppState::open_temp("now-playing").await;
+        let path = std::env::temp_dir().join("emes_now_playing_song.mp3");
+        std::fs::write(&path, b"not really audio").unwrap();
+        FileDao::new(&app_state.db_state)
+            .await
+            .unwrap()
+            .insert_file(FileEntry {
+                id: None,
+                name: "song.mp3".to_string(),
+                size: 16.0,
+                path: path.display().to_string(),
+                class: 0,
+                is_training_data: None,
+                created_at: 0.0,
+            })
+            .await
+            .unwrap();
+
+        let started = TrackStarted {
+            name: "song.mp3".to_string(),
+            path: path.clone(),
+            duration: Some(Duration::from_secs(185)),
+        };
+        let event = serde_json::to_value(now_playing_event(&app_state, started).await).unwrap();
+
+        // Untagged files fall back to the track name and have no cover to link
+        assert_eq!(event["title"], "song.mp3");
+        assert_eq!(event["artist"], serde_json::Value::Null);
+        assert_eq!(event["album"], serde_json::Value::Null);
+        assert_eq!(event["duration_sec"], 185);
+        assert!(event["file_id"].is_i64());
+        assert_eq!(event["cover_url"], serde_json::Value::Null);
+
+        let unknown = TrackStarted {
+            name: "elsewhere.mp3".to_string(),
+            path,
+            duration: None,
+        };
+        let event = now_playing_event(&app_state, unknown).await;
+        assert_eq!(event.file_id, None);
+        assert_eq!(event.duration_sec, None);
+    }
+}
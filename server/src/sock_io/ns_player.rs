@@ -0,0 +1,105 @@
+use anyhow::Result;
+use serde::Deserialize;
+use socketioxide::extract::SocketRef;
+
+use crate::player::{MusicPlayer, PlaybackState, PlayerStatus, Track};
+
+/// Payload of `play`, the same shape as `POST /api/player/play`
+#[derive(Debug, Deserialize)]
+pub struct PlayCommand {
+    pub playlist: Vec<Track>,
+    pub selected_index: usize,
+}
+
+/// A playback command received on `/player`
+#[derive(Debug)]
+pub enum PlayerCommand {
+    /// Start the given playlist, or resume what's loaded
+    Play(Option<PlayCommand>),
+    Pause,
+    Toggle,
+    Next,
+    Prev,
+    /// Jump to this many seconds into the track
+    Seek(f32),
+    Volume(f32),
+}
+
+/// Apply `command` to the player, returning the status to reply with
+pub fn dispatch(player: &MusicPlayer, command: PlayerCommand) -> Result<PlayerStatus> {
+    match command {
+        PlayerCommand::Play(Some(req)) => player.play(&req.playlist, req.selected_index)?,
+        PlayerCommand::Play(None) => {
+            if player.playback_state() == PlaybackState::Paused {
+                player.toggle()?;
+            }
+        }
+        PlayerCommand::Pause => {
+            if player.playback_state() == PlaybackState::Playing {
+                player.toggle()?;
+            }
+        }
+        PlayerCommand::Toggle => {
+            player.toggle()?;
+        }
+        PlayerCommand::Next => player.next()?,
+        PlayerCommand::Prev => player.prev()?,
+        PlayerCommand::Seek(seconds) => player.seek_to(seconds)?,
+        PlayerCommand::Volume(volume) => player.set_volume(volume)?,
+    }
+    player.status()
+}
+
+/// Run `command` and answer on the same socket with `status`, or `error`
+pub fn reply(s: &SocketRef, player: &MusicPlayer, command: PlayerCommand) {
+    let sent = match dispatch(player, command) {
+        Ok(status) => s.emit("status", &status),
+        Err(e) => {
+            tracing::error!("Failed to run player command: {:#}", e);
+            s.emit("error", &format!("{:#}", e))
+        }
+    };
+    if let Err(e) = sent {
+        tracing::error!("Failed to reply to player command: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::wav_track;
+    use std::time::Duration;
+
+    #[test]
+    fn test_toggle_replies_with_status() {
+        let player = MusicPlayer::new();
+        player.detach();
+        player.set_fade_duration(Duration::ZERO);
+        let track = wav_track("ns-player-toggle", 2);
+        let play = PlayCommand {
+            playlist: vec![track.clone()],
+            selected_index: 0,
+        };
+        let status = dispatch(&player, PlayerCommand::Play(Some(play))).unwrap();
+        assert!(status.playing);
+        assert_eq!(status.current_track.as_deref(), Some("ns-player-toggle"));
+
+        let status = dispatch(&player, PlayerCommand::Toggle).unwrap();
+        assert!(status.paused);
+        assert!(!status.playing);
+        assert!(player.is_paused());
+
+        let status = dispatch(&player, PlayerCommand::Toggle).unwrap();
+        assert!(status.playing);
+
+        // Pause and play only ever move towards their state
+        assert!(dispatch(&player, PlayerCommand::Pause).unwrap().paused);
+        assert!(dispatch(&player, PlayerCommand::Pause).unwrap().paused);
+        assert!(
+            dispatch(&player, PlayerCommand::Play(None))
+                .unwrap()
+                .playing
+        );
+        let _ = std::fs::remove_file(track.path());
+    }
+}
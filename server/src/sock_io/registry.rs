@@ -0,0 +1,75 @@
+use socketioxide::extract::SocketRef;
+use socketioxide::socket::Sid;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Sockets connected right now, by id, with the namespace each joined.
+/// A client that reconnects gets a new id, its old entry goes on disconnect.
+#[derive(Clone, Default)]
+pub struct SocketRegistry {
+    sockets: Arc<Mutex<HashMap<Sid, &'static str>>>,
+}
+
+impl SocketRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `s` and remove it again once it disconnects
+    pub fn track(&self, s: &SocketRef, ns: &'static str) {
+        self.register(s.id, ns);
+        let registry = self.clone();
+        s.on_disconnect(async move |s: SocketRef| {
+            registry.unregister(s.id);
+        });
+    }
+
+    pub fn register(&self, sid: Sid, ns: &'static str) {
+        self.sockets.lock().unwrap().insert(sid, ns);
+    }
+
+    /// Returns `false` when the socket wasn't registered
+    pub fn unregister(&self, sid: Sid) -> bool {
+        self.sockets.lock().unwrap().remove(&sid).is_some()
+    }
+
+    #[cfg(test)]
+    pub fn count(&self) -> usize {
+        self.sockets.lock().unwrap().len()
+    }
+
+    /// Sockets connected to `ns`, to skip building an update none would receive
+    pub fn sockets_in(&self, ns: &str) -> Vec<Sid> {
+        self.sockets
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, socket_ns)| **socket_ns == ns)
+            .map(|(sid, _)| *sid)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_and_disconnect_update_count() {
+        let registry = SocketRegistry::new();
+        let (player, ai) = (Sid::new(), Sid::new());
+
+        registry.register(player, "/player");
+        registry.register(ai, "/ai");
+        assert_eq!(registry.count(), 2);
+        assert_eq!(registry.sockets_in("/player"), [player]);
+
+        assert!(registry.unregister(player));
+        assert_eq!(registry.count(), 1);
+        assert!(registry.sockets_in("/player").is_empty());
+        // A second disconnect of the same socket changes nothing
+        assert!(!registry.unregister(player));
+        assert!(registry.unregister(ai));
+        assert_eq!(registry.count(), 0);
+    }
+}
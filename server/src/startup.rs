@@ -0,0 +1,159 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::dao::db_state::ensure_writable;
+use crate::player::is_output_device_present;
+use crate::ws2812::{SpiConfig, strip_config};
+
+/// A precondition the machine doesn't meet, and what the server does about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartupIssue {
+    pub component: &'static str,
+    pub problem: String,
+    pub impact: String,
+    /// The server can't run at all without this, rather than just losing a feature
+    pub fatal: bool,
+}
+
+impl fmt::Display for StartupIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}; {}", self.component, self.problem, self.impact)
+    }
+}
+
+/// Check what the server expects from the board before anything opens it, so
+/// a missing piece is reported up front instead of surfacing as a panic later
+pub fn validate_config(config: &Config) -> Vec<StartupIssue> {
+    let mut issues = Vec::new();
+    issues.extend(check_audio_output(is_output_device_present()));
+    issues.extend(match strip_config(&config.led_strip) {
+        Ok(strip) => check_led_strip(&strip, config.led_strip.device_wait_ms),
+        Err(e) => Some(StartupIssue {
+            component: "LED strip",
+            problem: e.to_string(),
+            impact: "the LED strip is disabled; fix led_strip.device and restart".to_string(),
+            fatal: false,
+        }),
+    });
+    issues.extend(check_database(&config.database.resolve_path()));
+    issues
+}
+
+/// Log every issue, errors for fatal ones and warnings for the rest
+pub fn report(issues: &[StartupIssue]) {
+    if issues.is_empty() {
+        tracing::info!("All startup checks passed");
+        return;
+    }
+    for issue in issues {
+        if issue.fatal {
+            tracing::error!("{}", issue);
+        } else {
+            tracing::warn!("{}", issue);
+        }
+    }
+}
+
+fn check_audio_output(present: bool) -> Option<StartupIssue> {
+    (!present).then(|| StartupIssue {
+        component: "Audio output",
+        problem: "the es8388 output device was not found".to_string(),
+        impact: "playback is unavailable until the codec is connected".to_string(),
+        fatal: false,
+    })
+}
+
+fn check_led_strip(config: &SpiConfig, device_wait_ms: u64) -> Option<StartupIssue> {
+    if config.num_leds == 0 {
+        return Some(StartupIssue {
+            component: "LED strip",
+            problem: "the strip is configured with 0 LEDs".to_string(),
+            impact: "LED effects are accepted but nothing lights up".to_string(),
+            fatal: false,
+        });
+    }
+
+    let device_path = config.device_path();
+    if Path::new(&device_path).exists() {
+        return None;
+    }
+    let impact = if device_wait_ms == 0 {
+        "the LED strip is disabled; enable SPI (dtparam=spi=on) and restart".to_string()
+    } else {
+        format!(
+            "waiting up to {} ms for it, then the LED strip is disabled; \
+             enable SPI (dtparam=spi=on) if it never appears",
+            device_wait_ms
+        )
+    };
+    Some(StartupIssue {
+        component: "LED strip",
+        problem: format!("{} does not exist", device_path),
+        impact,
+        fatal: false,
+    })
+}
+
+fn check_database(path: &Path) -> Option<StartupIssue> {
+    let err = ensure_writable(path).err()?;
+    Some(StartupIssue {
+        component: "Database",
+        problem: format!("{:#}", err),
+        impact: "the server can't start; check the file and directory permissions".to_string(),
+        fatal: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::AppState;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_missing_spi_device_still_serves() {
+        let config = SpiConfig::new(99, 99, 11);
+        let issue = check_led_strip(&config, 0).expect("a missing spidev node is reported");
+        assert_eq!(issue.component, "LED strip");
+        assert_eq!(issue.problem, "/dev/spidev99.99 does not exist");
+        assert!(issue.impact.starts_with("the LED strip is disabled"));
+        assert!(!issue.fatal);
+
+        // Degraded, but the HTTP API still comes up
+        let app_state = Arc::new(AppState::open_temp("startup-no-spi").await);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new().nest("/api", crate::api::routes::routes(app_state));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /api/metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        server.abort();
+    }
+
+    #[test]
+    fn test_unwritable_database_is_fatal() {
+        // A file where the parent directory should be can't be created past
+        let not_a_dir = std::env::temp_dir().join("emes-startup-not-a-dir");
+        std::fs::write(&not_a_dir, b"").unwrap();
+        let issue = check_database(&not_a_dir.join("data.db")).expect("the bad path is reported");
+        assert!(issue.fatal);
+        assert!(check_database(&std::env::temp_dir().join("emes-startup.db")).is_none());
+    }
+
+    #[test]
+    fn test_missing_audio_output_is_a_warning() {
+        assert!(check_audio_output(true).is_none());
+        assert!(!check_audio_output(false).unwrap().fatal);
+    }
+}
@@ -0,0 +1,156 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// How often and how quickly a supervised task is restarted after panicking.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn backoff(&self, restarts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(restarts))
+            .min(self.max_backoff)
+    }
+}
+
+/// Run the task built by `make_task` on `tracker`, spawning a fresh one each
+/// time it panics until the policy's restart budget is used up. A task that
+/// returns normally is not restarted.
+pub fn spawn_supervised<F, Fut>(
+    tracker: &TaskTracker,
+    name: &'static str,
+    policy: RestartPolicy,
+    shutdown_token: CancellationToken,
+    make_task: F,
+) where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tracker.spawn(async move {
+        let mut restarts = 0;
+        loop {
+            let err = match tokio::spawn(make_task()).await {
+                Ok(()) => return,
+                Err(err) => err,
+            };
+
+            if !err.is_panic() {
+                tracing::warn!(task = name, "Task was cancelled: {}", err);
+                return;
+            }
+            if restarts >= policy.max_restarts {
+                tracing::error!(
+                    task = name,
+                    restarts,
+                    "Task panicked and will not be restarted: {}",
+                    err
+                );
+                return;
+            }
+
+            let backoff = policy.backoff(restarts);
+            restarts += 1;
+            tracing::error!(
+                task = name,
+                restarts,
+                ?backoff,
+                "Task panicked, restarting: {}",
+                err
+            );
+
+            tokio::select! {
+                () = shutdown_token.cancelled() => return,
+                () = tokio::time::sleep(backoff) => {},
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast_policy(max_restarts: u32) -> RestartPolicy {
+        RestartPolicy {
+            max_restarts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicked_task_is_restarted() {
+        let tracker = TaskTracker::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = runs.clone();
+        spawn_supervised(
+            &tracker,
+            "flaky",
+            fast_policy(3),
+            CancellationToken::new(),
+            move || {
+                let runs = task_runs.clone();
+                async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first run fails");
+                    }
+                }
+            },
+        );
+
+        tracker.close();
+        tracker.wait().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_are_bounded() {
+        let tracker = TaskTracker::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = runs.clone();
+        spawn_supervised(
+            &tracker,
+            "broken",
+            fast_policy(2),
+            CancellationToken::new(),
+            move || {
+                let runs = task_runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    panic!("always fails");
+                }
+            },
+        );
+
+        tracker.close();
+        tracker.wait().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+    }
+}
@@ -1,19 +1,31 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::broadcast;
 
 #[derive(Clone)]
 
 pub struct LedStripState {
     event_chan: broadcast::Sender<String>,
+    initialized: Arc<AtomicBool>,
 }
 
 impl LedStripState {
     pub fn new() -> Self {
         Self {
             event_chan: broadcast::channel(100).0,
+            initialized: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub fn get_event_chan_sender(&self) -> broadcast::Sender<String> {
         self.event_chan.clone()
     }
+
+    pub fn set_initialized(&self, initialized: bool) {
+        self.initialized.store(initialized, Ordering::Relaxed);
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Relaxed)
+    }
 }
@@ -1,7 +1,11 @@
 mod led_strip_state;
 mod lib;
+mod playback_leds;
 mod strip_task;
 
 pub use led_strip_state::LedStripState;
-pub use lib::{Color, SpiConfig, Ws2812};
-pub use strip_task::{SetLedStripStatusEvent, Ws2812StripTask};
+#[cfg(test)]
+pub(crate) use lib::MockSpi;
+pub use lib::{Color, SpiConfig, StripChip, WhiteBalance, Ws2812, Ws2812Error};
+pub use playback_leds::follow_player;
+pub use strip_task::{SetLedStripStatusEvent, Ws2812StripTask, strip_config};
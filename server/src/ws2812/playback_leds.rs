@@ -0,0 +1,125 @@
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+
+use crate::app_state::AppState;
+use crate::config::PlaybackLedConfig;
+use crate::dao::player_led_dao::PlayerLedEntry;
+use crate::player::PlaybackState;
+use crate::ws2812::SetLedStripStatusEvent;
+
+/// The LED strip event showing `state` with the configured colors
+fn playback_event(state: PlaybackState, config: &PlaybackLedConfig) -> SetLedStripStatusEvent {
+    let color = match state {
+        PlaybackState::Playing => config.playing,
+        PlaybackState::Paused => config.paused,
+        PlaybackState::Stopped => config.stopped,
+    };
+    SetLedStripStatusEvent {
+        enable: color.is_some(),
+        status: color.map(|color| PlayerLedEntry {
+            id: 0,
+            frequency: color.frequency,
+            scale: color.scale,
+            red: color.red,
+            green: color.green,
+            blue: color.blue,
+        }),
+    }
+}
+
+/// Send an LED strip event each time the player starts, pauses or stops,
+/// until `shutdown_token` is cancelled. The state at startup is left to the
+/// strip's saved settings.
+pub async fn follow_player(
+    app_state: Arc<AppState>,
+    config: PlaybackLedConfig,
+    shutdown_token: CancellationToken,
+) {
+    let player = app_state.player_state.get_music_player();
+    let event_chan_sender = app_state.led_strip_state.get_event_chan_sender();
+    let mut state_changes = player.subscribe_playback_state();
+
+    loop {
+        let state = tokio::select! {
+            _ = shutdown_token.cancelled() => return,
+            state = state_changes.recv() => match state {
+                Ok(state) => state,
+                // Missed some changes, only the latest one matters
+                Err(RecvError::Lagged(_)) => player.playback_state(),
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        tracing::debug!(?state, "Playback state changed, updating the led strip");
+        let event_str = json!(playback_event(state, &config)).to_string();
+        let _ = event_chan_sender.send(event_str);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::wav_track;
+    use std::time::Duration;
+
+    async fn next_event(
+        receiver: &mut tokio::sync::broadcast::Receiver<String>,
+    ) -> SetLedStripStatusEvent {
+        let event = tokio::time::timeout(Duration::from_secs(2), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&event).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pause_sends_paused_color() {
+        let app_state = Arc::new(AppState::open_temp("playback-leds").await);
+        let player = app_state.player_state.get_music_player();
+        player.detach();
+        player.set_fade_duration(Duration::ZERO);
+        let mut receiver = app_state
+            .led_strip_state
+            .get_event_chan_sender()
+            .subscribe();
+
+        let config = PlaybackLedConfig::default();
+        let shutdown_token = CancellationToken::new();
+        let task = tokio::spawn(follow_player(
+            app_state.clone(),
+            config.clone(),
+            shutdown_token.clone(),
+        ));
+        // Let the task subscribe before the player changes state
+        tokio::task::yield_now().await;
+
+        player
+            .play(&vec![wav_track("playback-leds", 5)], 0)
+            .unwrap();
+        let playing = next_event(&mut receiver).await;
+        assert!(playing.enable);
+        assert_eq!(playing.status.unwrap().green, 255);
+
+        assert!(player.toggle().unwrap());
+        let paused = next_event(&mut receiver).await;
+        let expected = config.paused.unwrap();
+        let status = paused.status.unwrap();
+        assert!(paused.enable);
+        assert_eq!(
+            (status.red, status.green, status.blue, status.frequency),
+            (
+                expected.red,
+                expected.green,
+                expected.blue,
+                expected.frequency
+            )
+        );
+
+        player.stop().unwrap();
+        assert!(!next_event(&mut receiver).await.enable);
+        shutdown_token.cancel();
+        task.await.unwrap();
+    }
+}
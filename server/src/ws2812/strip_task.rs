@@ -1,30 +1,60 @@
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tokio_util::sync::CancellationToken;
 
 use crate::app_state::AppState;
+use crate::config::{BootAnimationConfig, BootAnimationKind, LedStripConfig, WhiteBalancePreset};
 use crate::dao::player_led_dao;
-use crate::ws2812::{Color, SpiConfig, Ws2812};
+use crate::ws2812::{Color, SpiConfig, WhiteBalance, Ws2812, Ws2812Error};
+
+// Events this close together are applied once, with the newest settings
+const EVENT_COALESCE_WINDOW: Duration = Duration::from_millis(50);
+// How often to look for the spidev node while waiting for it
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The strip described by the `led_strip` config, failing on a device path
+/// that isn't a spidev node
+pub fn strip_config(led_strip: &LedStripConfig) -> Result<SpiConfig, Ws2812Error> {
+    let mut config = SpiConfig::from_device_path(&led_strip.device, led_strip.num_leds)?
+        .with_chip(led_strip.chip);
+    config.white_balance = match led_strip.white_balance {
+        WhiteBalancePreset::Uncorrected => WhiteBalance::UNCORRECTED,
+        WhiteBalancePreset::Neutral => WhiteBalance::NEUTRAL,
+        WhiteBalancePreset::Warm => WhiteBalance::WARM,
+    };
+    Ok(config)
+}
 
 struct Inner {
     strip: Ws2812,
 }
 
 impl Inner {
-    pub fn new() -> Self {
-        let config = SpiConfig::new(0, 1, 11);
+    /// Open the strip, waiting up to `device_wait_ms` for its spidev node,
+    /// which can show up a few seconds after boot
+    async fn open(led_strip: &LedStripConfig) -> Result<Self, Ws2812Error> {
+        let device_wait = Duration::from_millis(led_strip.device_wait_ms);
+        let config = strip_config(led_strip)?;
+        let device_path = config.device_path();
+        if !wait_for_device(Path::new(&device_path), device_wait).await {
+            return Err(Ws2812Error::SpiDeviceNotFound(device_path));
+        }
 
-        let strip = match Ws2812::new(config) {
-            Ok(strip) => strip,
-            Err(e) => {
-                // panic!("Error creating WS2812 strip: {}", e);
-                // tracing::error!("Error creating WS2812 strip: {}", e);
-                panic!("Error creating WS2812 strip: {}", e);
-            }
-        };
-        Self { strip }
+        let strip = Ws2812::new(config)?;
+        Ok(Self { strip })
+    }
+
+    /// Stop any animation and blank the physical strip
+    fn shutdown(&mut self) {
+        self.strip.stop_animation();
+        if let Err(e) = self.strip.clear() {
+            tracing::error!("Failed to clear led strip on shutdown: {}", e);
+        }
     }
 }
 
@@ -34,8 +64,14 @@ pub struct Ws2812StripTask {
 }
 
 impl Ws2812StripTask {
-    pub fn new(app_state: Arc<AppState>) -> Self {
-        let inner = Arc::new(RwLock::new(Inner::new()));
+    pub async fn open(app_state: Arc<AppState>) -> Result<Self, Ws2812Error> {
+        let inner = Inner::open(&app_state.config.led_strip).await?;
+        Ok(Self::with_strip(app_state, inner.strip))
+    }
+
+    pub(crate) fn with_strip(app_state: Arc<AppState>, strip: Ws2812) -> Self {
+        let inner = Arc::new(RwLock::new(Inner { strip }));
+        app_state.led_strip_state.set_initialized(true);
         Self { app_state, inner }
     }
 
@@ -43,13 +79,25 @@ impl Ws2812StripTask {
         let event_chan_sender = self.app_state.led_strip_state.get_event_chan_sender();
         let mut event_chan_receiver = event_chan_sender.subscribe();
 
-        // self.init_strip().await;
+        // The saved settings are applied once the boot animation is over, or
+        // right away without one. An event arriving before then replaces both.
+        let mut boot_deadline = self
+            .app_state
+            .config
+            .led_strip
+            .boot_animation
+            .and_then(|boot_animation| self.start_boot_animation(boot_animation));
+        if boot_deadline.is_none() {
+            self.init_strip().await;
+        }
 
         while !shutdown_token.is_cancelled() {
             tokio::select! {
                 event = event_chan_receiver.recv() => {
                     match event {
                         Ok(event) => {
+                            boot_deadline = None;
+                            let event = coalesce_events(&mut event_chan_receiver, event).await;
                             tracing::info!("Received event from led strip: {}", event);
                             self.handle_event(&event).await;
                         }
@@ -61,23 +109,53 @@ impl Ws2812StripTask {
                 _ = shutdown_token.cancelled() => {
                     tracing::info!("Shutting down led strip task");
                 },
+                _ = tokio::time::sleep_until(
+                    boot_deadline.unwrap_or_else(tokio::time::Instant::now)
+                ), if boot_deadline.is_some() => {
+                    boot_deadline = None;
+                    self.inner.write().unwrap().strip.stop_animation();
+                    self.init_strip().await;
+                },
                 _ = tokio::time::sleep(Duration::from_millis(33)) => {
                     let mut inner = self.inner.write().unwrap();
                     inner.strip.show().unwrap();
                 },
             }
         }
+
+        // Blank the strip now rather than whenever `Inner` happens to drop
+        self.inner.write().unwrap().shutdown();
+    }
+
+    /// Start the boot animation, returning when it should end
+    fn start_boot_animation(
+        &self,
+        boot_animation: BootAnimationConfig,
+    ) -> Option<tokio::time::Instant> {
+        let mut inner = self.inner.write().unwrap();
+        let started = match boot_animation.kind {
+            BootAnimationKind::Chase => inner.strip.start_chase(Color::white(), 1.0, true),
+            BootAnimationKind::Breathe => inner.strip.start_breathe(Color::white(), 1.0),
+        };
+        if let Err(e) = started {
+            tracing::error!("Failed to start the boot animation: {}", e);
+            return None;
+        }
+        Some(tokio::time::Instant::now() + Duration::from_millis(boot_animation.duration_ms))
     }
 
     async fn init_strip(&self) {
-        let player_led_dao = player_led_dao::PlayerLedDao::new(&self.app_state.db_state).await;
+        let Ok(player_led_dao) = player_led_dao::PlayerLedDao::new(&self.app_state.db_state).await
+        else {
+            return;
+        };
         let led_strip = player_led_dao.get_led_strip_status().await;
         if led_strip.is_err() {
             return;
         }
 
         let led_strip = led_strip.unwrap();
-        let led_color = Color::new(led_strip.red, led_strip.green, led_strip.blue);
+        let led_color = Color::from(led_strip);
         let led_scale = led_strip.scale;
         let led_frequency = led_strip.frequency;
 
@@ -93,6 +171,7 @@ impl Ws2812StripTask {
     }
 
     async fn handle_event(&self, event_str: &str) {
+        self.app_state.metrics.record_led_event();
         let event = match serde_json::from_str::<SetLedStripStatusEvent>(event_str) {
             Ok(event) => event,
             Err(e) => {
@@ -109,11 +188,17 @@ impl Ws2812StripTask {
         }
 
         let led_strip = event.status.unwrap();
-        let led_color = Color::new(led_strip.red, led_strip.green, led_strip.blue);
+        let led_color = Color::from(led_strip);
         let led_scale = led_strip.scale;
         let led_frequency = led_strip.frequency;
 
         let mut inner = self.inner.write().unwrap();
+        // No frequency means a steady color rather than a breathe
+        if led_frequency <= 0.0 {
+            inner.strip.stop_animation();
+            inner.strip.fill(led_color.scale(led_scale as f32)).unwrap();
+            return;
+        }
         inner
             .strip
             .set_leds(&[led_color.scale(led_scale as f32)])
@@ -125,8 +210,251 @@ impl Ws2812StripTask {
     }
 }
 
+/// Poll until `path` exists, false if it still doesn't after `timeout`
+async fn wait_for_device(path: &Path, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if path.exists() {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(DEVICE_POLL_INTERVAL).await;
+    }
+}
+
+/// Keep receiving for a short window after `latest` and return the newest
+/// event, so dragging a color picker restarts the animation once rather than
+/// for every step
+async fn coalesce_events(receiver: &mut broadcast::Receiver<String>, mut latest: String) -> String {
+    let deadline = tokio::time::Instant::now() + EVENT_COALESCE_WINDOW;
+    loop {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Ok(event)) => latest = event,
+            Ok(Err(RecvError::Lagged(_))) => continue,
+            Ok(Err(RecvError::Closed)) | Err(_) => return latest,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SetLedStripStatusEvent {
     pub enable: bool,
     pub status: Option<player_led_dao::PlayerLedEntry>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws2812::{MockSpi, StripChip};
+    use std::sync::Mutex;
+
+    /// A strip on a mock bus whose latest sent frame is kept in the returned slot
+    fn recorded_strip() -> (Ws2812, Arc<Mutex<Vec<Color>>>) {
+        let mut strip =
+            Ws2812::with_transport(SpiConfig::new(0, 1, 11), Box::new(MockSpi::default()));
+        let last_frame = Arc::new(Mutex::new(Vec::new()));
+        let frames = last_frame.clone();
+        strip.set_frame_callback(move |colors| *frames.lock().unwrap() = colors.to_vec());
+        (strip, last_frame)
+    }
+
+    #[test]
+    fn test_strip_config_uses_white_balance_preset() {
+        let led_strip = LedStripConfig::default();
+        assert_eq!(
+            strip_config(&led_strip).unwrap().white_balance,
+            WhiteBalance::UNCORRECTED
+        );
+
+        let led_strip: LedStripConfig =
+            serde_json::from_str(r#"{ "white_balance": "warm" }"#).unwrap();
+        assert_eq!(
+            strip_config(&led_strip).unwrap().white_balance,
+            WhiteBalance::WARM
+        );
+    }
+
+    #[test]
+    fn test_strip_config_from_led_strip_config() {
+        // The defaults describe the strip wired to the board
+        assert_eq!(
+            strip_config(&LedStripConfig::default()).unwrap(),
+            SpiConfig::new(0, 1, 11)
+        );
+
+        let led_strip: LedStripConfig = serde_json::from_str(
+            r#"{ "device": "/dev/spidev1.0", "num_leds": 30, "chip": "sk6812" }"#,
+        )
+        .unwrap();
+        let config = strip_config(&led_strip).unwrap();
+        assert_eq!((config.bus, config.cs, config.num_leds), (1, 0, 30));
+        assert_eq!(config.chip, StripChip::Sk6812);
+
+        let led_strip: LedStripConfig =
+            serde_json::from_str(r#"{ "device": "/dev/ttyS0" }"#).unwrap();
+        assert!(matches!(
+            strip_config(&led_strip),
+            Err(Ws2812Error::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_late_device_node() {
+        let path = std::env::temp_dir().join(format!("emes-spidev-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let node = path.clone();
+        let create = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            std::fs::write(node, b"").unwrap();
+        });
+
+        assert!(wait_for_device(&path, Duration::from_secs(5)).await);
+        create.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_missing_device_node() {
+        let path = std::env::temp_dir().join("emes-spidev-never-appears");
+        assert!(!wait_for_device(&path, Duration::from_millis(250)).await);
+    }
+
+    #[tokio::test]
+    async fn test_rapid_events_are_coalesced() {
+        let (sender, mut receiver) = broadcast::channel(100);
+        let flood = tokio::spawn(async move {
+            for i in 0..20 {
+                sender.send(format!("event {}", i)).unwrap();
+                if i % 4 == 0 {
+                    tokio::time::sleep(Duration::from_millis(2)).await;
+                }
+            }
+            sender
+        });
+
+        let first = receiver.recv().await.unwrap();
+        let latest = coalesce_events(&mut receiver, first).await;
+        let _sender = flood.await.unwrap();
+
+        // One event to apply, so the breathe animation restarts once
+        assert_eq!(latest, "event 19");
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_boot_animation_then_saved_state() {
+        let mut app_state = AppState::open_temp("strip-task-boot").await;
+        app_state.config.led_strip.boot_animation = Some(BootAnimationConfig {
+            kind: BootAnimationKind::Chase,
+            duration_ms: 200,
+        });
+        player_led_dao::PlayerLedDao::new(&app_state.db_state)
+            .await
+            .unwrap()
+            .set_led_strip_status(player_led_dao::PlayerLedEntry {
+                id: 1,
+                frequency: 0.5,
+                scale: 1.0,
+                red: 0,
+                green: 0,
+                blue: 255,
+            })
+            .await
+            .unwrap();
+        let (strip, last_frame) = recorded_strip();
+        let task = Arc::new(Ws2812StripTask::with_strip(Arc::new(app_state), strip));
+
+        let shutdown_token = CancellationToken::new();
+        let running = tokio::spawn({
+            let task = task.clone();
+            let shutdown_token = shutdown_token.clone();
+            async move { task.run(shutdown_token).await }
+        });
+
+        // The white chase lights one LED, the saved blue breathe all of them alike
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(task.inner.read().unwrap().strip.is_animating());
+        {
+            let frame = last_frame.lock().unwrap();
+            assert_eq!(frame.iter().filter(|color| color.r > 0).count(), 1);
+        }
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(task.inner.read().unwrap().strip.is_animating());
+        {
+            let frame = last_frame.lock().unwrap();
+            assert!(frame.iter().all(|color| *color == frame[0]), "{:?}", frame);
+            assert_eq!((frame[0].r, frame[0].g), (0, 0));
+        }
+
+        shutdown_token.cancel();
+        running.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_saved_state_without_boot_animation() {
+        let app_state = AppState::open_temp("strip-task-no-boot").await;
+        assert!(app_state.config.led_strip.boot_animation.is_none());
+        player_led_dao::PlayerLedDao::new(&app_state.db_state)
+            .await
+            .unwrap()
+            .set_led_strip_status(player_led_dao::PlayerLedEntry {
+                id: 1,
+                frequency: 0.5,
+                scale: 1.0,
+                red: 0,
+                green: 255,
+                blue: 0,
+            })
+            .await
+            .unwrap();
+        let (strip, last_frame) = recorded_strip();
+        let task = Arc::new(Ws2812StripTask::with_strip(Arc::new(app_state), strip));
+
+        let shutdown_token = CancellationToken::new();
+        let running = tokio::spawn({
+            let task = task.clone();
+            let shutdown_token = shutdown_token.clone();
+            async move { task.run(shutdown_token).await }
+        });
+
+        // The saved green breathe starts right away
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(task.inner.read().unwrap().strip.is_animating());
+        {
+            let frame = last_frame.lock().unwrap();
+            assert!(frame.iter().all(|color| *color == frame[0]), "{:?}", frame);
+            assert_eq!((frame[0].r, frame[0].b), (0, 0));
+            assert!(frame[0].g > 0, "{:?}", frame);
+        }
+
+        shutdown_token.cancel();
+        running.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_clears_strip() {
+        let app_state = Arc::new(AppState::open_temp("strip-task-shutdown").await);
+        let config = SpiConfig::new(0, 1, 11);
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(config.clone(), Box::new(spi.clone()));
+        strip.fill(Color::red()).unwrap();
+        strip.show().unwrap();
+        let task = Ws2812StripTask::with_strip(app_state, strip);
+
+        let shutdown_token = CancellationToken::new();
+        shutdown_token.cancel();
+        task.run(shutdown_token).await;
+
+        let blank_spi = MockSpi::default();
+        Ws2812::with_transport(config, Box::new(blank_spi.clone()))
+            .show()
+            .unwrap();
+        assert_eq!(spi.last_frame(), blank_spi.last_frame());
+        assert!(!task.inner.read().unwrap().strip.is_animating());
+    }
+}
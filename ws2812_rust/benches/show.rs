@@ -0,0 +1,38 @@
+//! Frame encoding throughput of `Ws2812::show()`
+//!
+//! Frames go to a transport that discards them, so only the encoding is measured.
+//! Run with `cargo bench --bench show`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use ws2812_rust::{Color, SpiConfig, SpiTransport, Ws2812};
+
+/// Transport that drops every frame
+struct NullSpi;
+
+impl SpiTransport for NullSpi {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        black_box(data);
+        Ok(())
+    }
+}
+
+fn bench_show(c: &mut Criterion) {
+    let mut group = c.benchmark_group("show");
+    for num_leds in [30, 100, 300, 1000] {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, num_leds), Box::new(NullSpi));
+        let colors: Vec<Color> = (0..num_leds)
+            .map(|i| Color::new(i as u8, (i * 7) as u8, (i * 13) as u8))
+            .collect();
+        strip.set_leds(&colors).unwrap();
+
+        group.throughput(Throughput::Elements(num_leds as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(num_leds), &num_leds, |b, _| {
+            b.iter(|| strip.show().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_show);
+criterion_main!(benches);
@@ -8,6 +8,7 @@
 //!
 //! - SPI-based communication for reliable timing
 //! - RGB and GRB color format support
+//! - WS2812B, WS2815 and SK6812 bit timings
 //! - Built-in animation patterns (breathe, chase)
 //! - Thread-safe animation control
 //! - Configurable LED count and timing
@@ -26,10 +27,12 @@
 //! strip.show()?;
 //! ```
 
+use serde::{Deserialize, Serialize};
 use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
@@ -48,8 +51,8 @@ pub enum Ws2812Error {
     AnimationError(String),
 }
 
-/// RGB Color representation
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// RGB Color representation, black by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -97,19 +100,232 @@ impl Color {
         }
     }
 
+    /// Check whether every channel is zero (the LED is off)
+    pub fn is_black(&self) -> bool {
+        self.r == 0 && self.g == 0 && self.b == 0
+    }
+
+    /// Estimated draw of an LED showing this color, see [`MA_PER_CHANNEL`]
+    pub fn current_ma(&self) -> f32 {
+        (self.r as f32 + self.g as f32 + self.b as f32) / 255.0 * MA_PER_CHANNEL
+    }
+
+    /// Perceived brightness from 0.0 to 1.0, using Rec. 709 weights
+    pub fn luminance(&self) -> f32 {
+        (0.2126 * self.r as f32 + 0.7152 * self.g as f32 + 0.0722 * self.b as f32) / 255.0
+    }
+
+    /// Composite this color over `background` with opacity `alpha` (0.0 to 1.0)
+    pub fn blend_over(&self, background: Color, alpha: f32) -> Self {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let channel = |fg: u8, bg: u8| {
+            (fg as f32 * alpha + bg as f32 * (1.0 - alpha)).round().clamp(0.0, 255.0) as u8
+        };
+        Self {
+            r: channel(self.r, background.r),
+            g: channel(self.g, background.g),
+            b: channel(self.b, background.b),
+        }
+    }
+
+    /// Move each channel toward `target` by at most `step`, without overshooting
+    pub fn approach(&self, target: Color, step: u8) -> Self {
+        let channel = |from: u8, to: u8| {
+            if from < to {
+                from.saturating_add(step).min(to)
+            } else {
+                from.saturating_sub(step).max(to)
+            }
+        };
+        Self {
+            r: channel(self.r, target.r),
+            g: channel(self.g, target.g),
+            b: channel(self.b, target.b),
+        }
+    }
+
     /// Convert RGB to GRB format (WS2812 order)
     pub fn to_grb(&self) -> [u8; 3] {
         [self.g, self.r, self.b]
     }
+
+    /// Convert to the byte order a strip expects on the wire
+    pub fn to_order(&self, order: ColorOrder) -> [u8; 3] {
+        match order {
+            ColorOrder::Grb => self.to_grb(),
+            ColorOrder::Rgb => [self.r, self.g, self.b],
+        }
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Self::new(r, g, b)
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self::new(r, g, b)
+    }
+}
+
+impl From<Color> for [u8; 3] {
+    /// RGB order; use [`Color::to_order`] for the wire order
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b]
+    }
+}
+
+/// Order in which a strip reads the three color bytes of each LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorOrder {
+    Grb,
+    Rgb,
+}
+
+/// LED controller on the strip, which decides the bit timing on the wire.
+///
+/// Patterns are SPI bytes sent at 6.5MHz, so each SPI bit lasts about 154ns:
+///
+/// | Chip      | "0" high | "1" high | Reset   | Order |
+/// |-----------|----------|----------|---------|-------|
+/// | WS2812B   | ~308ns   | ~923ns   | ~52us   | GRB   |
+/// | WS2815    | ~308ns   | ~1077ns  | ~283us  | GRB   |
+/// | SK6812    | ~308ns   | ~615ns   | ~86us   | GRB   |
+///
+/// The WS2815 is a 12V part with a longer "1" window and needs a reset of at
+/// least 280us; the SK6812 wants a shorter "1" pulse and at least 80us of reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StripChip {
+    #[default]
+    Ws2812b,
+    Ws2815,
+    Sk6812,
+}
+
+const WS2812B_SPI_BITS: [[u8; 8]; 256] = spi_bit_table(StripChip::Ws2812b.led_zero(), StripChip::Ws2812b.led_one());
+const WS2815_SPI_BITS: [[u8; 8]; 256] = spi_bit_table(StripChip::Ws2815.led_zero(), StripChip::Ws2815.led_one());
+const SK6812_SPI_BITS: [[u8; 8]; 256] = spi_bit_table(StripChip::Sk6812.led_zero(), StripChip::Sk6812.led_one());
+
+impl StripChip {
+    /// SPI byte that encodes a "0" bit
+    pub const fn led_zero(self) -> u8 {
+        match self {
+            StripChip::Ws2812b | StripChip::Ws2815 | StripChip::Sk6812 => 0b1100_0000,
+        }
+    }
+
+    /// SPI byte that encodes a "1" bit
+    pub const fn led_one(self) -> u8 {
+        match self {
+            StripChip::Ws2812b => 0b1111_1100,
+            StripChip::Ws2815 => 0b1111_1110,
+            StripChip::Sk6812 => 0b1111_0000,
+        }
+    }
+
+    /// Number of zero bytes sent before each frame to latch the previous one
+    pub const fn reset_bytes(self) -> usize {
+        match self {
+            StripChip::Ws2812b => 42,
+            StripChip::Ws2815 => 230,
+            StripChip::Sk6812 => 70,
+        }
+    }
+
+    /// Color byte order the chip uses unless the strip is wired otherwise
+    pub const fn default_color_order(self) -> ColorOrder {
+        match self {
+            StripChip::Ws2812b | StripChip::Ws2815 | StripChip::Sk6812 => ColorOrder::Grb,
+        }
+    }
+
+    /// Lookup table from color byte to SPI bytes
+    fn spi_bits(self) -> &'static [[u8; 8]; 256] {
+        match self {
+            StripChip::Ws2812b => &WS2812B_SPI_BITS,
+            StripChip::Ws2815 => &WS2815_SPI_BITS,
+            StripChip::Sk6812 => &SK6812_SPI_BITS,
+        }
+    }
+}
+
+/// SPI clock polarity and phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpiMode {
+    #[default]
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+}
+
+impl SpiMode {
+    fn flags(self) -> SpiModeFlags {
+        match self {
+            SpiMode::Mode0 => SpiModeFlags::SPI_MODE_0,
+            SpiMode::Mode1 => SpiModeFlags::SPI_MODE_1,
+            SpiMode::Mode2 => SpiModeFlags::SPI_MODE_2,
+            SpiMode::Mode3 => SpiModeFlags::SPI_MODE_3,
+        }
+    }
+}
+
+/// Per-channel output scalers (0 to 255) correcting the tint of the strip's white
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhiteBalance {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl WhiteBalance {
+    /// Colors are sent as they are
+    pub const UNCORRECTED: Self = Self::new(255, 255, 255);
+    /// Tones down the green and blue cast of a typical WS2812 white
+    pub const NEUTRAL: Self = Self::new(255, 176, 240);
+    /// A yellower white, closer to an incandescent bulb
+    pub const WARM: Self = Self::new(255, 150, 100);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Scale each channel of `color` by its scaler
+    pub fn apply(&self, color: Color) -> Color {
+        let channel = |value: u8, scaler: u8| ((value as u16 * scaler as u16 + 127) / 255) as u8;
+        Color {
+            r: channel(color.r, self.r),
+            g: channel(color.g, self.g),
+            b: channel(color.b, self.b),
+        }
+    }
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        Self::UNCORRECTED
+    }
 }
 
 /// SPI Configuration for WS2812
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SpiConfig {
     pub bus: u8,
     pub cs: u8,
     pub num_leds: usize,
     pub max_speed_hz: u32,
+    pub chip: StripChip,
+    pub color_order: ColorOrder,
+    pub mode: SpiMode,
+    pub lsb_first: bool,
+    /// Must stay 8, the frame encoding produces one SPI byte per LED bit
+    pub bits_per_word: u8,
+    pub white_balance: WhiteBalance,
 }
 
 impl SpiConfig {
@@ -120,13 +336,169 @@ impl SpiConfig {
             cs,
             num_leds,
             max_speed_hz: 6_500_000, // 6.5MHz as in Python version
+            chip: StripChip::Ws2812b,
+            color_order: StripChip::Ws2812b.default_color_order(),
+            mode: SpiMode::Mode0,
+            lsb_first: false,
+            bits_per_word: 8,
+            white_balance: WhiteBalance::UNCORRECTED,
         }
     }
 
+    /// Create a configuration from a spidev path such as `/dev/spidev1.0`
+    pub fn from_device_path(path: &str, num_leds: usize) -> Result<Self, Ws2812Error> {
+        let invalid = || Ws2812Error::ConfigError(format!("Not a spidev device path: {}", path));
+        let (bus, cs) = path
+            .strip_prefix("/dev/spidev")
+            .and_then(|rest| rest.split_once('.'))
+            .ok_or_else(invalid)?;
+        let bus = bus.parse::<u8>().map_err(|_| invalid())?;
+        let cs = cs.parse::<u8>().map_err(|_| invalid())?;
+        Ok(Self::new(bus, cs, num_leds))
+    }
+
+    /// Drive a different LED chip, switching to its default color order
+    pub fn with_chip(mut self, chip: StripChip) -> Self {
+        self.chip = chip;
+        self.color_order = chip.default_color_order();
+        self
+    }
+
     /// Get the spidev device path
     pub fn device_path(&self) -> String {
         format!("/dev/spidev{}.{}", self.bus, self.cs)
     }
+
+    /// Build the spidev options, rejecting word sizes the encoding can't use
+    pub fn spidev_options(&self) -> Result<SpidevOptions, Ws2812Error> {
+        if self.bits_per_word != 8 {
+            return Err(Ws2812Error::ConfigError(format!(
+                "bits_per_word must be 8, got {}",
+                self.bits_per_word
+            )));
+        }
+
+        Ok(SpidevOptions::new()
+            .max_speed_hz(self.max_speed_hz)
+            .mode(self.mode.flags())
+            .lsb_first(self.lsb_first)
+            .bits_per_word(self.bits_per_word)
+            .build())
+    }
+}
+
+/// Destination for encoded frames.
+///
+/// Implemented for [`Spidev`]; tests and simulators can supply their own
+/// transport through [`Ws2812::with_transport`].
+pub trait SpiTransport: Send + Sync {
+    /// Transmit one encoded frame, reset bytes included
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()>;
+}
+
+impl SpiTransport for Spidev {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut transfer = SpidevTransfer::write(data);
+        self.transfer(&mut transfer)
+    }
+}
+
+/// Transport that records every frame instead of driving hardware
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockSpi {
+    pub frames: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl MockSpi {
+    /// The most recently transmitted frame, if any
+    pub fn last_frame(&self) -> Option<Vec<u8>> {
+        self.frames.lock().unwrap().last().cloned()
+    }
+}
+
+#[cfg(test)]
+impl SpiTransport for MockSpi {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.frames.lock().unwrap().push(data.to_vec());
+        Ok(())
+    }
+}
+
+/// SPI bit patterns for every byte value, MSB first, so encoding a frame is a
+/// table lookup per color byte instead of a branch per bit
+const fn spi_bit_table(zero: u8, one: u8) -> [[u8; 8]; 256] {
+    let mut table = [[0u8; 8]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            table[byte][bit] = if (byte >> (7 - bit)) & 1 == 1 { one } else { zero };
+            bit += 1;
+        }
+        byte += 1;
+    }
+    table
+}
+
+/// Scale one channel by the strip brightness. With `error` the fraction lost
+/// to rounding down is carried to the next frame, so over time the output
+/// averages the exact value instead of stepping between whole levels.
+fn scale_channel(byte: u8, brightness: f32, error: Option<&mut f32>) -> u8 {
+    let exact = byte as f32 * brightness;
+    match error {
+        Some(error) => {
+            let target = exact + *error;
+            let out = target.floor().clamp(0.0, 255.0);
+            *error = target - out;
+            out as u8
+        }
+        None => exact as u8,
+    }
+}
+
+/// Current drawn by one fully lit color channel of an LED, in milliamps
+pub const MA_PER_CHANNEL: f32 = 20.0;
+
+/// Animation frame rate unless changed with [`Ws2812::set_fps`]
+pub const DEFAULT_FPS: f32 = 30.0;
+
+/// Fewest frames a breathe cycle may have at [`DEFAULT_FPS`], scaled with the
+/// frame rate so the fastest allowed breathe is the same at any FPS
+const MIN_BREATHE_FRAMES: f32 = 6.0;
+
+/// Frames in one breathe cycle at `fps`
+fn breathe_frames(fps: f32, hz: f32) -> Result<usize, Ws2812Error> {
+    let frames = (fps / hz) as usize;
+    let min_frames = (MIN_BREATHE_FRAMES * fps / DEFAULT_FPS).ceil() as usize;
+    if frames < min_frames {
+        return Err(Ws2812Error::AnimationError(format!(
+            "Frequency too high, minimum {} frames required",
+            min_frames
+        )));
+    }
+    Ok(frames)
+}
+
+/// Frames each LED stays lit during a chase, at least one so a chase faster
+/// than the frame rate still visits every LED
+fn chase_frames_per_led(fps: f32, hz: f32, num_leds: usize) -> usize {
+    let frames = (fps / hz) as usize;
+    frames.div_ceil(num_leds).max(1)
+}
+
+/// Strip contents for one chase frame: a single lit LED, the rest black
+fn chase_frame(color: Color, frame: usize, frames_per_led: usize, num_leds: usize, clockwise: bool) -> Vec<Color> {
+    let mut colors = vec![Color::black(); num_leds];
+    let led_index = (frame / frames_per_led) % num_leds;
+    let actual_index = if clockwise {
+        (num_leds - 1) - led_index
+    } else {
+        led_index
+    };
+    colors[actual_index] = color;
+    colors
 }
 
 /// Animation control structure
@@ -134,26 +506,41 @@ impl SpiConfig {
 struct AnimationControl {
     running: Arc<RwLock<bool>>,
     handle: Option<JoinHandle<()>>,
+    /// Bits of the current f32 intensity, for animations that have one
+    intensity: Option<Arc<AtomicU32>>,
 }
 
+/// Observer called with the colors of every frame before it is sent
+pub type FrameCallback = Box<dyn FnMut(&[Color]) + Send + Sync>;
+
 /// Main WS2812 driver structure
+///
+/// A strip configured with zero LEDs is valid: `fill`, `set_leds`, `rotate`
+/// and `show` do nothing, while `set_led`/`get_led` and starting an animation
+/// return `ConfigError`.
 pub struct Ws2812 {
-    spi: Spidev,
+    spi: Box<dyn SpiTransport>,
     config: SpiConfig,
     led_buffer: Arc<Mutex<Vec<Color>>>,
     tx_buffer: Vec<u8>,
+    spi_bits: &'static [[u8; 8]; 256],
+    on_frame: Option<FrameCallback>,
+    brightness: f32,
+    dithering_enabled: bool,
+    /// Rounding error carried per channel, in wire order, while dithering
+    dither_error: Vec<f32>,
+    max_current_ma: Option<u32>,
+    /// Factor the last frame was scaled by to stay under `max_current_ma`
+    power_scale: f32,
+    fps: f32,
     animation: Option<AnimationControl>,
 }
 
 impl Ws2812 {
-    // WS2812 timing constants (as in Python version)
-    const LED_ZERO: u8 = 0b1100_0000; // WS2812 "0" bit pattern
-    const LED_ONE: u8 = 0b1111_1100;  // WS2812 "1" bit pattern
-    const RESET_BYTES_COUNT: usize = 42; // Reset signal length
-
     /// Create a new WS2812 driver instance
     pub fn new(config: SpiConfig) -> Result<Self, Ws2812Error> {
         let device_path = config.device_path();
+        let options = config.spidev_options()?;
         
         // Check if SPI device exists
         if !Path::new(&device_path).exists() {
@@ -162,35 +549,40 @@ impl Ws2812 {
 
         // Open and configure SPI device
         let mut spi = Spidev::open(&device_path)?;
-        let options = SpidevOptions::new()
-            .max_speed_hz(config.max_speed_hz)
-            .mode(SpiModeFlags::SPI_MODE_0)
-            .lsb_first(false)
-            .bits_per_word(8)
-            .build();
         spi.configure(&options)?;
 
+        Ok(Self::with_transport(config, Box::new(spi)))
+    }
+
+    /// Create a driver that sends frames through `transport` instead of
+    /// opening a spidev device
+    pub fn with_transport(config: SpiConfig, transport: Box<dyn SpiTransport>) -> Self {
         // Initialize buffers
         let led_buffer = Arc::new(Mutex::new(vec![Color::black(); config.num_leds]));
-        let tx_buffer = vec![0u8; Self::RESET_BYTES_COUNT + config.num_leds * 24];
+        let tx_buffer = vec![0u8; config.chip.reset_bytes() + config.num_leds * 24];
+        let spi_bits = config.chip.spi_bits();
 
-        Ok(Self {
-            spi,
+        Self {
+            spi: transport,
             config,
             led_buffer,
             tx_buffer,
+            spi_bits,
+            on_frame: None,
+            brightness: 1.0,
+            dithering_enabled: false,
+            dither_error: Vec::new(),
+            max_current_ma: None,
+            power_scale: 1.0,
+            fps: DEFAULT_FPS,
             animation: None,
-        })
+        }
     }
 
     /// Set a single LED color
     pub fn set_led(&mut self, index: usize, color: Color) -> Result<(), Ws2812Error> {
         if index >= self.config.num_leds {
-            return Err(Ws2812Error::ConfigError(format!(
-                "LED index {} out of range (0-{})",
-                index,
-                self.config.num_leds - 1
-            )));
+            return Err(self.index_error(index));
         }
 
         let mut buffer = self.led_buffer.lock().unwrap();
@@ -198,14 +590,17 @@ impl Ws2812 {
         Ok(())
     }
 
+    fn index_error(&self, index: usize) -> Ws2812Error {
+        if self.is_empty() {
+            return Ws2812Error::ConfigError(format!("LED index {} out of range, the strip has no LEDs", index));
+        }
+        Ws2812Error::ConfigError(format!("LED index {} out of range (0-{})", index, self.config.num_leds - 1))
+    }
+
     /// Get a LED color
     pub fn get_led(&self, index: usize) -> Result<Color, Ws2812Error> {
         if index >= self.config.num_leds {
-            return Err(Ws2812Error::ConfigError(format!(
-                "LED index {} out of range (0-{})",
-                index,
-                self.config.num_leds - 1
-            )));
+            return Err(self.index_error(index));
         }
 
         let buffer = self.led_buffer.lock().unwrap();
@@ -233,48 +628,184 @@ impl Ws2812 {
         Ok(())
     }
 
+    /// Copy of the LED buffer, e.g. to restore it once an animation stops
+    pub fn save_frame(&self) -> Vec<Color> {
+        self.led_buffer.lock().unwrap().clone()
+    }
+
+    /// Put back a frame taken with [`Ws2812::save_frame`]; call `show` to send it
+    pub fn load_frame(&mut self, frame: &[Color]) -> Result<(), Ws2812Error> {
+        self.set_leds(frame)
+    }
+
+    /// Shift the LED buffer by `steps` with wrap-around, positive toward higher
+    /// indices; call `show` after each step to scroll a pattern
+    pub fn rotate(&mut self, steps: isize) {
+        let mut buffer = self.led_buffer.lock().unwrap();
+        if buffer.is_empty() {
+            return;
+        }
+        let shift = steps.rem_euclid(buffer.len() as isize) as usize;
+        buffer.rotate_right(shift);
+    }
+
     /// Clear all LEDs (turn them off)
     pub fn clear(&mut self) -> Result<(), Ws2812Error> {
         self.fill(Color::black())?;
         self.show()
     }
 
-    /// Convert 8-bit value to WS2812 SPI bits
-    fn byte_to_spi_bits(&self, byte: u8) -> [u8; 8] {
-        let mut bits = [0u8; 8];
-        for i in 0..8 {
-            bits[i] = if (byte >> (7 - i)) & 1 == 1 {
-                Self::LED_ONE
-            } else {
-                Self::LED_ZERO
-            };
+    /// Update the LED strip with current buffer contents
+    pub fn show(&mut self) -> Result<(), Ws2812Error> {
+        let led_buffer = self.led_buffer.clone();
+        let buffer = led_buffer.lock().unwrap();
+        self.transmit(&buffer)
+    }
+
+    /// Turn the physical strip off without touching the LED buffer
+    pub fn blank(&mut self) -> Result<(), Ws2812Error> {
+        let black = vec![Color::black(); self.config.num_leds];
+        self.transmit(&black)
+    }
+
+    /// Send the LED buffer again after [`Ws2812::blank`]
+    pub fn restore(&mut self) -> Result<(), Ws2812Error> {
+        self.show()
+    }
+
+    /// Send `colors` as a frame without touching the LED buffer, e.g. frames
+    /// streamed from elsewhere. Colors past the last LED are dropped and LEDs
+    /// without a color are sent black.
+    pub fn show_colors(&mut self, colors: &[Color]) -> Result<(), Ws2812Error> {
+        let num_leds = self.config.num_leds;
+        if colors.len() == num_leds {
+            return self.transmit(colors);
         }
-        bits
+        let mut frame = colors[..colors.len().min(num_leds)].to_vec();
+        frame.resize(num_leds, Color::black());
+        self.transmit(&frame)
     }
 
-    /// Update the LED strip with current buffer contents
-    pub fn show(&mut self) -> Result<(), Ws2812Error> {
+    /// Mirror every transmitted frame to `callback`, e.g. for a web preview
+    pub fn set_frame_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&[Color]) + Send + Sync + 'static,
+    {
+        self.on_frame = Some(Box::new(callback));
+    }
+
+    /// Stop mirroring frames
+    pub fn clear_frame_callback(&mut self) {
+        self.on_frame = None;
+    }
+
+    /// Scale every LED by `brightness` (0.0 to 1.0) when sending, leaving the buffer as is
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+    }
+
+    /// Get the strip brightness
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Scale frames that would draw more than `limit` milliamps down to it,
+    /// keeping the colors' proportions; `None` removes the limit
+    pub fn set_max_current_ma(&mut self, limit: Option<u32>) {
+        self.max_current_ma = limit;
+    }
+
+    /// Get the current limit
+    pub fn max_current_ma(&self) -> Option<u32> {
+        self.max_current_ma
+    }
+
+    /// Factor the current limit scaled the last frame by, 1.0 when it fit
+    pub fn power_scale(&self) -> f32 {
+        self.power_scale
+    }
+
+    /// Estimated draw of the LED buffer with brightness and white balance, before any current limit
+    pub fn estimated_current_ma(&self) -> f32 {
         let buffer = self.led_buffer.lock().unwrap();
-        
+        self.frame_current_ma(&buffer)
+    }
+
+    fn frame_current_ma(&self, colors: &[Color]) -> f32 {
+        let white_balance = self.config.white_balance;
+        let draw: f32 = colors.iter().map(|&color| white_balance.apply(color).current_ma()).sum();
+        draw * self.brightness
+    }
+
+    /// Correct the color of every LED when sending, e.g. `WhiteBalance::NEUTRAL`
+    pub fn set_white_balance(&mut self, r: u8, g: u8, b: u8) {
+        self.config.white_balance = WhiteBalance::new(r, g, b);
+    }
+
+    /// Get the white balance correction
+    pub fn white_balance(&self) -> WhiteBalance {
+        self.config.white_balance
+    }
+
+    /// Dither the brightness scaling over successive frames, which smooths
+    /// slow fades at low brightness where 8 bits per channel visibly step
+    pub fn set_dithering(&mut self, enabled: bool) {
+        self.dithering_enabled = enabled;
+        self.dither_error.clear();
+    }
+
+    /// Encode `colors` and send them as one frame
+    fn transmit(&mut self, colors: &[Color]) -> Result<(), Ws2812Error> {
+        // Nothing to latch on a strip without LEDs
+        if colors.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(on_frame) = self.on_frame.as_mut() {
+            on_frame(colors);
+        }
+
+        self.power_scale = match self.max_current_ma {
+            Some(limit) => {
+                let draw = self.frame_current_ma(colors);
+                if draw > limit as f32 { limit as f32 / draw } else { 1.0 }
+            }
+            None => 1.0,
+        };
+        let brightness = self.brightness * self.power_scale;
+
+        let balanced = self.config.white_balance != WhiteBalance::UNCORRECTED;
+        let scaled = brightness < 1.0;
+        let dithering = scaled && self.dithering_enabled;
+        if dithering {
+            self.dither_error.resize(colors.len() * 3, 0.0);
+        }
+
         // Clear tx buffer with reset bytes
         self.tx_buffer.fill(0);
         
         // Convert LED colors to SPI bits
-        let mut bit_index = Self::RESET_BYTES_COUNT;
-        for color in buffer.iter() {
-            let grb = color.to_grb();
+        let mut bit_index = self.config.chip.reset_bytes();
+        for (i, color) in colors.iter().enumerate() {
+            let color = if balanced { self.config.white_balance.apply(*color) } else { *color };
+            let bytes = color.to_order(self.config.color_order);
             
             // Convert each color byte to SPI timing bits
-            for &byte in &grb {
-                let spi_bits = self.byte_to_spi_bits(byte);
-                self.tx_buffer[bit_index..bit_index + 8].copy_from_slice(&spi_bits);
+            for (j, &byte) in bytes.iter().enumerate() {
+                let byte = if scaled {
+                    let error = if dithering { Some(&mut self.dither_error[i * 3 + j]) } else { None };
+                    scale_channel(byte, brightness, error)
+                } else {
+                    byte
+                };
+                let spi_bits = &self.spi_bits[byte as usize];
+                self.tx_buffer[bit_index..bit_index + 8].copy_from_slice(spi_bits);
                 bit_index += 8;
             }
         }
 
         // Send data via SPI
-        let mut transfer = SpidevTransfer::write(&self.tx_buffer);
-        self.spi.transfer(&mut transfer)?;
+        self.spi.send(&self.tx_buffer)?;
 
         Ok(())
     }
@@ -289,22 +820,41 @@ impl Ws2812 {
         self.config.num_leds == 0
     }
 
+    fn check_animatable(&self) -> Result<(), Ws2812Error> {
+        if self.is_empty() {
+            return Err(Ws2812Error::ConfigError("Cannot animate a strip without LEDs".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Set the animation frame rate, used by animations started afterwards
+    pub fn set_fps(&mut self, fps: f32) -> Result<(), Ws2812Error> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(Ws2812Error::ConfigError(format!("FPS must be positive, got {}", fps)));
+        }
+        self.fps = fps;
+        Ok(())
+    }
+
+    /// Get the animation frame rate
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
     /// Start a breathing animation with the specified color and frequency
     pub fn start_breathe(&mut self, color: Color, hz: f32) -> Result<(), Ws2812Error> {
         self.stop_animation();
+        self.check_animatable()?;
 
-        let fps = 30.0;
-        let frames = (fps / hz) as usize;
-        if frames < 6 {
-            return Err(Ws2812Error::AnimationError(
-                "Frequency too high, minimum 6 frames required".to_string(),
-            ));
-        }
+        let fps = self.fps;
+        let frames = breathe_frames(fps, hz)?;
 
         let running = Arc::new(RwLock::new(true));
         let running_clone = running.clone();
         let led_buffer_clone = self.led_buffer.clone();
         let num_leds = self.config.num_leds;
+        let current_intensity = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+        let current_intensity_clone = current_intensity.clone();
 
         let handle = thread::spawn(move || {
             let mut frame = 0;
@@ -316,6 +866,7 @@ impl Ws2812 {
                 // Calculate breathing intensity using cosine wave
                 let phase = (frame as f32) * 2.0 * std::f32::consts::PI / frames as f32;
                 let intensity = (phase.cos() + 1.0) * 0.5; // 0.0 to 1.0
+                current_intensity_clone.store(intensity.to_bits(), Ordering::Relaxed);
                 
                 let scaled_color = color.scale(intensity);
                 
@@ -338,19 +889,32 @@ impl Ws2812 {
         self.animation = Some(AnimationControl {
             running,
             handle: Some(handle),
+            intensity: Some(current_intensity),
         });
 
         Ok(())
     }
 
+    /// Intensity (0.0 to 1.0) of the running breathe animation, to phase-align
+    /// other effects with it. `None` when no breathe is running.
+    pub fn current_intensity(&self) -> Option<f32> {
+        let anim = self.animation.as_ref()?;
+        if !*anim.running.read().unwrap() {
+            return None;
+        }
+        anim.intensity
+            .as_ref()
+            .map(|bits| f32::from_bits(bits.load(Ordering::Relaxed)))
+    }
+
     /// Start a chase animation with the specified color and frequency
     pub fn start_chase(&mut self, color: Color, hz: f32, clockwise: bool) -> Result<(), Ws2812Error> {
         self.stop_animation();
+        self.check_animatable()?;
 
-        let fps = 30.0;
-        let frames = (fps / hz) as usize;
-        let frames_per_led = (frames as f32 / self.config.num_leds as f32).ceil() as usize;
-        let total_frames = frames_per_led * self.config.num_leds;
+        let fps = self.fps;
+        let frames_per_led = chase_frames_per_led(fps, hz, self.config.num_leds);
+        let total_frames = frames_per_led.saturating_mul(self.config.num_leds);
 
         let running = Arc::new(RwLock::new(true));
         let running_clone = running.clone();
@@ -364,20 +928,7 @@ impl Ws2812 {
             while *running_clone.read().unwrap() {
                 let start_time = Instant::now();
                 
-                // Clear all LEDs
-                let mut colors = vec![Color::black(); num_leds];
-                
-                // Calculate which LED should be lit
-                let led_index = frame / frames_per_led;
-                let actual_index = if clockwise {
-                    (num_leds - 1) - led_index
-                } else {
-                    led_index
-                };
-                
-                if actual_index < num_leds {
-                    colors[actual_index] = color;
-                }
+                let colors = chase_frame(color, frame, frames_per_led, num_leds, clockwise);
                 
                 // Update LED buffer
                 {
@@ -398,6 +949,7 @@ impl Ws2812 {
         self.animation = Some(AnimationControl {
             running,
             handle: Some(handle),
+            intensity: None,
         });
 
         Ok(())
@@ -450,6 +1002,83 @@ mod tests {
         assert_eq!(half.b, 127);
     }
 
+    #[test]
+    fn test_color_is_black() {
+        assert!(Color::black().is_black());
+        assert!(!Color::new(0, 0, 1).is_black());
+    }
+
+    #[test]
+    fn test_color_luminance() {
+        assert_eq!(Color::black().luminance(), 0.0);
+        assert!((Color::white().luminance() - 1.0).abs() < 1e-6);
+        assert!(Color::green().luminance() > Color::red().luminance());
+        assert!(Color::red().luminance() > Color::blue().luminance());
+    }
+
+    #[test]
+    fn test_color_blend_over() {
+        let overlay = Color::new(200, 100, 0);
+        let background = Color::new(0, 100, 200);
+        assert_eq!(overlay.blend_over(background, 0.0), background);
+        assert_eq!(overlay.blend_over(background, 1.0), overlay);
+        assert_eq!(overlay.blend_over(background, 0.5), Color::new(100, 100, 100));
+        assert_eq!(overlay.blend_over(background, 2.0), overlay);
+    }
+
+    #[test]
+    fn test_color_approach_converges() {
+        let target = Color::new(250, 3, 128);
+        let mut color = Color::new(0, 255, 128);
+        for _ in 0..100 {
+            let next = color.approach(target, 10);
+            assert!(next.r <= target.r && next.g >= target.g, "overshot: {:?}", next);
+            assert_eq!(next.b, target.b);
+            color = next;
+        }
+        assert_eq!(color, target);
+    }
+
+    #[test]
+    fn test_color_approach_step() {
+        let color = Color::new(100, 100, 100);
+        assert_eq!(color.approach(Color::new(255, 0, 104), 5), Color::new(105, 95, 104));
+        assert_eq!(color.approach(Color::black(), 0), color);
+        assert_eq!(color.approach(Color::white(), 255), Color::white());
+    }
+
+    #[test]
+    fn test_color_default_is_black() {
+        assert_eq!(Color::default(), Color::black());
+    }
+
+    #[test]
+    fn test_color_conversions() {
+        let color = Color::new(255, 128, 64);
+        assert_eq!(Color::from((255, 128, 64)), color);
+        assert_eq!(Color::from([255, 128, 64]), color);
+        let rgb: [u8; 3] = color.into();
+        assert_eq!(rgb, [255, 128, 64]);
+        assert_eq!(Color::from(rgb), color);
+    }
+
+    #[test]
+    fn test_color_json_round_trip() {
+        let color = Color::new(255, 128, 0);
+        let json = serde_json::to_string(&color).unwrap();
+        assert_eq!(json, r#"{"r":255,"g":128,"b":0}"#);
+        assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), color);
+    }
+
+    #[test]
+    fn test_spi_config_json_round_trip() {
+        let config = SpiConfig::new(0, 1, 11).with_chip(StripChip::Sk6812);
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["chip"], "sk6812");
+        assert_eq!(json["color_order"], "grb");
+        assert_eq!(serde_json::from_value::<SpiConfig>(json).unwrap(), config);
+    }
+
     #[test]
     fn test_color_to_grb() {
         let color = Color::new(255, 128, 64);
@@ -462,4 +1091,438 @@ mod tests {
         let config = SpiConfig::new(1, 0, 30);
         assert_eq!(config.device_path(), "/dev/spidev1.0");
     }
+
+    #[test]
+    fn test_spi_config_from_device_path() {
+        let config = SpiConfig::from_device_path("/dev/spidev1.0", 30).unwrap();
+        assert_eq!((config.bus, config.cs, config.num_leds), (1, 0, 30));
+        let config = SpiConfig::from_device_path("/dev/spidev0.12", 5).unwrap();
+        assert_eq!((config.bus, config.cs), (0, 12));
+        assert_eq!(config.device_path(), "/dev/spidev0.12");
+    }
+
+    #[test]
+    fn test_spi_config_rejects_bad_paths() {
+        for path in ["/dev/null", "/dev/spidev", "/dev/spidev1", "/dev/spidev1.", "/dev/spidevx.0", "/dev/spidev1.0.2", "/dev/spidev300.0", "spidev1.0"] {
+            assert!(
+                matches!(SpiConfig::from_device_path(path, 30), Err(Ws2812Error::ConfigError(_))),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_spidev_options_defaults() {
+        let options = SpiConfig::new(0, 0, 30).spidev_options().unwrap();
+        assert_eq!(options.spi_mode, Some(SpiModeFlags::SPI_MODE_0));
+        assert_eq!(options.lsb_first, Some(false));
+        assert_eq!(options.bits_per_word, Some(8));
+        assert_eq!(options.max_speed_hz, Some(6_500_000));
+    }
+
+    #[test]
+    fn test_spidev_options_custom_mode() {
+        let mut config = SpiConfig::new(0, 0, 30);
+        config.mode = SpiMode::Mode1;
+        config.lsb_first = true;
+        let options = config.spidev_options().unwrap();
+        assert_eq!(options.spi_mode, Some(SpiModeFlags::SPI_MODE_1));
+        assert_eq!(options.lsb_first, Some(true));
+    }
+
+    #[test]
+    fn test_spidev_options_rejects_word_size() {
+        let mut config = SpiConfig::new(0, 0, 30);
+        config.bits_per_word = 16;
+        assert!(matches!(config.spidev_options(), Err(Ws2812Error::ConfigError(_))));
+        assert!(matches!(Ws2812::new(config), Err(Ws2812Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_clear_transmits_black_frame() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 2), Box::new(spi.clone()));
+        strip.fill(Color::red()).unwrap();
+        strip.show().unwrap();
+        strip.clear().unwrap();
+
+        let frame = spi.last_frame().unwrap();
+        let chip = StripChip::Ws2812b;
+        assert_eq!(frame.len(), chip.reset_bytes() + 2 * 24);
+        let (reset, data) = frame.split_at(chip.reset_bytes());
+        assert!(reset.iter().all(|&b| b == 0));
+        assert!(data.iter().all(|&b| b == chip.led_zero()));
+        assert_eq!(spi.frames.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_breathe_frames_scale_with_fps() {
+        assert_eq!(breathe_frames(30.0, 1.0).unwrap(), 30);
+        assert_eq!(breathe_frames(60.0, 1.0).unwrap(), 60);
+        assert_eq!(breathe_frames(60.0, 0.5).unwrap(), 2 * breathe_frames(30.0, 0.5).unwrap());
+        // The fastest breathe is 5Hz whatever the frame rate
+        assert!(breathe_frames(30.0, 5.0).is_ok());
+        assert!(breathe_frames(60.0, 5.0).is_ok());
+        assert!(breathe_frames(60.0, 6.0).is_err());
+    }
+
+    #[test]
+    fn test_set_fps() {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 1), Box::new(MockSpi::default()));
+        assert_eq!(strip.fps(), DEFAULT_FPS);
+        strip.set_fps(60.0).unwrap();
+        assert_eq!(strip.fps(), 60.0);
+        assert!(strip.set_fps(0.0).is_err());
+        assert!(strip.set_fps(f32::NAN).is_err());
+        assert_eq!(strip.fps(), 60.0);
+    }
+
+    #[test]
+    fn test_chase_lights_each_led_in_turn() {
+        let num_leds = 4;
+        let frames_per_led = chase_frames_per_led(30.0, 2.0, num_leds);
+        assert_eq!(frames_per_led, 4);
+
+        for clockwise in [false, true] {
+            let lit: Vec<usize> = (0..frames_per_led * num_leds)
+                .map(|frame| {
+                    let colors = chase_frame(Color::red(), frame, frames_per_led, num_leds, clockwise);
+                    let lit: Vec<usize> = (0..num_leds).filter(|&i| !colors[i].is_black()).collect();
+                    assert_eq!(lit.len(), 1, "frame {} clockwise {}", frame, clockwise);
+                    lit[0]
+                })
+                .collect();
+
+            for (step, frames) in lit.chunks(frames_per_led).enumerate() {
+                let expected = if clockwise { num_leds - 1 - step } else { step };
+                assert!(frames.iter().all(|&i| i == expected), "{:?} clockwise {}", lit, clockwise);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_chase_visits_every_led() {
+        assert_eq!(chase_frames_per_led(30.0, 60.0, 10), 1);
+        assert_eq!(chase_frames_per_led(30.0, 1.0, 7), 5);
+    }
+
+    #[test]
+    fn test_empty_strip_buffer_methods_are_no_ops() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 0), Box::new(spi.clone()));
+        assert!(strip.is_empty());
+        strip.fill(Color::red()).unwrap();
+        strip.set_leds(&[Color::red(), Color::blue()]).unwrap();
+        strip.load_frame(&[Color::green()]).unwrap();
+        strip.rotate(3);
+        assert!(strip.save_frame().is_empty());
+
+        strip.show().unwrap();
+        strip.blank().unwrap();
+        strip.restore().unwrap();
+        strip.clear().unwrap();
+        assert_eq!(spi.last_frame(), None);
+    }
+
+    #[test]
+    fn test_empty_strip_rejects_indexes_and_animations() {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 0), Box::new(MockSpi::default()));
+        assert!(matches!(strip.set_led(0, Color::red()), Err(Ws2812Error::ConfigError(_))));
+        assert!(matches!(strip.get_led(0), Err(Ws2812Error::ConfigError(_))));
+        assert!(matches!(strip.start_chase(Color::red(), 1.0, false), Err(Ws2812Error::ConfigError(_))));
+        assert!(matches!(strip.start_breathe(Color::red(), 0.5), Err(Ws2812Error::ConfigError(_))));
+        assert!(!strip.is_animating());
+    }
+
+    #[test]
+    fn test_current_intensity_follows_breathe() {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 1), Box::new(MockSpi::default()));
+        assert_eq!(strip.current_intensity(), None);
+
+        strip.start_breathe(Color::white(), 1.0).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let first = strip.current_intensity().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let later = strip.current_intensity().unwrap();
+        assert!((0.0..=1.0).contains(&first) && (0.0..=1.0).contains(&later));
+        assert!(later < first, "{} then {}", first, later);
+
+        strip.stop_animation();
+        assert_eq!(strip.current_intensity(), None);
+        strip.start_chase(Color::white(), 1.0, false).unwrap();
+        assert_eq!(strip.current_intensity(), None);
+    }
+
+    /// Color bytes of a frame, in wire order
+    fn decode_frame(frame: &[u8]) -> Vec<u8> {
+        frame[StripChip::Ws2812b.reset_bytes()..]
+            .chunks(8)
+            .map(|bits| bits.iter().fold(0u8, |byte, &b| (byte << 1) | (b == StripChip::Ws2812b.led_one()) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn test_brightness_scales_without_dithering() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 1), Box::new(spi.clone()));
+        strip.set_led(0, Color::new(200, 3, 255)).unwrap();
+        strip.set_brightness(0.5);
+        strip.show().unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), vec![1, 100, 127]);
+        assert_eq!(strip.get_led(0).unwrap(), Color::new(200, 3, 255));
+    }
+
+    #[test]
+    fn test_white_balance_scales_channels() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 1), Box::new(spi.clone()));
+        strip.fill(Color::white()).unwrap();
+        // 0.8 of green
+        strip.set_white_balance(255, 204, 255);
+        strip.show().unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), vec![204, 255, 255]);
+        assert_eq!(strip.get_led(0).unwrap(), Color::white());
+
+        let WhiteBalance { r, g, b } = WhiteBalance::WARM;
+        strip.set_white_balance(r, g, b);
+        strip.show().unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), vec![150, 255, 100]);
+    }
+
+    #[test]
+    fn test_current_limit_scales_frame_to_cap() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 10), Box::new(spi.clone()));
+        strip.fill(Color::white()).unwrap();
+        // 10 white LEDs draw 600mA
+        assert_eq!(strip.estimated_current_ma(), 600.0);
+        strip.set_max_current_ma(Some(300));
+        strip.show().unwrap();
+        assert!((strip.power_scale() - 0.5).abs() < 1e-6);
+
+        let bytes = decode_frame(&spi.last_frame().unwrap());
+        let sent: f32 = bytes.chunks(3).map(|grb| Color::new(grb[1], grb[0], grb[2]).current_ma()).sum();
+        // Rounding down never goes over the cap, and loses less than a level per channel
+        assert!(sent <= 300.0 && sent > 300.0 - 30.0 * MA_PER_CHANNEL / 255.0, "{}", sent);
+        assert!(bytes.iter().all(|&byte| byte == bytes[0]));
+        assert_eq!(strip.get_led(0).unwrap(), Color::white());
+
+        strip.set_max_current_ma(Some(1000));
+        strip.show().unwrap();
+        assert_eq!(strip.power_scale(), 1.0);
+    }
+
+    #[test]
+    fn test_dithering_averages_fractional_levels() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 1), Box::new(spi.clone()));
+        // Green 1 * 0.25 = 0.25, red 3 * 0.25 = 0.75, blue 10 * 0.25 = 2.5
+        strip.set_led(0, Color::new(3, 1, 10)).unwrap();
+        strip.set_brightness(0.25);
+        strip.set_dithering(true);
+
+        let frames = 100;
+        let mut sums = [0u32; 3];
+        for _ in 0..frames {
+            strip.show().unwrap();
+            for (sum, byte) in sums.iter_mut().zip(decode_frame(&spi.last_frame().unwrap())) {
+                *sum += byte as u32;
+            }
+        }
+
+        let averages: Vec<f32> = sums.iter().map(|&sum| sum as f32 / frames as f32).collect();
+        for (average, expected) in averages.iter().zip([0.25, 0.75, 2.5]) {
+            assert!((average - expected).abs() < 0.02, "{:?}", averages);
+        }
+    }
+
+    #[test]
+    fn test_frame_survives_animation() {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 3), Box::new(MockSpi::default()));
+        let original = vec![Color::red(), Color::new(1, 2, 3), Color::blue()];
+        strip.set_leds(&original).unwrap();
+
+        let frame = strip.save_frame();
+        strip.start_chase(Color::white(), 10.0, true).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        strip.stop_animation();
+        assert_ne!(strip.save_frame(), original);
+
+        strip.load_frame(&frame).unwrap();
+        assert_eq!(strip.save_frame(), original);
+    }
+
+    fn rotated(steps: isize) -> Vec<Color> {
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 4), Box::new(MockSpi::default()));
+        strip.set_leds(&[Color::red(), Color::green(), Color::blue(), Color::white()]).unwrap();
+        strip.rotate(steps);
+        strip.save_frame()
+    }
+
+    #[test]
+    fn test_rotate_toward_higher_indices() {
+        assert_eq!(rotated(1), vec![Color::white(), Color::red(), Color::green(), Color::blue()]);
+    }
+
+    #[test]
+    fn test_rotate_toward_lower_indices() {
+        assert_eq!(rotated(-1), vec![Color::green(), Color::blue(), Color::white(), Color::red()]);
+    }
+
+    #[test]
+    fn test_rotate_wraps_steps_past_length() {
+        assert_eq!(rotated(9), rotated(1));
+        assert_eq!(rotated(-6), rotated(2));
+        assert_eq!(rotated(4), rotated(0));
+    }
+
+    #[test]
+    fn test_blank_keeps_buffer() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 2), Box::new(spi.clone()));
+        strip.fill(Color::blue()).unwrap();
+        strip.show().unwrap();
+        let lit = spi.last_frame().unwrap();
+
+        strip.blank().unwrap();
+        let frame = spi.last_frame().unwrap();
+        let (reset, data) = frame.split_at(StripChip::Ws2812b.reset_bytes());
+        assert!(reset.iter().all(|&b| b == 0));
+        assert!(data.iter().all(|&b| b == StripChip::Ws2812b.led_zero()));
+        assert_eq!(strip.get_led(0).unwrap(), Color::blue());
+        assert_eq!(strip.get_led(1).unwrap(), Color::blue());
+
+        strip.restore().unwrap();
+        assert_eq!(spi.last_frame().unwrap(), lit);
+    }
+
+    #[test]
+    fn test_show_colors_sends_given_frame() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 3), Box::new(spi.clone()));
+        strip.fill(Color::white()).unwrap();
+
+        strip.show_colors(&[Color::new(1, 2, 3), Color::new(4, 5, 6), Color::new(7, 8, 9)]).unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), vec![2, 1, 3, 5, 4, 6, 8, 7, 9]);
+        assert_eq!(strip.get_led(0).unwrap(), Color::white());
+
+        // Cut to the strip's length, or padded with black
+        let colors = [Color::red(); 5];
+        strip.show_colors(&colors).unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), [0u8, 255, 0].repeat(3));
+        strip.show_colors(&[Color::blue()]).unwrap();
+        assert_eq!(decode_frame(&spi.last_frame().unwrap()), vec![0, 0, 255, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_frame_callback_receives_colors() {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let seen = frames.clone();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 3), Box::new(MockSpi::default()));
+        strip.set_frame_callback(move |colors| seen.lock().unwrap().push(colors.to_vec()));
+
+        strip.fill(Color::green()).unwrap();
+        strip.show().unwrap();
+        strip.blank().unwrap();
+        strip.clear_frame_callback();
+        strip.show().unwrap();
+
+        let frames = frames.lock().unwrap();
+        assert_eq!(*frames, vec![vec![Color::green(); 3], vec![Color::black(); 3]]);
+    }
+
+    /// The per-bit encoder the lookup table replaced
+    fn byte_to_spi_bits(chip: StripChip, byte: u8) -> [u8; 8] {
+        let mut bits = [0u8; 8];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = if (byte >> (7 - i)) & 1 == 1 {
+                chip.led_one()
+            } else {
+                chip.led_zero()
+            };
+        }
+        bits
+    }
+
+    const CHIPS: [StripChip; 3] = [StripChip::Ws2812b, StripChip::Ws2815, StripChip::Sk6812];
+
+    #[test]
+    fn test_spi_table_matches_bitwise_encoding() {
+        for chip in CHIPS {
+            for byte in 0..=255u8 {
+                assert_eq!(chip.spi_bits()[byte as usize], byte_to_spi_bits(chip, byte), "{:?} byte {:#04x}", chip, byte);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chips_select_distinct_encodings() {
+        for (i, a) in CHIPS.iter().enumerate() {
+            assert_ne!(a.led_zero(), a.led_one(), "{:?}", a);
+            for b in &CHIPS[i + 1..] {
+                assert_ne!(a.led_one(), b.led_one(), "{:?} vs {:?}", a, b);
+                assert_ne!(a.reset_bytes(), b.reset_bytes(), "{:?} vs {:?}", a, b);
+                assert_ne!(a.spi_bits(), b.spi_bits(), "{:?} vs {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chip_sets_frame_layout() {
+        let spi = MockSpi::default();
+        let config = SpiConfig::new(0, 0, 1).with_chip(StripChip::Ws2815);
+        assert_eq!(config.color_order, ColorOrder::Grb);
+        let mut strip = Ws2812::with_transport(config, Box::new(spi.clone()));
+        strip.set_led(0, Color::new(0x00, 0xFF, 0x00)).unwrap();
+        strip.show().unwrap();
+
+        let mut expected = vec![0u8; StripChip::Ws2815.reset_bytes()];
+        expected.extend([StripChip::Ws2815.led_one(); 8]);
+        expected.extend([StripChip::Ws2815.led_zero(); 16]);
+        assert_eq!(spi.last_frame().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_color_order() {
+        let color = Color::new(1, 2, 3);
+        assert_eq!(color.to_order(ColorOrder::Grb), [2, 1, 3]);
+        assert_eq!(color.to_order(ColorOrder::Rgb), [1, 2, 3]);
+    }
+
+    /// Expected SPI bytes for a string of bits written MSB first
+    fn spi_pattern(bits: &str) -> Vec<u8> {
+        bits.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| if c == '1' { StripChip::Ws2812b.led_one() } else { StripChip::Ws2812b.led_zero() })
+            .collect()
+    }
+
+    #[test]
+    fn test_show_encodes_grb_bits() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 2), Box::new(spi.clone()));
+        strip
+            .set_leds(&[Color::new(0x12, 0xA5, 0x00), Color::new(0xFF, 0x01, 0x80)])
+            .unwrap();
+        strip.show().unwrap();
+
+        let mut expected = vec![0u8; 42];
+        // Green, red, blue of the first LED, then the second
+        expected.extend(spi_pattern("10100101 00010010 00000000"));
+        expected.extend(spi_pattern("00000001 11111111 10000000"));
+        assert_eq!(expected.len(), 42 + 48);
+        assert_eq!(spi.last_frame().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_show_black_strip() {
+        let spi = MockSpi::default();
+        let mut strip = Ws2812::with_transport(SpiConfig::new(0, 0, 2), Box::new(spi.clone()));
+        strip.show().unwrap();
+
+        let mut expected = vec![0u8; 42];
+        expected.extend([StripChip::Ws2812b.led_zero(); 48]);
+        assert_eq!(spi.last_frame().unwrap(), expected);
+    }
 }